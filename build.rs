@@ -0,0 +1,71 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// With the embed_assets feature, gathers the files the examples load at run time from resources/ and
+// examples/ into OUT_DIR/embedded, for src/assets.rs to compile into the binary. Only files with the
+// extensions below are taken, so the example sources, notes and sounds stay out of the binary.
+// LEARN_OPENGL_EMBED_EXTENSIONS replaces the list, e.g. LEARN_OPENGL_EMBED_EXTENSIONS=vert,frag,png
+#[rustfmt::skip]
+const EMBED_EXTENSIONS: [&str; 21] = [
+    // shaders
+    "vert", "frag", "geom", "comp", "tesc", "tese", "vs", "fs", "gs", "glsl",
+    // images
+    "png", "jpg", "jpeg", "tga", "bmp", "hdr",
+    // models and their materials
+    "obj", "mtl", "fbx", "dae", "gltf",
+];
+
+const EXTENSIONS_ENV: &str = "LEARN_OPENGL_EMBED_EXTENSIONS";
+
+fn main() {
+    if env::var_os("CARGO_FEATURE_EMBED_ASSETS").is_none() {
+        return;
+    }
+    println!("cargo:rerun-if-changed=resources");
+    println!("cargo:rerun-if-changed=examples");
+    println!("cargo:rerun-if-env-changed={}", EXTENSIONS_ENV);
+
+    let extensions: Vec<String> = match env::var(EXTENSIONS_ENV) {
+        Ok(list) => list.split(',').map(|extension| extension.trim().to_lowercase()).collect(),
+        Err(_) => EMBED_EXTENSIONS.iter().map(|extension| extension.to_string()).collect(),
+    };
+
+    let embedded = Path::new(&env::var("OUT_DIR").unwrap()).join("embedded");
+    if embedded.exists() {
+        fs::remove_dir_all(&embedded).unwrap();
+    }
+    for root in ["resources", "examples"] {
+        let target = embedded.join(root);
+        fs::create_dir_all(&target).unwrap();
+        gather(Path::new(root), &target, &extensions).unwrap();
+    }
+}
+
+fn gather(source: &Path, target: &Path, extensions: &[String]) -> io::Result<()> {
+    if !source.is_dir() {
+        return Ok(());
+    }
+    for entry in fs::read_dir(source)? {
+        let path = entry?.path();
+        let target = target.join(path.file_name().unwrap());
+        if path.is_dir() {
+            gather(&path, &target, extensions)?;
+            continue;
+        }
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_lowercase();
+        if extensions.contains(&extension) {
+            fs::create_dir_all(target.parent().unwrap())?;
+            // a link is enough where the file system allows it, a copy otherwise
+            if fs::hard_link(&path, &target).is_err() {
+                fs::copy(&path, &target)?;
+            }
+        }
+    }
+    Ok(())
+}
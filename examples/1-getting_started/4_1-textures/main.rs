@@ -10,6 +10,7 @@ extern crate glfw;
 use glad_gl::gl;
 use glad_gl::gl::{GLint, GLsizei, GLsizeiptr, GLuint, GLvoid};
 use glfw::{Action, Context, Key};
+use learn_opengl_with_rust::assets::open_image;
 use learn_opengl_with_rust::shader_s::Shader_S;
 use learn_opengl_with_rust::{size_of_floats, size_of_uint};
 use std::mem;
@@ -137,7 +138,7 @@ fn main() {
 
         // load image, create texture and generate mipmaps
 
-        let img = image::open("resources/textures/container.jpg").expect("Texture failed to load");
+        let img = open_image("resources/textures/container.jpg").expect("Texture failed to load");
         let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
         let data = img.into_rgb8().into_raw();
 
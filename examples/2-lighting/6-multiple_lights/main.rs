@@ -12,8 +12,10 @@ use glad_gl::gl::{GLint, GLsizei, GLsizeiptr, GLuint, GLvoid};
 use glam::*;
 use glfw::{Action, Context, Key};
 use image::ColorType;
+use learn_opengl_with_rust::assets::open_image;
 use learn_opengl_with_rust::camera::{Camera, CameraMovement};
-use learn_opengl_with_rust::shader_m::Shader_M;
+use learn_opengl_with_rust::lights::{DirLight, Lights, PointLight, SpotLight};
+use learn_opengl_with_rust::shader::Shader;
 use learn_opengl_with_rust::SIZE_OF_FLOAT;
 
 const SCR_WIDTH: f32 = 800.0;
@@ -73,15 +75,17 @@ fn main() {
     // build and compile our shader programs
     // ------------------------------------
     // create shaders
-    let lightingShader = Shader_M::new(
+    let lightingShader = Shader::new(
         "examples/2-lighting/6-multiple_lights/6-multiple_lights.vert",
         "examples/2-lighting/6-multiple_lights/6-multiple_lights.frag",
+        None,
     )
     .unwrap();
 
-    let lightCubeShader = Shader_M::new(
+    let lightCubeShader = Shader::new(
         "examples/2-lighting/6-multiple_lights/6-light_cube.vert",
         "examples/2-lighting/6-multiple_lights/6-light_cube.frag",
+        None,
     )
     .unwrap();
 
@@ -158,6 +162,21 @@ fn main() {
         vec3( 0.0,  0.0, -3.0),
     ];
 
+    // directional light and point lights, the spot light follows the camera and is updated each frame
+    let mut lights = Lights {
+        dirLight: Some(DirLight::new(
+            vec3(-0.2, -1.0, -0.3),
+            vec3(0.05, 0.05, 0.05),
+            vec3(0.4, 0.4, 0.4),
+            vec3(0.5, 0.5, 0.5),
+        )),
+        pointLights: pointLightPositions
+            .iter()
+            .map(|position| PointLight::new(*position, vec3(0.05, 0.05, 0.05), vec3(0.8, 0.8, 0.8), vec3(1.0, 1.0, 1.0)))
+            .collect(),
+        spotLight: None,
+    };
+
     unsafe {
         // configure global opengl state
         // -----------------------------
@@ -225,8 +244,8 @@ fn main() {
     // shader configuration
     // --------------------
     lightingShader.use_shader();
-    lightingShader.setInt("material.diffuse", 0);
-    lightingShader.setInt("material.specular", 1);
+    lightingShader.set_int("material.diffuse", 0);
+    lightingShader.set_int("material.specular", 1);
 
     // render loop
     while !window.should_close() {
@@ -246,73 +265,34 @@ fn main() {
 
             // be sure to activate shader when setting uniforms/drawing objects
             lightingShader.use_shader();
-            lightingShader.setVec3("viewPos", &state.camera.Position);
-            lightingShader.setFloat("material.shininess", 32.0);
+            lightingShader.set_vec3("viewPos", &state.camera.Position);
+            lightingShader.set_float("material.shininess", 32.0);
 
             /*
-               Here we set all the uniforms for the 5/6 types of lights we have. We have to set them manually and index
-               the proper PointLight struct in the array to set each uniform variable. This can be done more code-friendly
-               by defining light types as classes and set their values in there, or by using a more efficient uniform approach
-               by using 'Uniform buffer objects', but that is something we'll discuss in the 'Advanced GLSL' tutorial.
+               Here we set all the uniforms for the 5/6 types of lights we have. The light structs mirror the glsl
+               structs field by field, so Lights::upload can set every member of dirLight, pointLights[i] and
+               spotLight by name instead of building each uniform name by hand.
             */
-            // directional light
-            lightingShader.setVec3_xyz("dirLight.direction", -0.2, -1.0, -0.3);
-            lightingShader.setVec3_xyz("dirLight.ambient", 0.05, 0.05, 0.05);
-            lightingShader.setVec3_xyz("dirLight.diffuse", 0.4, 0.4, 0.4);
-            lightingShader.setVec3_xyz("dirLight.specular", 0.5, 0.5, 0.5);
-            // point light 1
-            lightingShader.setVec3("pointLights[0].position", &pointLightPositions[0]);
-            lightingShader.setVec3_xyz("pointLights[0].ambient", 0.05, 0.05, 0.05);
-            lightingShader.setVec3_xyz("pointLights[0].diffuse", 0.8, 0.8, 0.8);
-            lightingShader.setVec3_xyz("pointLights[0].specular", 1.0, 1.0, 1.0);
-            lightingShader.setFloat("pointLights[0].constant", 1.0);
-            lightingShader.setFloat("pointLights[0].linear", 0.09);
-            lightingShader.setFloat("pointLights[0].quadratic", 0.032);
-            // point light 2
-            lightingShader.setVec3("pointLights[1].position", &pointLightPositions[1]);
-            lightingShader.setVec3_xyz("pointLights[1].ambient", 0.05, 0.05, 0.05);
-            lightingShader.setVec3_xyz("pointLights[1].diffuse", 0.8, 0.8, 0.8);
-            lightingShader.setVec3_xyz("pointLights[1].specular", 1.0, 1.0, 1.0);
-            lightingShader.setFloat("pointLights[1].constant", 1.0);
-            lightingShader.setFloat("pointLights[1].linear", 0.09);
-            lightingShader.setFloat("pointLights[1].quadratic", 0.032);
-            // point light 3
-            lightingShader.setVec3("pointLights[2].position", &pointLightPositions[2]);
-            lightingShader.setVec3_xyz("pointLights[2].ambient", 0.05, 0.05, 0.05);
-            lightingShader.setVec3_xyz("pointLights[2].diffuse", 0.8, 0.8, 0.8);
-            lightingShader.setVec3_xyz("pointLights[2].specular", 1.0, 1.0, 1.0);
-            lightingShader.setFloat("pointLights[2].constant", 1.0);
-            lightingShader.setFloat("pointLights[2].linear", 0.09);
-            lightingShader.setFloat("pointLights[2].quadratic", 0.032);
-            // point light 4
-            lightingShader.setVec3("pointLights[3].position", &pointLightPositions[3]);
-            lightingShader.setVec3_xyz("pointLights[3].ambient", 0.05, 0.05, 0.05);
-            lightingShader.setVec3_xyz("pointLights[3].diffuse", 0.8, 0.8, 0.8);
-            lightingShader.setVec3_xyz("pointLights[3].specular", 1.0, 1.0, 1.0);
-            lightingShader.setFloat("pointLights[3].constant", 1.0);
-            lightingShader.setFloat("pointLights[3].linear", 0.09);
-            lightingShader.setFloat("pointLights[3].quadratic", 0.032);
-            // spotLight
-            lightingShader.setVec3("spotLight.position", &state.camera.Position);
-            lightingShader.setVec3("spotLight.direction", &state.camera.Front);
-            lightingShader.setVec3_xyz("spotLight.ambient", 0.0, 0.0, 0.0);
-            lightingShader.setVec3_xyz("spotLight.diffuse", 1.0, 1.0, 1.0);
-            lightingShader.setVec3_xyz("spotLight.specular", 1.0, 1.0, 1.0);
-            lightingShader.setFloat("spotLight.constant", 1.0);
-            lightingShader.setFloat("spotLight.linear", 0.09);
-            lightingShader.setFloat("spotLight.quadratic", 0.032);
-            lightingShader.setFloat("spotLight.cutOff", 12.5f32.to_radians().cos());
-            lightingShader.setFloat("spotLight.outerCutOff", 15.0f32.to_radians().cos());
+            lights.spotLight = Some(SpotLight::new(
+                state.camera.Position,
+                state.camera.Front,
+                12.5,
+                15.0,
+                vec3(0.0, 0.0, 0.0),
+                vec3(1.0, 1.0, 1.0),
+                vec3(1.0, 1.0, 1.0),
+            ));
+            lights.upload(&lightingShader);
 
             // view/projection transformations
             let projection = Mat4::perspective_rh_gl(state.camera.Zoom.to_radians(), SCR_WIDTH / SCR_HEIGHT, 0.1, 100.0);
             let view = state.camera.GetViewMatrix();
-            lightingShader.setMat4("projection", &projection);
-            lightingShader.setMat4("view", &view);
+            lightingShader.set_mat4("projection", &projection);
+            lightingShader.set_mat4("view", &view);
 
             // world transformation
             let model = Mat4::IDENTITY;
-            lightingShader.setMat4("model", &model);
+            lightingShader.set_mat4("model", &model);
 
             // bind diffuse map
             gl::ActiveTexture(gl::TEXTURE0);
@@ -330,22 +310,22 @@ fn main() {
                 let mut model = Mat4::from_translation(*cube_pos);
                 let angle = (20.0 * i as f32).to_radians();
                 model = model * Mat4::from_axis_angle(Vec3::new(1.0, 0.3, 0.5), angle);
-                lightingShader.setMat4("model", &model);
+                lightingShader.set_mat4("model", &model);
 
                 gl::DrawArrays(gl::TRIANGLES, 0, 36);
             }
 
             // also draw the lamp objects
             lightCubeShader.use_shader();
-            lightCubeShader.setMat4("projection", &projection);
-            lightCubeShader.setMat4("view", &view);
+            lightCubeShader.set_mat4("projection", &projection);
+            lightCubeShader.set_mat4("view", &view);
 
             // we now draw as many light bulbs as we have point lights.
             gl::BindVertexArray(lightCubeVAO);
             for pointPos in pointLightPositions {
                 let mut model = Mat4::from_translation(pointPos);
                 model *= Mat4::from_scale(vec3(0.2, 0.2, 0.2));
-                lightCubeShader.setMat4("model", &model);
+                lightCubeShader.set_mat4("model", &model);
                 gl::DrawArrays(gl::TRIANGLES, 0, 36);
             }
         }
@@ -432,7 +412,7 @@ fn scroll_handler(state: &mut State, _xoffset: f64, yoffset: f64) {
 fn loadTexture(path: &str) -> GLuint {
     let mut texture_id: GLuint = 0;
 
-    let img = image::open(path).expect("Texture failed to load");
+    let img = open_image(path).expect("Texture failed to load");
     let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
 
     let color_type = img.color();
@@ -12,9 +12,11 @@ use glad_gl::gl::{GLint, GLsizei, GLuint, GLvoid};
 use glam::*;
 use glfw::{Action, Context, Key};
 use image::ColorType;
+use learn_opengl_with_rust::assets::open_image;
+use learn_opengl_with_rust::batch::StaticBatch;
 use learn_opengl_with_rust::camera::{Camera, CameraMovement};
 use learn_opengl_with_rust::model::{FlipV, Gamma, Model};
-use learn_opengl_with_rust::shader_m::Shader_M;
+use learn_opengl_with_rust::shader::Shader;
 
 const SCR_WIDTH: f32 = 800.0;
 const SCR_HEIGHT: f32 = 800.0;
@@ -54,9 +56,10 @@ fn main() {
     let camera = Camera::camera_vec3(vec3(0.0, 0.5, 4.0));
 
     // build and compile our shaders
-    let ourShader = Shader_M::new(
+    let ourShader = Shader::new(
         "examples/3-model_loading/1-model_loading/1-model_loading.vert",
         "examples/3-model_loading/1-model_loading/1-model_loading.frag",
+        None,
     )
     .unwrap();
 
@@ -82,6 +85,11 @@ fn main() {
     // let ourModel = Model::new("/Users/john/Dev_Rust/Repos/russimp/models/OBJ/cube.obj", Gamma(false), FlipV(false));
     // let ourModel = Model::new("/Users/john/Dev_Rust/Dev/Models/Oyanirami0.3ds", Gamma(false), FlipV(false));
 
+    // the model's meshes merged into one per material, drawn with a draw call each. It looks the same as
+    // drawing the model, with fewer draw calls for models of many meshes sharing textures.
+    let batch = StaticBatch::from_model(&ourModel);
+    println!("cyborg: {}", batch.report);
+
     // render loop
     while !window.should_close() {
         let currentFrame = glfw.get_time() as f32;
@@ -104,14 +112,14 @@ fn main() {
             // view/projection transformations
             let projection = Mat4::perspective_rh_gl(state.camera.Zoom.to_radians(), SCR_WIDTH / SCR_HEIGHT, 0.1, 100.0);
             let view = state.camera.GetViewMatrix();
-            ourShader.setMat4("projection", &projection);
-            ourShader.setMat4("view", &view);
+            ourShader.set_mat4("projection", &projection);
+            ourShader.set_mat4("view", &view);
 
             let mut model = Mat4::from_translation(vec3(0.0, 0.0, 0.0));
             model = model * Mat4::from_scale(vec3(1.0, 1.0, 1.0));
-            ourShader.setMat4("model", &model);
+            ourShader.set_mat4("model", &model);
 
-            ourModel.Draw(ourShader.id);
+            batch.draw(&ourShader);
         }
 
         window.swap_buffers();
@@ -191,7 +199,7 @@ fn scroll_handler(state: &mut State, _xoffset: f64, yoffset: f64) {
 fn loadTexture(path: &str) -> GLuint {
     let mut texture_id: GLuint = 0;
 
-    let img = image::open(path).expect("Texture failed to load");
+    let img = open_image(path).expect("Texture failed to load");
     let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
 
     let color_type = img.color();
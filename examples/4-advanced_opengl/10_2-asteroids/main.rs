@@ -140,12 +140,12 @@ fn main() {
             let mut model = Mat4::from_translation(vec3(0.0, -3.0, 0.0));
             model = model * Mat4::from_scale(vec3(4.0, 4.0, 4.0));
             shader.set_mat4("model", &model);
-            planet.Draw(shader.id);
+            planet.draw(&shader);
 
             // draw meteorites
             for model in &modelMatrices {
                 shader.set_mat4("model", &model);
-                rock.Draw(shader.id);
+                rock.draw(&shader);
             }
         }
 
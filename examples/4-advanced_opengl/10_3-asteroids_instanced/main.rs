@@ -9,14 +9,14 @@
 extern crate glfw;
 
 use glad_gl::gl;
-use glad_gl::gl::{GLsizei, GLsizeiptr, GLuint, GLvoid};
-use glam::{vec3, Mat4, Vec4};
+use glam::{vec3, Mat4};
 use glfw::{Action, Context, Key};
 use learn_opengl_with_rust::camera::{Camera, CameraMovement};
+use learn_opengl_with_rust::lod::{LodChain, LodInstances, LodSelector};
 use learn_opengl_with_rust::model::{FlipV, Gamma, Model};
 use learn_opengl_with_rust::shader::Shader;
+use learn_opengl_with_rust::stream_buffer::BufferUsage;
 use rand::prelude::*;
-use std::mem;
 
 const SCR_WIDTH: f32 = 800.0;
 const SCR_HEIGHT: f32 = 800.0;
@@ -88,6 +88,8 @@ fn main() {
     // load models
     // -----------
     let rock = Model::new("resources/objects/rock/rock.obj", Gamma(false), FlipV(false));
+    // the rock with a half, a quarter and a tenth of its triangles for the rocks further away
+    let rocks = LodChain::generate(rock, &[0.5, 0.25, 0.1]);
     let planet = Model::new("resources/objects/planet/planet.obj", Gamma(false), FlipV(false));
 
     // generate a large list of semi-random model transformation matrices
@@ -121,67 +123,12 @@ fn main() {
         modelMatrices.push(model);
     }
 
-    // Buffer
-    let mut instanceVBO: GLuint = 0;
-
-    unsafe {
-        // configure instanced array
-        // -------------------------
-        gl::GenBuffers(1, &mut instanceVBO);
-        gl::BindBuffer(gl::ARRAY_BUFFER, instanceVBO);
-        gl::BufferData(
-            gl::ARRAY_BUFFER,
-            (mem::size_of::<Mat4>() * amount as usize) as GLsizeiptr,
-            modelMatrices.as_ptr() as *const GLvoid,
-            gl::STATIC_DRAW,
-        );
-
-        // set transformation matrices as an instance vertex attribute (with divisor 1)
-        // note: we're cheating a little by taking the, now publicly declared, VAO of the model's mesh(es) and adding new vertexAttribPointers
-        // normally you'd want to do this in a more organized fashion, but for learning purposes this will do.
-        // -----------------------------------------------------------------------------------------------------------------------------------
-        for mesh in &rock.meshes {
-            let VAO = mesh.VAO;
-            gl::BindVertexArray(VAO);
-            // set attribute pointers for matrix (4 times vec4)
-            gl::EnableVertexAttribArray(3);
-            gl::VertexAttribPointer(3, 4, gl::FLOAT, gl::FALSE, mem::size_of::<Mat4>() as GLsizei, 0 as *const GLvoid);
-            gl::EnableVertexAttribArray(4);
-            gl::VertexAttribPointer(
-                4,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                mem::size_of::<Mat4>() as GLsizei,
-                mem::size_of::<Vec4>() as *const GLvoid,
-            );
-            gl::EnableVertexAttribArray(3);
-            gl::VertexAttribPointer(
-                5,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                mem::size_of::<Mat4>() as GLsizei,
-                (2 * mem::size_of::<Vec4>()) as *const GLvoid,
-            );
-            gl::EnableVertexAttribArray(6);
-            gl::VertexAttribPointer(
-                6,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                mem::size_of::<Mat4>() as GLsizei,
-                (3 * mem::size_of::<Vec4>()) as *const GLvoid,
-            );
-
-            gl::VertexAttribDivisor(3, 1);
-            gl::VertexAttribDivisor(4, 1);
-            gl::VertexAttribDivisor(5, 1);
-            gl::VertexAttribDivisor(6, 1);
-
-            gl::BindVertexArray(0);
-        }
-    }
+    // configure instanced arrays
+    // --------------------------
+    // the transformation matrices go to the shader as the instance attribute aInstanceMatrix at location 3,
+    // taking the place of the tangent and bitangent attributes of the rock's meshes, which the shader doesn't read.
+    // Each frame they are sorted into a buffer per level of detail.
+    let mut instances: LodInstances<Mat4> = LodInstances::new(&rocks, 3, BufferUsage::Stream);
 
     // render loop
     while !window.should_close() {
@@ -215,19 +162,13 @@ fn main() {
             let mut model = Mat4::from_translation(vec3(0.0, -3.0, 0.0));
             model = model * Mat4::from_scale(vec3(4.0, 4.0, 4.0));
             planetShader.set_mat4("model", &model);
-            planet.Draw(planetShader.id);
+            planet.draw(&planetShader);
 
-            // draw meteorites
+            // draw meteorites, at the level of detail where the simplification is under a pixel on screen
+            let selector = LodSelector::new(&state.camera, SCR_HEIGHT, 1.0);
+            instances.assign(&selector, &rocks, &modelMatrices, |model| *model);
             asteroidShader.use_shader();
-            asteroidShader.set_int("texture_diffuse1", 0);
-            gl::ActiveTexture(gl::TEXTURE0);
-            gl::BindTexture(gl::TEXTURE_2D, rock.textures_loaded[0].id);
-
-            for mesh in &rock.meshes {
-                gl::BindVertexArray(mesh.VAO);
-                gl::DrawElementsInstanced(gl::TRIANGLES, mesh.indices.len() as GLsizei, gl::UNSIGNED_INT, 0 as *const GLvoid, amount);
-                gl::BindVertexArray(0);
-            }
+            instances.draw(&rocks, &asteroidShader);
         }
 
         window.swap_buffers();
@@ -13,6 +13,7 @@ use glad_gl::gl::{GLint, GLsizei, GLsizeiptr, GLuint, GLvoid};
 use glam::{vec3, Mat3, Mat4};
 use glfw::{Action, Context, Key};
 use image::ColorType;
+use learn_opengl_with_rust::assets::open_image;
 use learn_opengl_with_rust::camera::{Camera, CameraMovement};
 use learn_opengl_with_rust::shader_m::Shader_M;
 use learn_opengl_with_rust::{size_of_floats, SIZE_OF_FLOAT};
@@ -370,7 +371,7 @@ fn scroll_handler(state: &mut State, _xoffset: f64, yoffset: f64) {
 fn loadTexture(path: &str) -> GLuint {
     let mut texture_id: GLuint = 0;
 
-    let img = image::open(path).expect("Texture failed to load");
+    let img = open_image(path).expect("Texture failed to load");
     let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
     let color_type = img.color();
 
@@ -433,7 +434,7 @@ pub fn loadCubemap(faces: Vec<&str>) -> u32 {
         gl::BindTexture(gl::TEXTURE_CUBE_MAP, textureID);
 
         for (i, path) in faces.iter().enumerate() {
-            let img = image::open(path).expect("Texture failed to load");
+            let img = open_image(path).expect("Texture failed to load");
             let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
             let color_type = img.color();
 
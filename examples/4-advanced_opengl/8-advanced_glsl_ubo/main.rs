@@ -9,18 +9,28 @@
 extern crate glfw;
 
 use glad_gl::gl;
-use glad_gl::gl::{GLint, GLintptr, GLsizei, GLsizeiptr, GLuint, GLvoid};
+use glad_gl::gl::{GLint, GLsizei, GLsizeiptr, GLuint, GLvoid};
 use glam::{vec3, Mat4};
 use glfw::{Action, Context, Key};
 use image::ColorType;
+use learn_opengl_with_rust::assets::open_image;
 use learn_opengl_with_rust::camera::{Camera, CameraMovement};
-use learn_opengl_with_rust::shader_m::Shader_M;
-use learn_opengl_with_rust::{c_string, size_of_floats, SIZE_OF_FLOAT};
+use learn_opengl_with_rust::shader::Shader;
+use learn_opengl_with_rust::uniform_buffer::UniformBuffer;
+use learn_opengl_with_rust::{size_of_floats, std140_struct, SIZE_OF_FLOAT};
 use std::mem;
 
 const SCR_WIDTH: f32 = 800.0;
 const SCR_HEIGHT: f32 = 800.0;
 
+// matches the Matrices uniform block in 8-advanced_glsl.vert
+std140_struct! {
+    struct Matrices {
+        projection: Mat4,
+        view: Mat4,
+    }
+}
+
 struct State {
     camera: Camera,
     deltaTime: f32,
@@ -65,24 +75,28 @@ fn main() {
 
     // build and compile our shader program
     // ------------------------------------
-    let shaderRed = Shader_M::new(
+    let shaderRed = Shader::new(
         "examples/4-advanced_opengl/8-advanced_glsl_ubo/8-advanced_glsl.vert",
         "examples/4-advanced_opengl/8-advanced_glsl_ubo/8-red.frag",
+        None,
     )
     .unwrap();
-    let shaderGreen = Shader_M::new(
+    let shaderGreen = Shader::new(
         "examples/4-advanced_opengl/8-advanced_glsl_ubo/8-advanced_glsl.vert",
         "examples/4-advanced_opengl/8-advanced_glsl_ubo/8-green.frag",
+        None,
     )
     .unwrap();
-    let shaderBlue = Shader_M::new(
+    let shaderBlue = Shader::new(
         "examples/4-advanced_opengl/8-advanced_glsl_ubo/8-advanced_glsl.vert",
         "examples/4-advanced_opengl/8-advanced_glsl_ubo/8-blue.frag",
+        None,
     )
     .unwrap();
-    let shaderYellow = Shader_M::new(
+    let shaderYellow = Shader::new(
         "examples/4-advanced_opengl/8-advanced_glsl_ubo/8-advanced_glsl.vert",
         "examples/4-advanced_opengl/8-advanced_glsl_ubo/8-yellow.frag",
+        None,
     )
     .unwrap();
 
@@ -137,9 +151,6 @@ fn main() {
     // Vertex Array Object id
     let mut cubeVAO: GLuint = 0;
     let mut cubeVBO: GLuint = 0;
-    let mut uboMatrices: GLuint = 0;
-
-    let size_of_mat4 = mem::size_of::<[f32; 16]>();
 
     unsafe {
         // configure global opengl state
@@ -162,40 +173,24 @@ fn main() {
 
         // configure a uniform buffer object
         // ---------------------------------
-        // first. We get the relevant block indices
-        let c_string = c_string!("Matrices");
-        let uniformBlockIndexRed = gl::GetUniformBlockIndex(shaderRed.id, c_string.as_ptr());
-        let uniformBlockIndexGreen = gl::GetUniformBlockIndex(shaderGreen.id, c_string.as_ptr());
-        let uniformBlockIndexBlue = gl::GetUniformBlockIndex(shaderBlue.id, c_string.as_ptr());
-        let uniformBlockIndexYellow = gl::GetUniformBlockIndex(shaderYellow.id, c_string.as_ptr());
-
-        // then we link each shader's uniform block to this uniform binding point
-        gl::UniformBlockBinding(shaderRed.id, uniformBlockIndexRed, 0);
-        gl::UniformBlockBinding(shaderGreen.id, uniformBlockIndexGreen, 0);
-        gl::UniformBlockBinding(shaderBlue.id, uniformBlockIndexBlue, 0);
-        gl::UniformBlockBinding(shaderYellow.id, uniformBlockIndexYellow, 0);
-
-        // Now actually create the buffer
-        gl::GenBuffers(1, &mut uboMatrices);
-        gl::BindBuffer(gl::UNIFORM_BUFFER, uboMatrices);
-        gl::BufferData(gl::UNIFORM_BUFFER, (2 * size_of_mat4) as GLsizeiptr, std::ptr::null(), gl::STATIC_DRAW);
-        gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
-
-        // define the range of the buffer that links to a uniform binding point
-        gl::BindBufferRange(gl::UNIFORM_BUFFER, 0, uboMatrices, 0, (2 * size_of_mat4) as GLsizeiptr);
-
-        // store the projection matrix (we only do this once now) (note: we're not using zoom anymore by changing the FoV)
-        let projection = Mat4::perspective_rh_gl(45.0, SCR_WIDTH / SCR_HEIGHT, 0.1, 100.0);
-        gl::BindBuffer(gl::UNIFORM_BUFFER, uboMatrices);
-        gl::BufferSubData(
-            gl::UNIFORM_BUFFER,
-            0,
-            size_of_mat4 as GLsizeiptr,
-            projection.to_cols_array().as_ptr() as *const GLvoid,
-        );
-        gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        // first. We link each shader's uniform block to this uniform binding point, checking that the
+        // block the shader declares has the same layout size as our Matrices struct
+        shaderRed.bind_uniform_block::<Matrices>("Matrices", 0).unwrap();
+        shaderGreen.bind_uniform_block::<Matrices>("Matrices", 0).unwrap();
+        shaderBlue.bind_uniform_block::<Matrices>("Matrices", 0).unwrap();
+        shaderYellow.bind_uniform_block::<Matrices>("Matrices", 0).unwrap();
     }
 
+    // Now actually create the buffer and attach it to the binding point
+    let uboMatrices: UniformBuffer<Matrices> = UniformBuffer::new();
+    uboMatrices.bind(0);
+
+    // store the projection matrix (note: we're not using zoom anymore by changing the FoV)
+    let mut matrices = Matrices {
+        projection: Mat4::perspective_rh_gl(45.0, SCR_WIDTH / SCR_HEIGHT, 0.1, 100.0),
+        view: Mat4::IDENTITY,
+    };
+
     // render loop
     while !window.should_close() {
         let currentFrame = glfw.get_time() as f32;
@@ -212,44 +207,37 @@ fn main() {
             gl::ClearColor(0.1, 0.1, 0.1, 1.0);
             gl::Clear(gl::COLOR_BUFFER_BIT | gl::DEPTH_BUFFER_BIT);
 
-            // set the view matrix in the sub data block - we only have to do this once per loop iteration.
-            let view = state.camera.GetViewMatrix();
-            gl::BindBuffer(gl::UNIFORM_BUFFER, uboMatrices);
-            gl::BufferSubData(
-                gl::UNIFORM_BUFFER,
-                size_of_mat4 as GLintptr,
-                size_of_mat4 as GLsizeiptr,
-                view.to_cols_array().as_ptr() as *const GLvoid,
-            );
-            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            // set the view matrix in the uniform block - we only have to do this once per loop iteration.
+            matrices.view = state.camera.GetViewMatrix();
+            uboMatrices.upload(&matrices);
 
             // draw 4 cubes
             // RED
             gl::BindVertexArray(cubeVAO);
             shaderRed.use_shader();
             let model = Mat4::from_translation(vec3(-0.75, 0.75, 0.0)); // move top-left
-            shaderRed.setMat4("model", &model);
+            shaderRed.set_mat4("model", &model);
             gl::DrawArrays(gl::TRIANGLES, 0, 36);
 
             // GREEN
             gl::BindVertexArray(cubeVAO);
             shaderGreen.use_shader();
             let model = Mat4::from_translation(vec3(0.75, 0.75, 0.0)); // move top-left
-            shaderGreen.setMat4("model", &model);
+            shaderGreen.set_mat4("model", &model);
             gl::DrawArrays(gl::TRIANGLES, 0, 36);
 
             // YELLOW
             gl::BindVertexArray(cubeVAO);
             shaderYellow.use_shader();
             let model = Mat4::from_translation(vec3(-0.75, -0.75, 0.0)); // move top-left
-            shaderYellow.setMat4("model", &model);
+            shaderYellow.set_mat4("model", &model);
             gl::DrawArrays(gl::TRIANGLES, 0, 36);
 
             // BLUE
             gl::BindVertexArray(cubeVAO);
             shaderBlue.use_shader();
             let model = Mat4::from_translation(vec3(0.75, -0.75, 0.0)); // move top-left
-            shaderBlue.setMat4("model", &model);
+            shaderBlue.set_mat4("model", &model);
             gl::DrawArrays(gl::TRIANGLES, 0, 36);
         }
 
@@ -336,7 +324,7 @@ fn scroll_handler(state: &mut State, _xoffset: f64, yoffset: f64) {
 fn loadTexture(path: &str) -> GLuint {
     let mut texture_id: GLuint = 0;
 
-    let img = image::open(path).expect("Texture failed to load");
+    let img = open_image(path).expect("Texture failed to load");
     let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
     let color_type = img.color();
 
@@ -399,7 +387,7 @@ pub fn loadCubemap(faces: Vec<&str>) -> u32 {
         gl::BindTexture(gl::TEXTURE_CUBE_MAP, textureID);
 
         for (i, path) in faces.iter().enumerate() {
-            let img = image::open(path).expect("Texture failed to load");
+            let img = open_image(path).expect("Texture failed to load");
             let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
             let color_type = img.color();
 
@@ -13,6 +13,7 @@ use glad_gl::gl::{GLint, GLsizei, GLuint, GLvoid};
 use glam::{vec3, Mat4};
 use glfw::{Action, Context, Key};
 use image::ColorType;
+use learn_opengl_with_rust::assets::open_image;
 use learn_opengl_with_rust::camera::{Camera, CameraMovement};
 use learn_opengl_with_rust::model::{FlipV, Gamma, Model};
 use learn_opengl_with_rust::shader::Shader;
@@ -105,7 +106,7 @@ fn main() {
 
             shader.set_float("time", currentFrameTime);
 
-            nanosuit.Draw(shader.id);
+            nanosuit.draw(&shader);
         }
 
         window.swap_buffers();
@@ -192,7 +193,7 @@ fn scroll_handler(state: &mut State, _xoffset: f64, yoffset: f64) {
 fn loadTexture(path: &str) -> GLuint {
     let mut texture_id: GLuint = 0;
 
-    let img = image::open(path).expect("Texture failed to load");
+    let img = open_image(path).expect("Texture failed to load");
     let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
 
     let color_type = img.color();
@@ -13,6 +13,7 @@ use glad_gl::gl::{GLint, GLsizei, GLuint, GLvoid};
 use glam::{vec3, Mat4};
 use glfw::{Action, Context, Key};
 use image::ColorType;
+use learn_opengl_with_rust::assets::open_image;
 use learn_opengl_with_rust::camera::{Camera, CameraMovement};
 use learn_opengl_with_rust::model::{FlipV, Gamma, Model};
 use learn_opengl_with_rust::shader::Shader;
@@ -110,14 +111,14 @@ fn main() {
             shader.set_mat4("view", &view);
             shader.set_mat4("model", &Mat4::IDENTITY);
 
-            backpack.Draw(shader.id);
+            backpack.draw(&shader);
 
             normalShader.use_shader();
             normalShader.set_mat4("projection", &projection);
             normalShader.set_mat4("view", &view);
             normalShader.set_mat4("model", &Mat4::IDENTITY);
 
-            backpack.Draw(normalShader.id);
+            backpack.draw(&normalShader);
         }
 
         window.swap_buffers();
@@ -204,7 +205,7 @@ fn scroll_handler(state: &mut State, _xoffset: f64, yoffset: f64) {
 fn loadTexture(path: &str) -> GLuint {
     let mut texture_id: GLuint = 0;
 
-    let img = image::open(path).expect("Texture failed to load");
+    let img = open_image(path).expect("Texture failed to load");
     let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
 
     let color_type = img.color();
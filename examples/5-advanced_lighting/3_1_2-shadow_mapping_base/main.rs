@@ -13,7 +13,10 @@ use glad_gl::gl::{GLint, GLsizei, GLsizeiptr, GLuint, GLvoid};
 use glam::{vec3, Mat4};
 use glfw::{Action, Context, Key};
 use image::ColorType;
+use learn_opengl_with_rust::assets::open_image;
 use learn_opengl_with_rust::camera::{Camera, CameraMovement};
+use learn_opengl_with_rust::mesh::Mesh;
+use learn_opengl_with_rust::primitives;
 use learn_opengl_with_rust::shader::Shader;
 use learn_opengl_with_rust::SIZE_OF_FLOAT;
 use std::ffi::c_uint;
@@ -105,7 +108,7 @@ fn main() {
     // Vertex Array Object id
     let mut planeVAO: GLuint = 0;
     let mut planeVBO: GLuint = 0;
-    let mut cubeVAO: GLuint = 0;
+    let cube = primitives::cube();
     #[allow(unused_variables, unused_mut)]
     let mut quadVAO: GLuint = 0;
     let mut depthMapFBO: GLuint = 0;
@@ -232,7 +235,7 @@ fn main() {
             gl::Clear(gl::DEPTH_BUFFER_BIT);
             gl::ActiveTexture(gl::TEXTURE0);
             gl::BindTexture(gl::TEXTURE_2D, woodTexture);
-            renderScene(&simpleDepthShader, planeVAO, &mut cubeVAO);
+            renderScene(&simpleDepthShader, planeVAO, &cube);
             gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
 
             // reset viewport
@@ -254,7 +257,7 @@ fn main() {
             gl::BindTexture(gl::TEXTURE_2D, woodTexture);
             gl::ActiveTexture(gl::TEXTURE1);
             gl::BindTexture(gl::TEXTURE_2D, depthMap);
-            renderScene(&shader, planeVAO, &mut cubeVAO);
+            renderScene(&shader, planeVAO, &cube);
 
             // render Depth map to quad for visual debugging
             // ---------------------------------------------
@@ -281,7 +284,7 @@ fn main() {
 
 // renders the 3D scene
 // --------------------
-fn renderScene(shader: &Shader, planeVAO: GLuint, cubeVAO: &mut GLuint) {
+fn renderScene(shader: &Shader, planeVAO: GLuint, cube: &Mesh) {
     // floor
     let model = Mat4::IDENTITY;
     shader.set_mat4("model", &model);
@@ -294,113 +297,18 @@ fn renderScene(shader: &Shader, planeVAO: GLuint, cubeVAO: &mut GLuint) {
     let mut model = Mat4::from_translation(vec3(0.0, 1.5, 0.0));
     model *= Mat4::from_scale(vec3(0.5, 0.5, 0.5));
     shader.set_mat4("model", &model);
-    renderCube(cubeVAO);
+    cube.draw(shader);
 
     let mut model = Mat4::from_translation(vec3(2.0, 0.0, 1.0));
     model *= Mat4::from_scale(vec3(0.5, 0.5, 0.5));
     shader.set_mat4("model", &model);
-    renderCube(cubeVAO);
+    cube.draw(shader);
 
     let mut model = Mat4::from_translation(vec3(-1.0, 0.0, 2.0));
     model *= Mat4::from_axis_angle(vec3(1.0, 0.0, 1.0).normalize(), 60.0f32.to_radians());
     model *= Mat4::from_scale(vec3(0.25, 0.25, 0.25));
     shader.set_mat4("model", &model);
-    renderCube(cubeVAO);
-}
-
-fn renderCube(cubeVAO: &mut GLuint) {
-    // initialize (if necessary)
-    if *cubeVAO == 0 {
-        #[rustfmt::skip]
-        let vertices: [f32; 288] = [
-            // back face
-            -1.0, -1.0, -1.0,  0.0,  0.0, -1.0, 0.0, 0.0, // bottom-left
-             1.0,  1.0, -1.0,  0.0,  0.0, -1.0, 1.0, 1.0, // top-right
-             1.0, -1.0, -1.0,  0.0,  0.0, -1.0, 1.0, 0.0, // bottom-right         
-             1.0,  1.0, -1.0,  0.0,  0.0, -1.0, 1.0, 1.0, // top-right
-            -1.0, -1.0, -1.0,  0.0,  0.0, -1.0, 0.0, 0.0, // bottom-left
-            -1.0,  1.0, -1.0,  0.0,  0.0, -1.0, 0.0, 1.0, // top-left
-            // front face
-            -1.0, -1.0,  1.0,  0.0,  0.0,  1.0, 0.0, 0.0, // bottom-left
-             1.0, -1.0,  1.0,  0.0,  0.0,  1.0, 1.0, 0.0, // bottom-right
-             1.0,  1.0,  1.0,  0.0,  0.0,  1.0, 1.0, 1.0, // top-right
-             1.0,  1.0,  1.0,  0.0,  0.0,  1.0, 1.0, 1.0, // top-right
-            -1.0,  1.0,  1.0,  0.0,  0.0,  1.0, 0.0, 1.0, // top-left
-            -1.0, -1.0,  1.0,  0.0,  0.0,  1.0, 0.0, 0.0, // bottom-left
-            // left face
-            -1.0,  1.0,  1.0, -1.0,  0.0,  0.0, 1.0, 0.0, // top-right
-            -1.0,  1.0, -1.0, -1.0,  0.0,  0.0, 1.0, 1.0, // top-left
-            -1.0, -1.0, -1.0, -1.0,  0.0,  0.0, 0.0, 1.0, // bottom-left
-            -1.0, -1.0, -1.0, -1.0,  0.0,  0.0, 0.0, 1.0, // bottom-left
-            -1.0, -1.0,  1.0, -1.0,  0.0,  0.0, 0.0, 0.0, // bottom-right
-            -1.0,  1.0,  1.0, -1.0,  0.0,  0.0, 1.0, 0.0, // top-right
-            // right face
-             1.0,  1.0,  1.0,  1.0,  0.0,  0.0, 1.0, 0.0, // top-left
-             1.0, -1.0, -1.0,  1.0,  0.0,  0.0, 0.0, 1.0, // bottom-right
-             1.0,  1.0, -1.0,  1.0,  0.0,  0.0, 1.0, 1.0, // top-right         
-             1.0, -1.0, -1.0,  1.0,  0.0,  0.0, 0.0, 1.0, // bottom-right
-             1.0,  1.0,  1.0,  1.0,  0.0,  0.0, 1.0, 0.0, // top-left
-             1.0, -1.0,  1.0,  1.0,  0.0,  0.0, 0.0, 0.0, // bottom-left     
-            // bottom face
-            -1.0, -1.0, -1.0,  0.0, -1.0,  0.0, 0.0, 1.0, // top-right
-             1.0, -1.0, -1.0,  0.0, -1.0,  0.0, 1.0, 1.0, // top-left
-             1.0, -1.0,  1.0,  0.0, -1.0,  0.0, 1.0, 0.0, // bottom-left
-             1.0, -1.0,  1.0,  0.0, -1.0,  0.0, 1.0, 0.0, // bottom-left
-            -1.0, -1.0,  1.0,  0.0, -1.0,  0.0, 0.0, 0.0, // bottom-right
-            -1.0, -1.0, -1.0,  0.0, -1.0,  0.0, 0.0, 1.0, // top-right
-            // top face
-            -1.0,  1.0, -1.0,  0.0,  1.0,  0.0, 0.0, 1.0, // top-left
-             1.0,  1.0,  1.0,  0.0,  1.0,  0.0, 1.0, 0.0, // bottom-right
-             1.0,  1.0, -1.0,  0.0,  1.0,  0.0, 1.0, 1.0, // top-right     
-             1.0,  1.0,  1.0,  0.0,  1.0,  0.0, 1.0, 0.0, // bottom-right
-            -1.0,  1.0, -1.0,  0.0,  1.0,  0.0, 0.0, 1.0, // top-left
-            -1.0,  1.0,  1.0,  0.0,  1.0,  0.0, 0.0, 0.0,  // bottom-left   
-        ];
-
-        unsafe {
-            let mut cubeVBO: GLuint = 0;
-            gl::GenVertexArrays(1, cubeVAO);
-            gl::GenBuffers(1, &mut cubeVBO);
-            // fill buffer
-            gl::BindBuffer(gl::ARRAY_BUFFER, cubeVBO);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (vertices.len() * SIZE_OF_FLOAT) as GLsizeiptr,
-                vertices.as_ptr() as *const GLvoid,
-                gl::STATIC_DRAW,
-            );
-
-            gl::BindVertexArray(*cubeVAO);
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, (8 * SIZE_OF_FLOAT) as GLsizei, 0 as *const GLvoid);
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribPointer(
-                1,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                (8 * SIZE_OF_FLOAT) as GLsizei,
-                (3 * SIZE_OF_FLOAT) as *const GLvoid,
-            );
-            gl::EnableVertexAttribArray(2);
-            gl::VertexAttribPointer(
-                2,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                (8 * SIZE_OF_FLOAT) as GLsizei,
-                (6 * SIZE_OF_FLOAT) as *const GLvoid,
-            );
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindVertexArray(0);
-        }
-    }
-    // render Cube
-    unsafe {
-        gl::BindVertexArray(*cubeVAO);
-        gl::DrawArrays(gl::TRIANGLES, 0, 36);
-        gl::BindVertexArray(0);
-    }
+    cube.draw(shader);
 }
 
 fn renderQuad(quadVAO: &mut GLuint) {
@@ -527,7 +435,7 @@ fn scroll_handler(state: &mut State, _xoffset: f64, yoffset: f64) {
 fn loadTexture(path: &str, gammaCorrection: bool) -> GLuint {
     let mut texture_id: GLuint = 0;
 
-    let img = image::open(path).expect("Texture failed to load");
+    let img = open_image(path).expect("Texture failed to load");
     let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
 
     let color_type = img.color();
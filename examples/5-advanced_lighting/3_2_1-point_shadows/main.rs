@@ -9,13 +9,15 @@
 extern crate glfw;
 
 use glad_gl::gl;
-use glad_gl::gl::{GLint, GLsizei, GLsizeiptr, GLuint, GLvoid};
+use glad_gl::gl::{GLint, GLsizei, GLuint, GLvoid};
 use glam::{vec3, Mat4};
 use glfw::{Action, Context, Key};
 use image::ColorType;
+use learn_opengl_with_rust::assets::open_image;
 use learn_opengl_with_rust::camera::{Camera, CameraMovement};
+use learn_opengl_with_rust::mesh::Mesh;
+use learn_opengl_with_rust::primitives;
 use learn_opengl_with_rust::shader::Shader;
-use learn_opengl_with_rust::SIZE_OF_FLOAT;
 use std::ffi::c_uint;
 
 const SCR_WIDTH: f32 = 800.0;
@@ -136,7 +138,7 @@ fn main() {
     // -------------
     let mut lightPos = vec3(0.0, 0.0, 0.0);
 
-    let mut cubeVAO: GLuint = 0;
+    let cube = primitives::cube();
 
     // render loop
     while !window.should_close() {
@@ -190,7 +192,7 @@ fn main() {
             simpleDepthShader.set_float("far_plane", far_plane);
             simpleDepthShader.set_vec3("lightPos", &lightPos);
 
-            renderScene(&simpleDepthShader, &mut cubeVAO);
+            renderScene(&simpleDepthShader, &cube);
 
             gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
 
@@ -216,7 +218,7 @@ fn main() {
             gl::ActiveTexture(gl::TEXTURE1);
             gl::BindTexture(gl::TEXTURE_CUBE_MAP, depthCubeMap);
 
-            renderScene(&shader, &mut cubeVAO);
+            renderScene(&shader, &cube);
         }
 
         window.swap_buffers();
@@ -225,7 +227,6 @@ fn main() {
     // optional: de-allocate all resources once they've outlived their purpose:
     // ------------------------------------------------------------------------
     unsafe {
-        gl::DeleteVertexArrays(1, &cubeVAO);
         gl::DeleteShader(shader.id);
         gl::DeleteShader(simpleDepthShader.id);
     }
@@ -233,7 +234,7 @@ fn main() {
 
 // renders the 3D scene
 // --------------------
-fn renderScene(shader: &Shader, cubeVAO: &mut GLuint) {
+fn renderScene(shader: &Shader, cube: &Mesh) {
     // room cube
     let model = Mat4::from_scale(vec3(5.0, 5.0, 5.0));
     shader.set_mat4("model", &model);
@@ -241,7 +242,7 @@ fn renderScene(shader: &Shader, cubeVAO: &mut GLuint) {
         gl::Disable(gl::CULL_FACE);
     } // note that we disable culling here since we render 'inside' the cube instead of the usual 'outside' which throws off the normal culling methods.
     shader.set_int("reverse_normals", 1); // A small little hack to invert normals when drawing cube from the inside so lighting still works.
-    renderCube(cubeVAO);
+    cube.draw(shader);
     shader.set_int("reverse_normals", 0); // and of course disable it
     unsafe {
         gl::Enable(gl::CULL_FACE);
@@ -251,123 +252,28 @@ fn renderScene(shader: &Shader, cubeVAO: &mut GLuint) {
     let mut model = Mat4::from_translation(vec3(4.0, -3.5, 0.0));
     model *= Mat4::from_scale(vec3(0.5, 0.5, 0.5));
     shader.set_mat4("model", &model);
-    renderCube(cubeVAO);
+    cube.draw(shader);
 
     let mut model = Mat4::from_translation(vec3(2.0, 3.0, 1.0));
     model *= Mat4::from_scale(vec3(0.75, 0.75, 0.75));
     shader.set_mat4("model", &model);
-    renderCube(cubeVAO);
+    cube.draw(shader);
 
     let mut model = Mat4::from_translation(vec3(-3.0, -1.0, 0.0));
     model *= Mat4::from_scale(vec3(0.5, 0.5, 0.5));
     shader.set_mat4("model", &model);
-    renderCube(cubeVAO);
+    cube.draw(shader);
 
     let mut model = Mat4::from_translation(vec3(-1.5, 1.0, 1.5));
     model *= Mat4::from_scale(vec3(0.5, 0.5, 0.5));
     shader.set_mat4("model", &model);
-    renderCube(cubeVAO);
+    cube.draw(shader);
 
     let mut model = Mat4::from_translation(vec3(-1.5, 2.0, -3.0));
     model *= Mat4::from_axis_angle(vec3(1.0, 0.0, 1.0).normalize(), 60.0f32.to_radians());
     model *= Mat4::from_scale(vec3(0.75, 0.75, 0.75));
     shader.set_mat4("model", &model);
-    renderCube(cubeVAO);
-}
-
-fn renderCube(cubeVAO: &mut GLuint) {
-    // initialize (if necessary)
-    if *cubeVAO == 0 {
-        #[rustfmt::skip]
-        let vertices: [f32; 288] = [
-            // back face
-            -1.0, -1.0, -1.0,  0.0,  0.0, -1.0, 0.0, 0.0, // bottom-left
-             1.0,  1.0, -1.0,  0.0,  0.0, -1.0, 1.0, 1.0, // top-right
-             1.0, -1.0, -1.0,  0.0,  0.0, -1.0, 1.0, 0.0, // bottom-right         
-             1.0,  1.0, -1.0,  0.0,  0.0, -1.0, 1.0, 1.0, // top-right
-            -1.0, -1.0, -1.0,  0.0,  0.0, -1.0, 0.0, 0.0, // bottom-left
-            -1.0,  1.0, -1.0,  0.0,  0.0, -1.0, 0.0, 1.0, // top-left
-            // front face
-            -1.0, -1.0,  1.0,  0.0,  0.0,  1.0, 0.0, 0.0, // bottom-left
-             1.0, -1.0,  1.0,  0.0,  0.0,  1.0, 1.0, 0.0, // bottom-right
-             1.0,  1.0,  1.0,  0.0,  0.0,  1.0, 1.0, 1.0, // top-right
-             1.0,  1.0,  1.0,  0.0,  0.0,  1.0, 1.0, 1.0, // top-right
-            -1.0,  1.0,  1.0,  0.0,  0.0,  1.0, 0.0, 1.0, // top-left
-            -1.0, -1.0,  1.0,  0.0,  0.0,  1.0, 0.0, 0.0, // bottom-left
-            // left face
-            -1.0,  1.0,  1.0, -1.0,  0.0,  0.0, 1.0, 0.0, // top-right
-            -1.0,  1.0, -1.0, -1.0,  0.0,  0.0, 1.0, 1.0, // top-left
-            -1.0, -1.0, -1.0, -1.0,  0.0,  0.0, 0.0, 1.0, // bottom-left
-            -1.0, -1.0, -1.0, -1.0,  0.0,  0.0, 0.0, 1.0, // bottom-left
-            -1.0, -1.0,  1.0, -1.0,  0.0,  0.0, 0.0, 0.0, // bottom-right
-            -1.0,  1.0,  1.0, -1.0,  0.0,  0.0, 1.0, 0.0, // top-right
-            // right face
-             1.0,  1.0,  1.0,  1.0,  0.0,  0.0, 1.0, 0.0, // top-left
-             1.0, -1.0, -1.0,  1.0,  0.0,  0.0, 0.0, 1.0, // bottom-right
-             1.0,  1.0, -1.0,  1.0,  0.0,  0.0, 1.0, 1.0, // top-right         
-             1.0, -1.0, -1.0,  1.0,  0.0,  0.0, 0.0, 1.0, // bottom-right
-             1.0,  1.0,  1.0,  1.0,  0.0,  0.0, 1.0, 0.0, // top-left
-             1.0, -1.0,  1.0,  1.0,  0.0,  0.0, 0.0, 0.0, // bottom-left     
-            // bottom face
-            -1.0, -1.0, -1.0,  0.0, -1.0,  0.0, 0.0, 1.0, // top-right
-             1.0, -1.0, -1.0,  0.0, -1.0,  0.0, 1.0, 1.0, // top-left
-             1.0, -1.0,  1.0,  0.0, -1.0,  0.0, 1.0, 0.0, // bottom-left
-             1.0, -1.0,  1.0,  0.0, -1.0,  0.0, 1.0, 0.0, // bottom-left
-            -1.0, -1.0,  1.0,  0.0, -1.0,  0.0, 0.0, 0.0, // bottom-right
-            -1.0, -1.0, -1.0,  0.0, -1.0,  0.0, 0.0, 1.0, // top-right
-            // top face
-            -1.0,  1.0, -1.0,  0.0,  1.0,  0.0, 0.0, 1.0, // top-left
-             1.0,  1.0,  1.0,  0.0,  1.0,  0.0, 1.0, 0.0, // bottom-right
-             1.0,  1.0, -1.0,  0.0,  1.0,  0.0, 1.0, 1.0, // top-right     
-             1.0,  1.0,  1.0,  0.0,  1.0,  0.0, 1.0, 0.0, // bottom-right
-            -1.0,  1.0, -1.0,  0.0,  1.0,  0.0, 0.0, 1.0, // top-left
-            -1.0,  1.0,  1.0,  0.0,  1.0,  0.0, 0.0, 0.0,  // bottom-left   
-        ];
-
-        unsafe {
-            let mut cubeVBO: GLuint = 0;
-            gl::GenVertexArrays(1, cubeVAO);
-            gl::GenBuffers(1, &mut cubeVBO);
-            // fill buffer
-            gl::BindBuffer(gl::ARRAY_BUFFER, cubeVBO);
-            gl::BufferData(
-                gl::ARRAY_BUFFER,
-                (vertices.len() * SIZE_OF_FLOAT) as GLsizeiptr,
-                vertices.as_ptr() as *const GLvoid,
-                gl::STATIC_DRAW,
-            );
-
-            gl::BindVertexArray(*cubeVAO);
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, (8 * SIZE_OF_FLOAT) as GLsizei, 0 as *const GLvoid);
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribPointer(
-                1,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                (8 * SIZE_OF_FLOAT) as GLsizei,
-                (3 * SIZE_OF_FLOAT) as *const GLvoid,
-            );
-            gl::EnableVertexAttribArray(2);
-            gl::VertexAttribPointer(
-                2,
-                2,
-                gl::FLOAT,
-                gl::FALSE,
-                (8 * SIZE_OF_FLOAT) as GLsizei,
-                (6 * SIZE_OF_FLOAT) as *const GLvoid,
-            );
-            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-            gl::BindVertexArray(0);
-        }
-    }
-    // render Cube
-    unsafe {
-        gl::BindVertexArray(*cubeVAO);
-        gl::DrawArrays(gl::TRIANGLES, 0, 36);
-        gl::BindVertexArray(0);
-    }
+    cube.draw(shader);
 }
 
 //
@@ -450,7 +356,7 @@ fn scroll_handler(state: &mut State, _xoffset: f64, yoffset: f64) {
 fn loadTexture(path: &str, gammaCorrection: bool) -> GLuint {
     let mut texture_id: GLuint = 0;
 
-    let img = image::open(path).expect("Texture failed to load");
+    let img = open_image(path).expect("Texture failed to load");
     let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
 
     let color_type = img.color();
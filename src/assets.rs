@@ -0,0 +1,186 @@
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+// Resolves the logical asset paths used throughout the examples, like "resources/textures/wood.png" or
+// "examples/5-advanced_lighting/3_1_3-shadow_mapping/3.1.3.shadow_mapping.vs", against an asset root
+// instead of the working directory, so examples run from anywhere.
+//
+// The root is the first of:
+//   - a root set with set_asset_root
+//   - the LEARN_OPENGL_ROOT environment variable
+//   - the crate directory this library was built from, when it still exists
+//   - the nearest directory holding a resources folder, walking up from the executable
+//   - the working directory
+//
+// With the embed_assets feature the shaders, images and models in the resources and examples folders
+// are also compiled into the binary, see build.rs for the extensions taken, and read_asset falls back
+// to those copies when a file is not found on disk. Files on disk still win so shader hot reloading
+// keeps working during development.
+pub const ROOT_ENV: &str = "LEARN_OPENGL_ROOT";
+
+static ROOT: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+pub fn set_asset_root(root: impl AsRef<Path>) {
+    *ROOT.write().unwrap() = Some(root.as_ref().to_path_buf());
+}
+
+pub fn asset_root() -> PathBuf {
+    if let Some(root) = ROOT.read().unwrap().as_ref() {
+        return root.clone();
+    }
+    let root = find_root();
+    *ROOT.write().unwrap() = Some(root.clone());
+    root
+}
+
+fn find_root() -> PathBuf {
+    if let Some(root) = env::var_os(ROOT_ENV) {
+        return PathBuf::from(root);
+    }
+
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    if manifest_dir.join("resources").is_dir() {
+        return manifest_dir.to_path_buf();
+    }
+
+    if let Ok(exe) = env::current_exe() {
+        if let Some(root) = exe.ancestors().skip(1).find(|dir| dir.join("resources").is_dir()) {
+            return root.to_path_buf();
+        }
+    }
+
+    PathBuf::from(".")
+}
+
+// The file system path of an asset. Absolute paths are returned unchanged.
+pub fn asset_path(path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    asset_root().join(path)
+}
+
+pub fn read_asset(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let result = fs::read(asset_path(&path));
+
+    #[cfg(feature = "embed_assets")]
+    if let Err(error) = &result {
+        if error.kind() == io::ErrorKind::NotFound {
+            if let Some(file) = embedded::file(path.as_ref()) {
+                return Ok(file.contents().to_vec());
+            }
+        }
+    }
+    result
+}
+
+pub fn read_asset_to_string(path: impl AsRef<Path>) -> io::Result<String> {
+    let bytes = read_asset(path)?;
+    String::from_utf8(bytes).map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+pub fn asset_exists(path: impl AsRef<Path>) -> bool {
+    #[cfg(feature = "embed_assets")]
+    if embedded::file(path.as_ref()).is_some() {
+        return true;
+    }
+    asset_path(path).is_file()
+}
+
+pub fn open_image(path: impl AsRef<Path>) -> image::ImageResult<image::DynamicImage> {
+    let path = path.as_ref();
+    let bytes = read_asset(path).map_err(image::ImageError::IoError)?;
+    let format = image::ImageFormat::from_path(path)?;
+    image::load_from_memory_with_format(&bytes, format)
+}
+
+// A path on disk for loaders that can only read files, like assimp which also opens the .mtl and
+// textures next to a model. An embedded asset that is not on disk has its whole directory extracted
+// to a temporary folder first, so files it refers to relative to itself are found too. Files left
+// there by a binary built from other assets are replaced.
+pub fn asset_file(path: impl AsRef<Path>) -> io::Result<PathBuf> {
+    let path = path.as_ref();
+    let disk_path = asset_path(path);
+    if disk_path.exists() {
+        return Ok(disk_path);
+    }
+
+    #[cfg(feature = "embed_assets")]
+    if let Some(extracted) = embedded::extract_dir_of(path)? {
+        return Ok(extracted);
+    }
+
+    Ok(disk_path)
+}
+
+#[cfg(feature = "embed_assets")]
+mod embedded {
+    use include_dir::{include_dir, Dir, File};
+    use std::env;
+    use std::fs;
+    use std::io;
+    use std::path::{Component, Path, PathBuf};
+
+    // the runtime files of resources/ and examples/, gathered by build.rs
+    static RESOURCES: Dir = include_dir!("$OUT_DIR/embedded/resources");
+    static EXAMPLES: Dir = include_dir!("$OUT_DIR/embedded/examples");
+
+    // Splits "resources/textures/wood.png" into the embedded folder and the path inside it.
+    fn split(path: &Path) -> Option<(&'static Dir<'static>, PathBuf)> {
+        let mut components = path.components().filter(|component| !matches!(component, Component::CurDir));
+        let dir = match components.next()?.as_os_str().to_str()? {
+            "resources" => &RESOURCES,
+            "examples" => &EXAMPLES,
+            _ => return None,
+        };
+        Some((dir, components.collect()))
+    }
+
+    pub fn file(path: &Path) -> Option<&'static File<'static>> {
+        let (dir, inner) = split(path)?;
+        dir.get_file(inner)
+    }
+
+    pub fn extract_dir_of(path: &Path) -> io::Result<Option<PathBuf>> {
+        let Some((dir, inner)) = split(path) else {
+            return Ok(None);
+        };
+        if dir.get_file(&inner).is_none() {
+            return Ok(None);
+        }
+
+        let root = if std::ptr::eq(dir, &RESOURCES) { "resources" } else { "examples" };
+        let base = env::temp_dir()
+            .join(format!("learn_opengl_assets-{}", env!("CARGO_PKG_VERSION")))
+            .join(root);
+        match inner.parent().and_then(|parent| dir.get_dir(parent)) {
+            Some(parent) => extract(parent, &base)?,
+            None => extract(dir, &base)?,
+        }
+        Ok(Some(base.join(&inner)))
+    }
+
+    // Writes the files of dir under base, leaving the ones already there with the same contents.
+    // Entries keep their paths from the embedded root, so dir's own path is part of where they go.
+    fn extract(dir: &Dir, base: &Path) -> io::Result<()> {
+        fs::create_dir_all(base.join(dir.path()))?;
+        for file in dir.files() {
+            let target = base.join(file.path());
+            let same_size = fs::metadata(&target).is_ok_and(|metadata| metadata.len() == file.contents().len() as u64);
+            if same_size && fs::read(&target)? == file.contents() {
+                continue;
+            }
+            fs::write(&target, file.contents())?;
+        }
+        for child in dir.dirs() {
+            extract(child, base)?;
+        }
+        Ok(())
+    }
+}
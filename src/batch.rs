@@ -0,0 +1,294 @@
+#![allow(dead_code)]
+
+use glam::*;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::bounds::Aabb;
+use crate::mesh::{Mesh, PrimitiveMode, Texture, Vertex};
+use crate::model::Model;
+use crate::shader::Shader;
+
+// Static batching: meshes that never move relative to each other, drawn with the same textures, merged
+// into one mesh per texture set with their transforms baked into the vertices. A model of many small
+// meshes like nanosuit then takes a draw call and a round of texture binds per material instead of per mesh.
+//
+//    let suit = StaticBatch::from_model(&model);
+//    println!("nanosuit: {}", suit.report);
+//    ...
+//    suit.draw(&shader);
+//
+// The meshes stay separate ranges of the merged index buffers, so they can still be culled one by one
+// and the rest drawn with a glMultiDrawElements per material:
+//
+//    suit.draw_visible(&shader, |range| range.aabb.transform(&model_matrix).intersects(&visible_region));
+//
+// Skinned meshes, those with bone weights, are left out: baking a transform into their vertices would
+// place them twice once the bones move them. Their indices are in skipped, for drawing them as they are.
+//
+
+// where one of the source meshes ended up in a batch
+#[derive(Debug, Copy, Clone)]
+pub struct BatchRange {
+    // the index of the mesh in the meshes the batch was made from
+    pub source: usize,
+    // the first index and the number of indices of the mesh in the batch's mesh
+    pub first: usize,
+    pub count: usize,
+    // the bounds of the mesh after its transform
+    pub aabb: Aabb,
+}
+
+// the meshes sharing a texture set, merged
+#[derive(Debug)]
+pub struct MaterialBatch {
+    pub mesh: Mesh,
+    pub ranges: Vec<BatchRange>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BatchReport {
+    pub meshes: usize,
+    pub draw_calls: usize,
+    // skinned meshes left out of the batches
+    pub skipped: usize,
+}
+
+impl BatchReport {
+    pub fn draw_calls_saved(&self) -> usize {
+        self.meshes - self.skipped - self.draw_calls
+    }
+}
+
+impl fmt::Display for BatchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} meshes in {} draw calls, {} saved",
+            self.meshes - self.skipped,
+            self.draw_calls,
+            self.draw_calls_saved()
+        )?;
+        if self.skipped > 0 {
+            write!(f, ", {} skinned meshes skipped", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct StaticBatch {
+    pub batches: Vec<MaterialBatch>,
+    // the indices of the skinned meshes left out of the batches
+    pub skipped: Vec<usize>,
+    pub report: BatchReport,
+}
+
+impl StaticBatch {
+    // batches the meshes of a model, placed by their node transforms
+    pub fn from_model(model: &Model) -> StaticBatch {
+        StaticBatch::new(model.meshes.iter().zip(model.mesh_transforms.iter().copied()))
+    }
+
+    // Merges meshes with the same textures, in the same order, and the same primitive mode. Strips,
+    // fans and loops can't be joined without breaking them up, so they are each a batch of their own.
+    pub fn new<'a>(meshes: impl IntoIterator<Item = (&'a Mesh, Mat4)>) -> StaticBatch {
+        let mut groups: Vec<Vec<(usize, &Mesh, Mat4)>> = vec![];
+        let mut skipped: Vec<usize> = vec![];
+        let mut mesh_count = 0;
+        for (source, (mesh, transform)) in meshes.into_iter().enumerate() {
+            mesh_count += 1;
+            if is_skinned(mesh) {
+                skipped.push(source);
+                continue;
+            }
+            let group = groups.iter_mut().find(|group| is_list(mesh.mode) && same_material(group[0].1, mesh));
+            match group {
+                Some(group) => group.push((source, mesh, transform)),
+                None => groups.push(vec![(source, mesh, transform)]),
+            }
+        }
+
+        let batches: Vec<MaterialBatch> = groups.into_iter().map(merge).collect();
+        let report = BatchReport {
+            meshes: mesh_count,
+            draw_calls: batches.len(),
+            skipped: skipped.len(),
+        };
+        StaticBatch { batches, skipped, report }
+    }
+
+    // one draw call per batch
+    pub fn draw(&self, shader: &Shader) {
+        for batch in &self.batches {
+            batch.mesh.draw(shader);
+        }
+    }
+
+    // Draws the meshes visible returns true for, with one glMultiDrawElements per batch.
+    pub fn draw_visible(&self, shader: &Shader, visible: impl Fn(&BatchRange) -> bool) {
+        for batch in &self.batches {
+            let ranges: Vec<(usize, usize)> = batch
+                .ranges
+                .iter()
+                .filter(|range| visible(range))
+                .map(|range| (range.first, range.count))
+                .collect();
+            batch.mesh.draw_ranges(shader, &ranges);
+        }
+    }
+}
+
+// the modes whose primitives don't depend on the ones before them, so meshes can be appended
+fn is_list(mode: PrimitiveMode) -> bool {
+    matches!(
+        mode,
+        PrimitiveMode::Points | PrimitiveMode::Lines | PrimitiveMode::Triangles | PrimitiveMode::Patches(_)
+    )
+}
+
+fn same_material(a: &Mesh, b: &Mesh) -> bool {
+    a.mode == b.mode && a.textures.len() == b.textures.len() && a.textures.iter().zip(&b.textures).all(|(a, b)| Rc::ptr_eq(a, b))
+}
+
+// bone weights mean the vertices are moved by a skeleton at draw time
+fn is_skinned(mesh: &Mesh) -> bool {
+    mesh.vertices
+        .iter()
+        .any(|vertex| { vertex.m_Weights }.iter().any(|&weight| weight != 0.0))
+}
+
+fn merge(group: Vec<(usize, &Mesh, Mat4)>) -> MaterialBatch {
+    let (mode, textures): (PrimitiveMode, Vec<Rc<Texture>>) = (group[0].1.mode, group[0].1.textures.clone());
+    let (vertices, indices, ranges) = merge_geometry(
+        mode,
+        group
+            .iter()
+            .map(|(source, mesh, transform)| (*source, mesh.vertices.as_slice(), mesh.indices.as_slice(), *transform)),
+    );
+    MaterialBatch {
+        mesh: Mesh::new(vertices, indices, textures).with_mode(mode),
+        ranges,
+    }
+}
+
+// Appends meshes, given as their source index, vertices, indices (none for an unindexed mesh) and
+// transform, into one vertex and index list, with the indices of each moved past the vertices before it.
+fn merge_geometry<'a>(
+    mode: PrimitiveMode,
+    parts: impl IntoIterator<Item = (usize, &'a [Vertex], &'a [u32], Mat4)>,
+) -> (Vec<Vertex>, Vec<u32>, Vec<BatchRange>) {
+    let mut vertices: Vec<Vertex> = vec![];
+    let mut indices: Vec<u32> = vec![];
+    let mut ranges: Vec<BatchRange> = vec![];
+
+    for (source, mesh_vertices, mesh_indices, transform) in parts {
+        let base = vertices.len() as u32;
+        let first = indices.len();
+        if transform == Mat4::IDENTITY {
+            vertices.extend_from_slice(mesh_vertices);
+        } else {
+            let normal_matrix = Mat3::from_mat4(transform).inverse().transpose();
+            vertices.extend(mesh_vertices.iter().map(|vertex| transform_vertex(vertex, &transform, &normal_matrix)));
+        }
+        if mesh_indices.is_empty() {
+            indices.extend(base..base + mesh_vertices.len() as u32);
+        } else {
+            indices.extend(mesh_indices.iter().map(|index| base + index));
+        }
+        // a mirroring transform turns the triangles inside out, wind them the other way to make up for it
+        if mode == PrimitiveMode::Triangles && transform.determinant() < 0.0 {
+            for triangle in indices[first..].chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+        ranges.push(BatchRange {
+            source,
+            first,
+            count: indices.len() - first,
+            aabb: Aabb::from_points(vertices[base as usize..].iter().map(|vertex| vertex.Position)),
+        });
+    }
+
+    (vertices, indices, ranges)
+}
+
+fn transform_vertex(vertex: &Vertex, transform: &Mat4, normal_matrix: &Mat3) -> Vertex {
+    let mut transformed = *vertex;
+    transformed.Position = transform.transform_point3(vertex.Position);
+    transformed.Normal = (*normal_matrix * vertex.Normal).normalize_or_zero();
+    transformed.Tangent = transform.transform_vector3(vertex.Tangent).normalize_or_zero();
+    transformed.Bitangent = transform.transform_vector3(vertex.Bitangent).normalize_or_zero();
+    transformed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::vertex;
+
+    // a triangle in the xy plane facing +z, wound counter clockwise
+    fn triangle() -> Vec<Vertex> {
+        [vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0)]
+            .iter()
+            .map(|&position| vertex(position, Vec3::Z, Vec2::ZERO, Vec3::X))
+            .collect()
+    }
+
+    // the normal the winding of the triangle at first gives
+    fn face_normal(vertices: &[Vertex], indices: &[u32], first: usize) -> Vec3 {
+        let [a, b, c] = [0, 1, 2].map(|corner| vertices[indices[first + corner] as usize].Position);
+        (b - a).cross(c - a).normalize()
+    }
+
+    #[test]
+    fn indices_are_rebased() {
+        let (first, second) = (triangle(), triangle());
+        let offset = Mat4::from_translation(vec3(2.0, 0.0, 0.0));
+        let (vertices, indices, ranges) = merge_geometry(
+            PrimitiveMode::Triangles,
+            [
+                (0, first.as_slice(), [0, 1, 2].as_slice(), Mat4::IDENTITY),
+                (1, second.as_slice(), [].as_slice(), offset),
+            ],
+        );
+        assert_eq!(vertices.len(), 6);
+        // the unindexed mesh gets an index per vertex, past the vertices of the first
+        assert_eq!(indices, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!((ranges[1].source, ranges[1].first, ranges[1].count), (1, 3, 3));
+        assert_eq!({ vertices[4].Position }, vec3(3.0, 0.0, 0.0));
+        assert_eq!(ranges[1].aabb.center(), vec3(2.5, 0.5, 0.0));
+
+        let (_, indices, _) = merge_geometry(
+            PrimitiveMode::Triangles,
+            [
+                (0, first.as_slice(), [2, 1, 0].as_slice(), Mat4::IDENTITY),
+                (1, second.as_slice(), [0, 2, 1].as_slice(), offset),
+            ],
+        );
+        assert_eq!(indices, vec![2, 1, 0, 3, 5, 4]);
+    }
+
+    #[test]
+    fn mirrored_transforms_flip_the_winding() {
+        let mesh = triangle();
+        let mirror = Mat4::from_scale(vec3(-1.0, 1.0, 1.0));
+        let (vertices, indices, _) = merge_geometry(
+            PrimitiveMode::Triangles,
+            [
+                (0, mesh.as_slice(), [0, 1, 2].as_slice(), Mat4::IDENTITY),
+                (1, mesh.as_slice(), [0, 1, 2].as_slice(), mirror),
+            ],
+        );
+        assert_eq!(indices, vec![0, 1, 2, 3, 5, 4]);
+        // the mirrored triangle still faces the way its normals point
+        for first in [0, 3] {
+            let normal = { vertices[indices[first] as usize].Normal };
+            assert!((face_normal(&vertices, &indices, first) - normal).length() < 1e-5);
+        }
+
+        // only triangles have a winding to fix
+        let (_, indices, _) = merge_geometry(PrimitiveMode::Points, [(0, mesh.as_slice(), [0, 1, 2].as_slice(), mirror)]);
+        assert_eq!(indices, vec![0, 1, 2]);
+    }
+}
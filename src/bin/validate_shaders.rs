@@ -0,0 +1,1111 @@
+#![allow(dead_code)]
+
+// Checks the example shaders without a GPU, so typos in chapters nobody has run yet show up on any machine.
+//
+// usage:
+//
+//    cargo run --features validate_shaders --bin validate_shaders
+//    cargo run --features validate_shaders --bin validate_shaders -- examples/5-advanced_lighting
+//
+// Every .vert, .frag, .geom and .comp file under the given directories (examples/ by default) has its
+// #includes expanded, then is parsed and validated by naga's glsl front end. Errors are reported as
+// file:line of the original source and the exit code is 1 if any shader failed.
+//
+// naga only accepts the Vulkan flavour of glsl 450, so each shader is first rewritten line for line,
+// keeping line numbers, into something it understands:
+//   - #version becomes 450
+//   - loose uniforms become plain globals and uniform blocks get a layout(binding = N)
+//   - samplers become a texture plus a sampler, combined again where they are used
+//   - structs holding samplers and in/out interface blocks are flattened into separate variables
+//   - in/out variables without a layout(location = N) get one, matrix inputs are split into columns
+//   - identifiers that only became keywords after the shader's version, like sample, are renamed
+//
+// naga has no geometry stage, so geometry shaders are checked as vertex shaders, which catches syntax
+// and type errors though not misuse of the stage itself:
+//   - gl_in and input blocks become arrays of structs sized by the input primitive, other inputs plain arrays
+//   - EmitVertex and EndPrimitive become empty functions, declared in place of the output layout
+//   - gl_Layer, gl_PrimitiveID and the other geometry only built-ins become plain globals
+// There are no tessellation stages either, those files are reported as skipped and not counted as checked.
+
+use learn_opengl_with_rust::assets::asset_root;
+use learn_opengl_with_rust::shader_error::{LogEntry, Severity};
+use learn_opengl_with_rust::shader_include::{preprocess_file, PreprocessedSource};
+
+use naga::front::glsl::{Frontend, Options};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::ShaderStage;
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+fn main() {
+    let root = asset_root();
+    let mut targets: Vec<String> = env::args().skip(1).collect();
+    if targets.is_empty() {
+        targets.push("examples".to_string());
+    }
+
+    let mut files: Vec<String> = vec![];
+    for target in &targets {
+        collect_files(&root, Path::new(target), &mut files);
+    }
+    files.sort();
+
+    let mut failed = 0;
+    let mut skipped = 0;
+    for file in &files {
+        match check_file(file) {
+            Outcome::Passed => {}
+            Outcome::Skipped(reason) => {
+                skipped += 1;
+                println!("{}: skipped, {}", file, reason);
+            }
+            Outcome::Failed(entries) => {
+                failed += 1;
+                for entry in entries {
+                    println!("{}", entry);
+                }
+            }
+        }
+    }
+
+    println!("{} shaders checked, {} failed, {} skipped", files.len() - skipped, failed, skipped);
+    if failed > 0 {
+        process::exit(1);
+    }
+}
+
+// Shader files under path, which is relative to the asset root. They are returned relative to it too,
+// so messages show paths like examples/2-lighting/1-colors/1-colors.frag.
+fn collect_files(root: &Path, path: &Path, files: &mut Vec<String>) {
+    let full_path = root.join(path);
+    if full_path.is_file() {
+        if shader_stage(path).is_some() {
+            files.push(path.to_string_lossy().replace('\\', "/"));
+        }
+        return;
+    }
+    match fs::read_dir(&full_path) {
+        Ok(entries) => {
+            for entry in entries.flatten() {
+                collect_files(root, &path.join(entry.file_name()), files);
+            }
+        }
+        Err(error) => eprintln!("{}: {}", full_path.display(), error),
+    }
+}
+
+enum Stage {
+    Naga(ShaderStage),
+    // checked as a vertex shader, see Adapter::geometry
+    Geometry,
+    Unsupported(&'static str),
+}
+
+fn shader_stage(path: &Path) -> Option<Stage> {
+    let stage = match path.extension()?.to_str()? {
+        "vert" => Stage::Naga(ShaderStage::Vertex),
+        "frag" => Stage::Naga(ShaderStage::Fragment),
+        "comp" => Stage::Naga(ShaderStage::Compute),
+        "geom" => Stage::Geometry,
+        "tesc" | "tese" => Stage::Unsupported("naga has no tessellation stages"),
+        _ => return None,
+    };
+    Some(stage)
+}
+
+enum Outcome {
+    Passed,
+    Skipped(&'static str),
+    Failed(Vec<LogEntry>),
+}
+
+fn check_file(file: &str) -> Outcome {
+    let stage = match shader_stage(Path::new(file)) {
+        Some(Stage::Unsupported(reason)) => return Outcome::Skipped(reason),
+        Some(stage) => stage,
+        None => return Outcome::Skipped("unknown shader stage"),
+    };
+
+    let source = match preprocess_file(file) {
+        Ok(source) => source,
+        Err(error) => return Outcome::Failed(vec![entry(file, None, error.to_string())]),
+    };
+    check_source(file, stage, &source)
+}
+
+fn check_source(file: &str, stage: Stage, source: &PreprocessedSource) -> Outcome {
+    let (stage, adapter) = match stage {
+        Stage::Naga(stage) => (stage, Adapter::default()),
+        Stage::Geometry => (ShaderStage::Vertex, Adapter::geometry()),
+        Stage::Unsupported(reason) => return Outcome::Skipped(reason),
+    };
+    let code = adapter.adapt(&source.code);
+
+    let module = match Frontend::default().parse(&Options::from(stage), &code) {
+        Ok(module) => module,
+        Err(errors) => {
+            let entries = errors
+                .iter()
+                .map(|error| source_entry(source, file, error.meta.location(&code).line_number, error.kind.to_string()))
+                .collect();
+            return Outcome::Failed(entries);
+        }
+    };
+
+    match Validator::new(ValidationFlags::all(), Capabilities::all()).validate(&module) {
+        Ok(_) => Outcome::Passed,
+        Err(error) => {
+            // the top level message is terse, the cause chain says what is actually wrong
+            let mut message = error.as_inner().to_string();
+            let mut cause = error.as_inner().source();
+            while let Some(inner) = cause {
+                message.push_str(": ");
+                message.push_str(&inner.to_string());
+                cause = inner.source();
+            }
+            // spans go from the function down to the expression at fault, the last one is the most precise
+            let span = error.spans().map(|(span, _)| *span).filter(|span| span.is_defined()).last();
+            let entry = match span {
+                Some(span) => source_entry(source, file, span.location(&code).line_number, message),
+                None => entry(file, None, message),
+            };
+            Outcome::Failed(vec![entry])
+        }
+    }
+}
+
+fn entry(file: &str, line: Option<u32>, message: String) -> LogEntry {
+    LogEntry {
+        file: file.to_string(),
+        line,
+        column: None,
+        severity: Severity::Error,
+        message,
+    }
+}
+
+// Points an error in the expanded code back at the file and line it came from.
+fn source_entry(source: &PreprocessedSource, file: &str, line: u32, message: String) -> LogEntry {
+    match source.location(line) {
+        Some((source_file, source_line)) => entry(source_file, Some(source_line), message),
+        None => entry(file, Some(line), message),
+    }
+}
+
+// Rewrites desktop glsl into the Vulkan glsl naga accepts. Lines are only ever edited in place,
+// never added or removed, so naga's line numbers are the line numbers of the input.
+#[derive(Default)]
+struct Adapter {
+    lines: Vec<String>,
+    // lines that now hold generated declarations, skipped when renaming uses
+    generated: HashSet<usize>,
+    next_binding: u32,
+    used_locations: HashMap<&'static str, HashSet<u32>>,
+    // struct name -> members, for structs holding samplers
+    opaque_structs: HashMap<String, Vec<Member>>,
+    // identifier -> the expression replacing it, e.g. a sampler combining its texture and sampler
+    renames: HashMap<String, String>,
+    // "instance.member" -> replacement, for flattened structs and interface blocks
+    members: HashMap<(String, String), String>,
+    // Some(vertices per input primitive) when adapting a geometry shader into a vertex shader
+    geometry_inputs: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+struct Member {
+    ty: String,
+    name: String,
+    // array suffix, e.g. "[4]"
+    array: String,
+}
+
+impl Adapter {
+    fn geometry() -> Adapter {
+        let mut adapter = Adapter {
+            geometry_inputs: Some(GEOMETRY_MAX_INPUTS),
+            ..Default::default()
+        };
+        adapter.renames.insert("gl_in".to_string(), "gs_gl_in".to_string());
+        for (built_in, replacement) in GEOMETRY_BUILT_INS {
+            adapter.renames.insert(built_in.to_string(), replacement.to_string());
+        }
+        adapter
+    }
+
+    fn adapt(mut self, code: &str) -> String {
+        self.lines = strip_comments(code).lines().map(|line| line.to_string()).collect();
+        self.find_explicit_locations();
+        if self.geometry_inputs.is_some() {
+            self.find_input_primitive();
+        }
+
+        let mut depth = 0;
+        let mut index = 0;
+        while index < self.lines.len() {
+            let line = self.lines[index].clone();
+            if depth == 0 {
+                let trimmed = line.trim_start();
+                if let Some(directive) = trimmed.strip_prefix('#') {
+                    if let Some(version) = directive.trim_start().strip_prefix("version") {
+                        if version
+                            .trim()
+                            .split(' ')
+                            .next()
+                            .and_then(|number| number.parse::<u32>().ok())
+                            .is_some_and(|number| number < 400)
+                        {
+                            for keyword in NEW_KEYWORDS {
+                                self.renames.insert(keyword.to_string(), format!("{}_", keyword));
+                            }
+                        }
+                        self.lines[index] = "#version 450 core".to_string();
+                    }
+                    index += 1;
+                    continue;
+                }
+                if let Some(end) = self.declaration(index) {
+                    depth += self.lines[index..=end].iter().map(|line| brace_balance(line)).sum::<i32>();
+                    index = end + 1;
+                    continue;
+                }
+            }
+            depth += brace_balance(&line);
+            index += 1;
+        }
+
+        self.rename_uses();
+        let mut code = self.lines.join("\n");
+        code.push('\n');
+        code
+    }
+
+    fn binding(&mut self) -> u32 {
+        self.next_binding += 1;
+        self.next_binding - 1
+    }
+
+    fn location(&mut self, storage: &'static str) -> u32 {
+        let used = self.used_locations.entry(storage).or_default();
+        let location = (0..).find(|location| !used.contains(location)).unwrap();
+        used.insert(location);
+        location
+    }
+
+    fn find_explicit_locations(&mut self) {
+        for line in &self.lines {
+            let words = words(line);
+            if words.first().map(String::as_str) != Some("layout") {
+                continue;
+            }
+            let storage = if words.iter().any(|word| word == "in") {
+                "in"
+            } else if words.iter().any(|word| word == "out") {
+                "out"
+            } else {
+                continue;
+            };
+            if let Some(position) = words.iter().position(|word| word == "location") {
+                if let Some(location) = words.get(position + 1).and_then(|word| word.parse().ok()) {
+                    self.used_locations.entry(storage).or_default().insert(location);
+                }
+            }
+        }
+    }
+
+    // The number of vertices geometry shader inputs are arrays of, from layout(triangles) in; and the like.
+    fn find_input_primitive(&mut self) {
+        for line in &self.lines {
+            let words = words(line);
+            if words.first().map(String::as_str) != Some("layout") || words.last().map(String::as_str) != Some("in") {
+                continue;
+            }
+            if let Some((_, vertices)) = INPUT_PRIMITIVES.iter().find(|(primitive, _)| words.iter().any(|word| word == primitive)) {
+                self.geometry_inputs = Some(*vertices);
+            }
+        }
+    }
+
+    // Handles a declaration at global scope starting on line index. Returns the last line it consumed,
+    // or None when the line is not a declaration this adapter rewrites.
+    fn declaration(&mut self, index: usize) -> Option<usize> {
+        let line = self.lines[index].clone();
+        if let Some(vertices) = self.geometry_inputs {
+            if let Some(end) = self.geometry_declaration(index, vertices) {
+                return Some(end);
+            }
+        }
+        let parsed = Declaration::parse(&line)?;
+
+        if parsed.storage == "struct" {
+            return self.struct_definition(index, &parsed);
+        }
+        let opens_block = parsed.rest.trim_start().starts_with('{') || (parsed.rest.trim().is_empty() && self.next_line_opens_block(index));
+
+        match parsed.storage.as_str() {
+            "uniform" | "buffer" if opens_block => {
+                let layout = with_qualifier(&parsed.layout, "binding", self.binding());
+                self.lines[index] = format!("{}layout({}) {} {} {}", parsed.indent, layout, parsed.qualifiers, parsed.ty, parsed.rest);
+                Some(index)
+            }
+            "in" | "out" if opens_block => self.interface_block(index, &parsed),
+            "uniform" if sampler_types(&parsed.ty).is_some() => {
+                let names = declared_names(&parsed.rest);
+                let declarations: Vec<String> = names.iter().map(|member| self.sampler(&member.name, &parsed.ty)).collect();
+                self.lines[index] = format!("{}{}", parsed.indent, declarations.join(" "));
+                self.generated.insert(index);
+                Some(index)
+            }
+            "uniform" if self.opaque_structs.contains_key(&parsed.ty) => {
+                let members = self.opaque_structs[&parsed.ty].clone();
+                let mut declarations: Vec<String> = vec![];
+                for instance in declared_names(&parsed.rest) {
+                    for member in &members {
+                        let name = format!("{}_{}", instance.name, member.name);
+                        if sampler_types(&member.ty).is_some() {
+                            declarations.push(self.sampler(&name, &member.ty));
+                            let combined = self.renames[&name].clone();
+                            self.members.insert((instance.name.clone(), member.name.clone()), combined);
+                        } else {
+                            declarations.push(format!("{} {}{};", member.ty, name, member.array));
+                            self.members.insert((instance.name.clone(), member.name.clone()), name);
+                        }
+                    }
+                }
+                self.lines[index] = format!("{}{}", parsed.indent, declarations.join(" "));
+                self.generated.insert(index);
+                Some(index)
+            }
+            "uniform" => {
+                // a plain global, which unlike a uniform block member may be a bool or have an initializer
+                self.lines[index] = format!("{}{}{}", parsed.indent, parsed.ty, parsed.rest);
+                Some(index)
+            }
+            "in" if MATRIX_COLUMNS.iter().any(|(matrix, _)| *matrix == parsed.ty) => self.matrix_input(index, &parsed),
+            "in" | "out" if !parsed.layout.contains("location") => {
+                let storage = if parsed.storage == "in" { "in" } else { "out" };
+                let location = self.location(storage);
+                let layout = with_qualifier(&parsed.layout, "location", location);
+                self.lines[index] = format!("{}layout({}) {} {} {}", parsed.indent, layout, parsed.qualifiers, parsed.ty, parsed.rest);
+                Some(index)
+            }
+            _ => None,
+        }
+    }
+
+    // The declarations only a geometry shader has: the primitive layouts, which make way for the
+    // declarations standing in for the stage's built-ins, and inputs, which are arrays of a value per vertex.
+    fn geometry_declaration(&mut self, index: usize, vertices: u32) -> Option<usize> {
+        let line = self.lines[index].clone();
+        let trimmed = line.trim();
+        if trimmed.starts_with("layout") && trimmed.ends_with(';') {
+            let words = words(trimmed);
+            let declarations = match words.last().map(String::as_str) {
+                Some("in") if words.len() > 1 => {
+                    let built_ins: Vec<String> = GEOMETRY_BUILT_INS
+                        .iter()
+                        .map(|(_, replacement)| format!("int {};", replacement))
+                        .collect();
+                    format!(
+                        "struct gs_PerVertex {{ vec4 gl_Position; float gl_PointSize; }}; gs_PerVertex gs_gl_in[{}]; {}",
+                        vertices,
+                        built_ins.join(" ")
+                    )
+                }
+                Some("out") if words.len() > 1 => "void EmitVertex() {} void EndPrimitive() {}".to_string(),
+                _ => return None,
+            };
+            self.lines[index] = declarations;
+            self.generated.insert(index);
+            return Some(index);
+        }
+
+        let parsed = Declaration::parse(&line)?;
+        if parsed.storage != "in" {
+            return None;
+        }
+        let opens_block = parsed.rest.trim_start().starts_with('{') || (parsed.rest.trim().is_empty() && self.next_line_opens_block(index));
+        if !opens_block {
+            // in vec3 normal[]; becomes a global vec3 normal[3];
+            self.lines[index] = format!(
+                "{}{}{}",
+                parsed.indent,
+                parsed.ty,
+                parsed.rest.replacen("[]", &format!("[{}]", vertices), 1)
+            );
+            return Some(index);
+        }
+
+        // in VS_OUT { vec3 normal; } gs_in[]; becomes a struct VS_OUT and a global VS_OUT gs_in[3];
+        let end = self.block_end(index)?;
+        let members = self.block_members(index, end);
+        let close = self.lines[end].clone();
+        let instance = words(&close[close.rfind('}')? + 1..]).into_iter().next();
+        let declarations = match instance {
+            Some(instance) => {
+                let fields: Vec<String> = members
+                    .iter()
+                    .map(|member| format!("{} {}{};", member.ty, member.name, member.array))
+                    .collect();
+                format!(
+                    "struct {} {{ {} }}; {} {}[{}];",
+                    parsed.ty,
+                    fields.join(" "),
+                    parsed.ty,
+                    instance,
+                    vertices
+                )
+            }
+            // without an instance name the members are arrays themselves
+            None => members
+                .iter()
+                .map(|member| format!("{} {}[{}];", member.ty, member.name, vertices))
+                .collect::<Vec<String>>()
+                .join(" "),
+        };
+        for line in &mut self.lines[index..=end] {
+            line.clear();
+        }
+        self.lines[index] = declarations;
+        self.generated.insert(index);
+        Some(end)
+    }
+
+    fn next_line_opens_block(&self, index: usize) -> bool {
+        self.lines[index + 1..]
+            .iter()
+            .map(|line| line.trim())
+            .find(|line| !line.is_empty())
+            .is_some_and(|line| line.starts_with('{'))
+    }
+
+    // The line holding the } that closes the block opened on or after line index.
+    fn block_end(&self, index: usize) -> Option<usize> {
+        let mut depth = 0;
+        let mut opened = false;
+        for (i, line) in self.lines.iter().enumerate().skip(index) {
+            for c in line.chars() {
+                match c {
+                    '{' => {
+                        depth += 1;
+                        opened = true;
+                    }
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+            }
+            if opened && depth <= 0 {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    // The members declared between the braces of a block spanning lines start..=end.
+    fn block_members(&self, start: usize, end: usize) -> Vec<Member> {
+        let text: Vec<&str> = self.lines[start..=end].iter().map(String::as_str).collect();
+        let text = text.join("\n");
+        let body = match (text.find('{'), text.rfind('}')) {
+            (Some(open), Some(close)) if open < close => &text[open + 1..close],
+            _ => return vec![],
+        };
+        let mut members = vec![];
+        for statement in body.split(';') {
+            let words = words(statement);
+            // skip interpolation and precision qualifiers in front of the type
+            let Some(ty_index) = words.iter().position(|word| !is_qualifier(word)) else {
+                continue;
+            };
+            let ty = words[ty_index].clone();
+            let after_type = statement[statement.find(ty.as_str()).unwrap() + ty.len()..].to_string();
+            for mut member in declared_names(&after_type) {
+                member.ty = ty.clone();
+                members.push(member);
+            }
+        }
+        members
+    }
+
+    fn struct_definition(&mut self, index: usize, parsed: &Declaration) -> Option<usize> {
+        let end = self.block_end(index)?;
+        let members = self.block_members(index, end);
+        if members.iter().any(|member| sampler_types(&member.ty).is_some()) {
+            // uniforms of this type are flattened, the struct itself can't exist in Vulkan glsl
+            self.opaque_structs.insert(parsed.ty.clone(), members);
+            for line in &mut self.lines[index..=end] {
+                line.clear();
+            }
+        }
+        Some(end)
+    }
+
+    // out VS_OUT { vec3 FragPos; ... } vs_out; becomes one out variable per member, vs_out_FragPos.
+    fn interface_block(&mut self, index: usize, parsed: &Declaration) -> Option<usize> {
+        let end = self.block_end(index)?;
+        let members = self.block_members(index, end);
+        let close = self.lines[end].clone();
+        let after = &close[close.rfind('}')? + 1..];
+        let instance = words(after).into_iter().next();
+        let storage = if parsed.storage == "in" { "in" } else { "out" };
+
+        let mut declarations = vec![];
+        for member in &members {
+            let name = match &instance {
+                Some(instance) => {
+                    let name = format!("{}_{}", instance, member.name);
+                    self.members.insert((instance.clone(), member.name.clone()), name.clone());
+                    name
+                }
+                None => member.name.clone(),
+            };
+            let location = self.location(storage);
+            let interpolation = if member.ty.starts_with('i') || member.ty.starts_with('u') {
+                "flat "
+            } else {
+                ""
+            };
+            declarations.push(format!(
+                "layout(location = {}) {}{} {} {};",
+                location, interpolation, storage, member.ty, name
+            ));
+        }
+
+        for line in &mut self.lines[index..=end] {
+            line.clear();
+        }
+        self.lines[index] = declarations.join(" ");
+        self.generated.insert(index);
+        Some(end)
+    }
+
+    // Declares a texture and a sampler for a sampler uniform and remembers how to combine them.
+    fn sampler(&mut self, name: &str, ty: &str) -> String {
+        let (texture, sampler) = sampler_types(ty).unwrap();
+        let binding = self.binding();
+        self.renames.insert(name.to_string(), format!("{}({}, {}_sampler)", ty, name, name));
+        format!(
+            "layout(set = 1, binding = {}) uniform {} {}; layout(set = 2, binding = {}) uniform {} {}_sampler;",
+            binding, texture, name, binding, sampler, name
+        )
+    }
+
+    // layout(location = 3) in mat4 aInstanceMatrix; becomes four vec4 inputs, rebuilt into a mat4 where used.
+    fn matrix_input(&mut self, index: usize, parsed: &Declaration) -> Option<usize> {
+        let (_, (column_type, columns)) = MATRIX_COLUMNS.iter().find(|(matrix, _)| *matrix == parsed.ty)?;
+        let name = declared_names(&parsed.rest).into_iter().next()?.name;
+        let words = words(&parsed.layout);
+        let first = match words.iter().position(|word| word == "location") {
+            Some(position) => words.get(position + 1)?.parse().ok()?,
+            None => self.location("in"),
+        };
+
+        let mut declarations = vec![];
+        let mut column_names = vec![];
+        for column in 0..*columns {
+            let location = first + column;
+            self.used_locations.entry("in").or_default().insert(location);
+            declarations.push(format!("layout(location = {}) in {} {}_{};", location, column_type, name, column));
+            column_names.push(format!("{}_{}", name, column));
+        }
+        self.renames.insert(name, format!("{}({})", parsed.ty, column_names.join(", ")));
+        self.lines[index] = format!("{}{}", parsed.indent, declarations.join(" "));
+        self.generated.insert(index);
+        Some(index)
+    }
+
+    // Points uses of flattened members and samplers at their replacements.
+    fn rename_uses(&mut self) {
+        if self.renames.is_empty() && self.members.is_empty() {
+            return;
+        }
+        for index in 0..self.lines.len() {
+            if self.generated.contains(&index) || self.lines[index].trim_start().starts_with('#') {
+                continue;
+            }
+            let line = &self.lines[index];
+            let tokens = tokens(line);
+            let mut renamed = String::new();
+            let mut last = 0;
+            let mut i = 0;
+            while i < tokens.len() {
+                let (start, end) = tokens[i];
+                let word = &line[start..end];
+                // a member access, but not one that is itself a member of something else
+                let is_member_access = i + 2 < tokens.len() && &line[tokens[i + 1].0..tokens[i + 1].1] == ".";
+                let after_dot = i > 0 && &line[tokens[i - 1].0..tokens[i - 1].1] == ".";
+                if is_member_access && !after_dot {
+                    let member = &line[tokens[i + 2].0..tokens[i + 2].1];
+                    if let Some(replacement) = self.members.get(&(word.to_string(), member.to_string())) {
+                        renamed.push_str(&line[last..start]);
+                        renamed.push_str(replacement);
+                        last = tokens[i + 2].1;
+                        i += 3;
+                        continue;
+                    }
+                }
+                if !after_dot {
+                    if let Some(replacement) = self.renames.get(word) {
+                        renamed.push_str(&line[last..start]);
+                        renamed.push_str(replacement);
+                        last = end;
+                    }
+                }
+                i += 1;
+            }
+            renamed.push_str(&line[last..]);
+            self.lines[index] = renamed;
+        }
+    }
+}
+
+// A global declaration split into its parts, e.g. "layout (location = 0) flat out int id;" is
+// layout "location = 0", qualifiers "flat out", storage "out", type "int", rest " id;".
+#[derive(Debug)]
+struct Declaration {
+    indent: String,
+    layout: String,
+    qualifiers: String,
+    storage: String,
+    ty: String,
+    rest: String,
+}
+
+impl Declaration {
+    fn parse(line: &str) -> Option<Declaration> {
+        let indent_length = line.len() - line.trim_start().len();
+        let mut text = &line[indent_length..];
+
+        let mut layout = String::new();
+        if let Some(after) = text.strip_prefix("layout") {
+            let after = after.trim_start();
+            let open = after.strip_prefix('(')?;
+            let close = open.find(')')?;
+            layout = open[..close].trim().to_string();
+            text = open[close + 1..].trim_start();
+        }
+
+        let mut qualifiers: Vec<&str> = vec![];
+        let mut storage = String::new();
+        loop {
+            let word_end = text.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(text.len());
+            let word = &text[..word_end];
+            if word.is_empty() {
+                return None;
+            }
+            if matches!(word, "uniform" | "in" | "out" | "buffer" | "struct") {
+                storage = word.to_string();
+            } else if !is_qualifier(word) {
+                break;
+            }
+            qualifiers.push(word);
+            text = text[word_end..].trim_start();
+        }
+        if storage.is_empty() {
+            return None;
+        }
+        if storage == "struct" {
+            qualifiers.pop();
+        }
+
+        let ty_end = text.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(text.len());
+        if ty_end == 0 {
+            return None;
+        }
+        Some(Declaration {
+            indent: line[..indent_length].to_string(),
+            layout,
+            qualifiers: qualifiers.join(" "),
+            storage,
+            ty: text[..ty_end].to_string(),
+            rest: text[ty_end..].to_string(),
+        })
+    }
+}
+
+fn is_qualifier(word: &str) -> bool {
+    matches!(
+        word,
+        "uniform"
+            | "in"
+            | "out"
+            | "buffer"
+            | "flat"
+            | "smooth"
+            | "noperspective"
+            | "centroid"
+            | "sample"
+            | "readonly"
+            | "writeonly"
+            | "coherent"
+            | "restrict"
+            | "volatile"
+            | "highp"
+            | "mediump"
+            | "lowp"
+            | "invariant"
+    )
+}
+
+// "layout(std140)" + binding 3 -> "std140, binding = 3", leaving an existing binding alone
+fn with_qualifier(layout: &str, name: &str, value: u32) -> String {
+    if words(layout).iter().any(|word| word == name) {
+        return layout.to_string();
+    }
+    if layout.is_empty() {
+        format!("{} = {}", name, value)
+    } else {
+        format!("{}, {} = {}", layout, name, value)
+    }
+}
+
+// The names declared by the rest of a declaration, " a, b[4];" -> a, b with array "[4]".
+fn declared_names(rest: &str) -> Vec<Member> {
+    let rest = rest.split(';').next().unwrap_or_default();
+    rest.split(',')
+        .filter_map(|part| {
+            let part = part.split('=').next().unwrap_or_default().trim();
+            let name_end = part.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(part.len());
+            if name_end == 0 {
+                return None;
+            }
+            Some(Member {
+                ty: String::new(),
+                name: part[..name_end].to_string(),
+                array: part[name_end..].trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+const MATRIX_COLUMNS: [(&str, (&str, u32)); 3] = [("mat2", ("vec2", 2)), ("mat3", ("vec3", 3)), ("mat4", ("vec4", 4))];
+
+// the vertices of each geometry shader input primitive
+const INPUT_PRIMITIVES: [(&str, u32); 5] = [
+    ("points", 1),
+    ("lines", 2),
+    ("lines_adjacency", 4),
+    ("triangles", 3),
+    ("triangles_adjacency", 6),
+];
+
+// enough for any input primitive, until the shader's layout says which it is
+const GEOMETRY_MAX_INPUTS: u32 = 6;
+
+// geometry shader built-ins a vertex shader doesn't have, besides gl_in, and the globals standing in for them
+const GEOMETRY_BUILT_INS: [(&str, &str); 5] = [
+    ("gl_Layer", "gs_Layer"),
+    ("gl_ViewportIndex", "gs_ViewportIndex"),
+    ("gl_PrimitiveID", "gs_PrimitiveID"),
+    ("gl_PrimitiveIDIn", "gs_PrimitiveIDIn"),
+    ("gl_InvocationID", "gs_InvocationID"),
+];
+
+// words reserved by glsl 4.x that older shaders may use as names
+const NEW_KEYWORDS: [&str; 3] = ["sample", "patch", "subroutine"];
+
+// The texture and sampler types a combined sampler type is split into.
+fn sampler_types(ty: &str) -> Option<(&'static str, &'static str)> {
+    let types = match ty {
+        "sampler1D" => ("texture1D", "sampler"),
+        "sampler2D" => ("texture2D", "sampler"),
+        "sampler3D" => ("texture3D", "sampler"),
+        "samplerCube" => ("textureCube", "sampler"),
+        "sampler1DArray" => ("texture1DArray", "sampler"),
+        "sampler2DArray" => ("texture2DArray", "sampler"),
+        "samplerCubeArray" => ("textureCubeArray", "sampler"),
+        "sampler2DMS" => ("texture2DMS", "sampler"),
+        "sampler2DShadow" => ("texture2D", "samplerShadow"),
+        "sampler2DArrayShadow" => ("texture2DArray", "samplerShadow"),
+        "samplerCubeShadow" => ("textureCube", "samplerShadow"),
+        "isampler2D" => ("itexture2D", "sampler"),
+        "usampler2D" => ("utexture2D", "sampler"),
+        _ => return None,
+    };
+    Some(types)
+}
+
+// Replaces // and /* */ comments with spaces, keeping newlines so line numbers don't move.
+fn strip_comments(code: &str) -> String {
+    let mut stripped = String::with_capacity(code.len());
+    let mut chars = code.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+                    chars.next();
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        stripped.push('\n');
+                    }
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+                stripped.push(' ');
+            }
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+fn brace_balance(line: &str) -> i32 {
+    line.chars()
+        .map(|c| match c {
+            '{' => 1,
+            '}' => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+// Byte ranges of the words and single punctuation characters of a line.
+fn tokens(line: &str) -> Vec<(usize, usize)> {
+    let mut tokens = vec![];
+    let mut word_start: Option<usize> = None;
+    for (i, c) in line.char_indices() {
+        let is_word = c.is_alphanumeric() || c == '_';
+        match (is_word, word_start) {
+            (true, None) => word_start = Some(i),
+            (false, Some(start)) => {
+                tokens.push((start, i));
+                word_start = None;
+            }
+            _ => {}
+        }
+        if !is_word && !c.is_whitespace() {
+            tokens.push((i, i + c.len_utf8()));
+        }
+    }
+    if let Some(start) = word_start {
+        tokens.push((start, line.len()));
+    }
+    tokens
+}
+
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use learn_opengl_with_rust::shader_include::preprocess_code;
+
+    // Adapts code, checks no line moved and that naga parses the result. Returns the adapted lines
+    // with their whitespace squeezed, to compare against.
+    fn adapt(stage: Stage, code: &str) -> Vec<String> {
+        let (naga_stage, adapter) = match stage {
+            Stage::Naga(stage) => (stage, Adapter::default()),
+            Stage::Geometry => (ShaderStage::Vertex, Adapter::geometry()),
+            Stage::Unsupported(_) => unreachable!(),
+        };
+        let adapted = adapter.adapt(code);
+        assert_eq!(adapted.lines().count(), code.lines().count(), "lines added or removed:\n{}", adapted);
+        if let Err(errors) = Frontend::default().parse(&Options::from(naga_stage), &adapted) {
+            panic!(
+                "{:?}\n{}",
+                errors.iter().map(|error| error.kind.to_string()).collect::<Vec<String>>(),
+                adapted
+            );
+        }
+        adapted
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+            .collect()
+    }
+
+    fn check(stage: Stage, code: &str) -> Outcome {
+        let source = preprocess_code(code, "test.glsl", Path::new(".")).unwrap();
+        check_source("test.glsl", stage, &source)
+    }
+
+    fn failed_lines(outcome: Outcome) -> Vec<Option<u32>> {
+        match outcome {
+            Outcome::Failed(entries) => entries.iter().map(|entry| entry.line).collect(),
+            _ => panic!("expected the shader to fail"),
+        }
+    }
+
+    #[test]
+    fn version_and_new_keywords() {
+        let lines = adapt(
+            Stage::Naga(ShaderStage::Fragment),
+            "#version 330 core\nout vec4 FragColor;\nvoid main()\n{\n    float sample = 1.0;\n    FragColor = vec4(sample);\n}\n",
+        );
+        assert_eq!(lines[0], "#version 450 core");
+        assert_eq!(lines[4], "float sample_ = 1.0;");
+        assert_eq!(lines[5], "FragColor = vec4(sample_);");
+
+        // sample is a keyword from 400 on, so it can't be a name there and is left alone
+        let lines = adapt(
+            Stage::Naga(ShaderStage::Fragment),
+            "#version 450 core\nsample in vec4 color;\nvoid main() {}\n",
+        );
+        assert_eq!(lines[1], "layout(location = 0) sample in vec4 color;");
+    }
+
+    #[test]
+    fn samplers() {
+        let lines = adapt(
+            Stage::Naga(ShaderStage::Fragment),
+            "#version 330 core\nin vec2 uv;\nout vec4 FragColor;\nuniform sampler2D diffuse, specular;\n\
+             void main()\n{\n    FragColor = texture(diffuse, uv) + texture(specular, uv);\n}\n",
+        );
+        assert_eq!(
+            lines[3],
+            "layout(set = 1, binding = 0) uniform texture2D diffuse; layout(set = 2, binding = 0) uniform sampler diffuse_sampler; \
+             layout(set = 1, binding = 1) uniform texture2D specular; layout(set = 2, binding = 1) uniform sampler specular_sampler;"
+        );
+        assert_eq!(
+            lines[6],
+            "FragColor = texture(sampler2D(diffuse, diffuse_sampler), uv) + texture(sampler2D(specular, specular_sampler), uv);"
+        );
+    }
+
+    #[test]
+    fn structs_holding_samplers() {
+        let lines = adapt(
+            Stage::Naga(ShaderStage::Fragment),
+            "#version 330 core\nstruct Material {\n    sampler2D diffuse;\n    float shininess;\n};\nuniform Material material;\n\
+             in vec2 uv;\nout vec4 FragColor;\nvoid main()\n{\n    FragColor = texture(material.diffuse, uv) * material.shininess;\n}\n",
+        );
+        assert!(lines[1..5].iter().all(|line| line.is_empty()));
+        assert_eq!(
+            lines[5],
+            "layout(set = 1, binding = 0) uniform texture2D material_diffuse; \
+             layout(set = 2, binding = 0) uniform sampler material_diffuse_sampler; float material_shininess;"
+        );
+        assert_eq!(
+            lines[10],
+            "FragColor = texture(sampler2D(material_diffuse, material_diffuse_sampler), uv) * material_shininess;"
+        );
+    }
+
+    #[test]
+    fn uniforms() {
+        let lines = adapt(
+            Stage::Naga(ShaderStage::Vertex),
+            "#version 330 core\nlayout (std140) uniform Matrices\n{\n    mat4 projection;\n    mat4 view;\n};\n\
+             uniform mat4 model;\nuniform bool flip = false;\nlayout(location = 0) in vec3 aPos;\n\
+             void main()\n{\n    gl_Position = projection * view * model * vec4(flip ? -aPos : aPos, 1.0);\n}\n",
+        );
+        assert_eq!(lines[1], "layout(std140, binding = 0) uniform Matrices");
+        assert_eq!(lines[6], "mat4 model;");
+        assert_eq!(lines[7], "bool flip = false;");
+    }
+
+    #[test]
+    fn interface_blocks() {
+        let code = "#version 330 core\nlayout (location = 0) in vec3 aPos;\nout VS_OUT {\n    vec3 FragPos;\n    flat int id;\n} vs_out;\n\
+                    void main()\n{\n    vs_out.FragPos = aPos;\n    vs_out.id = 1;\n    gl_Position = vec4(aPos, 1.0);\n}\n";
+        let lines = adapt(Stage::Naga(ShaderStage::Vertex), code);
+        assert_eq!(
+            lines[2],
+            "layout(location = 0) out vec3 vs_out_FragPos; layout(location = 1) flat out int vs_out_id;"
+        );
+        assert!(lines[3..6].iter().all(|line| line.is_empty()));
+        assert_eq!(lines[8], "vs_out_FragPos = aPos;");
+        assert_eq!(lines[9], "vs_out_id = 1;");
+
+        let lines = adapt(
+            Stage::Naga(ShaderStage::Fragment),
+            "#version 330 core\nout vec4 FragColor;\nin VS_OUT\n{\n    vec3 FragPos;\n} fs_in;\nvoid main()\n{\n    FragColor = vec4(fs_in.FragPos, 1.0);\n}\n",
+        );
+        // the fragment output already took out location 0, inputs count separately
+        assert_eq!(lines[1], "layout(location = 0) out vec4 FragColor;");
+        assert_eq!(lines[2], "layout(location = 0) in vec3 fs_in_FragPos;");
+        assert_eq!(lines[8], "FragColor = vec4(fs_in_FragPos, 1.0);");
+    }
+
+    #[test]
+    fn locations() {
+        let lines = adapt(
+            Stage::Naga(ShaderStage::Vertex),
+            "#version 330 core\nlayout (location = 0) in vec3 aPos;\nin vec3 aNormal;\nlayout (location = 3) in mat4 aInstanceMatrix;\nin vec2 aTexCoords;\n\
+             out vec3 Normal;\nvoid main()\n{\n    Normal = aNormal;\n    gl_Position = aInstanceMatrix * vec4(aPos + vec3(aTexCoords, 0.0), 1.0);\n}\n",
+        );
+        // explicit locations are kept and skipped over, matrices take a location per column
+        assert_eq!(lines[1], "layout (location = 0) in vec3 aPos;");
+        assert_eq!(lines[2], "layout(location = 1) in vec3 aNormal;");
+        assert_eq!(
+            lines[3],
+            "layout(location = 3) in vec4 aInstanceMatrix_0; layout(location = 4) in vec4 aInstanceMatrix_1; \
+             layout(location = 5) in vec4 aInstanceMatrix_2; layout(location = 6) in vec4 aInstanceMatrix_3;"
+        );
+        assert_eq!(lines[4], "layout(location = 2) in vec2 aTexCoords;");
+        assert_eq!(lines[5], "layout(location = 0) out vec3 Normal;");
+        assert_eq!(
+            lines[9],
+            "gl_Position = mat4(aInstanceMatrix_0, aInstanceMatrix_1, aInstanceMatrix_2, aInstanceMatrix_3) * vec4(aPos + vec3(aTexCoords, 0.0), 1.0);"
+        );
+    }
+
+    #[test]
+    fn comments_keep_lines() {
+        let lines = adapt(
+            Stage::Naga(ShaderStage::Fragment),
+            "#version 330 core\n/* a comment\n   over lines */ out vec4 FragColor; // and one after\nvoid main() { FragColor = vec4(1.0); }\n",
+        );
+        assert_eq!(lines[1], "");
+        assert_eq!(lines[2], "layout(location = 0) out vec4 FragColor;");
+    }
+
+    const GEOMETRY: &str = "#version 330 core\nlayout (triangles) in;\nlayout (line_strip, max_vertices = 6) out;\n\
+                            in VS_OUT {\n    vec3 normal;\n} gs_in[];\nin float depth[];\nout vec3 color;\nuniform mat4 projection;\n\
+                            void main()\n{\n    for (int i = 0; i < 3; i++) {\n        gl_Layer = i;\n\
+                                    gl_Position = projection * (gl_in[i].gl_Position + vec4(gs_in[i].normal * depth[i], 0.0));\n\
+                                    color = gs_in[i].normal;\n        EmitVertex();\n    }\n    EndPrimitive();\n}\n";
+
+    #[test]
+    fn geometry_shaders() {
+        let lines = adapt(Stage::Geometry, GEOMETRY);
+        assert!(lines[1].starts_with("struct gs_PerVertex { vec4 gl_Position; float gl_PointSize; }; gs_PerVertex gs_gl_in[3];"));
+        assert!(lines[1].contains("int gs_Layer;"));
+        assert_eq!(lines[2], "void EmitVertex() {} void EndPrimitive() {}");
+        assert_eq!(lines[3], "struct VS_OUT { vec3 normal; }; VS_OUT gs_in[3];");
+        assert!(lines[4..6].iter().all(|line| line.is_empty()));
+        assert_eq!(lines[6], "float depth[3];");
+        assert_eq!(lines[7], "layout(location = 0) out vec3 color;");
+        assert_eq!(lines[12], "gs_Layer = i;");
+        assert_eq!(
+            lines[13],
+            "gl_Position = projection * (gs_gl_in[i].gl_Position + vec4(gs_in[i].normal * depth[i], 0.0));"
+        );
+
+        // the input primitive sizes the arrays, wherever it is declared
+        let points = GEOMETRY.replace("layout (triangles) in;", "layout (points) in;");
+        let lines = adapt(Stage::Geometry, &points);
+        assert_eq!(lines[3], "struct VS_OUT { vec3 normal; }; VS_OUT gs_in[1];");
+    }
+
+    #[test]
+    fn geometry_errors_point_at_their_line() {
+        assert!(matches!(check(Stage::Geometry, GEOMETRY), Outcome::Passed));
+        let broken = GEOMETRY.replace("color = gs_in[i].normal;", "color = gs_in[i].normal");
+        assert_eq!(failed_lines(check(Stage::Geometry, &broken)), vec![Some(16)]);
+        let broken = GEOMETRY.replace("color = gs_in[i].normal;", "color = gs_in[i].missing;");
+        assert_eq!(failed_lines(check(Stage::Geometry, &broken)), vec![Some(15)]);
+    }
+
+    #[test]
+    fn tessellation_is_skipped() {
+        assert!(matches!(check_file("shader.tesc"), Outcome::Skipped(_)));
+    }
+}
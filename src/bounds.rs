@@ -0,0 +1,293 @@
+#![allow(dead_code)]
+
+use glam::*;
+
+// An axis aligned bounding box. The empty box has min > max, so growing it by a point gives a box
+// around just that point, and it is left out of unions.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub const EMPTY: Aabb = Aabb {
+        min: Vec3::splat(f32::INFINITY),
+        max: Vec3::splat(f32::NEG_INFINITY),
+    };
+
+    pub fn new(min: Vec3, max: Vec3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Aabb {
+        points.into_iter().fold(Aabb::EMPTY, |aabb, point| aabb.grow(point))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub fn grow(&self, point: Vec3) -> Aabb {
+        Aabb::new(self.min.min(point), self.max.max(point))
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    // half the size, the distance from the center to the faces
+    pub fn extents(&self) -> Vec3 {
+        self.size() * 0.5
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min).all() && point.cmple(self.max).all()
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            vec3(min.x, min.y, min.z),
+            vec3(max.x, min.y, min.z),
+            vec3(min.x, max.y, min.z),
+            vec3(max.x, max.y, min.z),
+            vec3(min.x, min.y, max.z),
+            vec3(max.x, min.y, max.z),
+            vec3(min.x, max.y, max.z),
+            vec3(max.x, max.y, max.z),
+        ]
+    }
+
+    // The box around this box after transforming it by matrix. Rotations make it larger than the
+    // transformed contents, since the corners of the old box stick out.
+    pub fn transform(&self, matrix: &Mat4) -> Aabb {
+        if self.is_empty() {
+            return *self;
+        }
+        // each axis of the matrix moves the box by its column times somewhere between min and max
+        let center = matrix.transform_point3(self.center());
+        let extents = self.extents();
+        let half = matrix.x_axis.truncate().abs() * extents.x
+            + matrix.y_axis.truncate().abs() * extents.y
+            + matrix.z_axis.truncate().abs() * extents.z;
+        Aabb::new(center - half, center + half)
+    }
+}
+
+impl Default for Aabb {
+    fn default() -> Self {
+        Aabb::EMPTY
+    }
+}
+
+// A bounding sphere, cheaper to test against view frustum planes and distances than a box.
+// A negative radius marks the empty sphere.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub const EMPTY: BoundingSphere = BoundingSphere {
+        center: Vec3::ZERO,
+        radius: -1.0,
+    };
+
+    pub fn new(center: Vec3, radius: f32) -> BoundingSphere {
+        BoundingSphere { center, radius }
+    }
+
+    // Centered on the box around the points, which is close to the smallest sphere for most meshes
+    // and never further off than the sphere around the box.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3> + Clone) -> BoundingSphere {
+        let aabb = Aabb::from_points(points.clone());
+        if aabb.is_empty() {
+            return BoundingSphere::EMPTY;
+        }
+        let center = aabb.center();
+        let radius_squared = points.into_iter().map(|point| point.distance_squared(center)).fold(0.0, f32::max);
+        BoundingSphere::new(center, radius_squared.sqrt())
+    }
+
+    // the sphere through the corners of the box
+    pub fn from_aabb(aabb: &Aabb) -> BoundingSphere {
+        if aabb.is_empty() {
+            return BoundingSphere::EMPTY;
+        }
+        BoundingSphere::new(aabb.center(), aabb.extents().length())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.radius < 0.0
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.distance_squared(self.center) <= self.radius * self.radius
+    }
+
+    // the smallest sphere around both spheres
+    pub fn union(&self, other: &BoundingSphere) -> BoundingSphere {
+        if self.is_empty() {
+            return *other;
+        }
+        if other.is_empty() {
+            return *self;
+        }
+        let offset = other.center - self.center;
+        let distance = offset.length();
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+        let radius = (distance + self.radius + other.radius) * 0.5;
+        let center = self.center + offset * ((radius - self.radius) / distance);
+        BoundingSphere::new(center, radius)
+    }
+
+    // The sphere after transforming by matrix. With a non uniform scale the sphere becomes an
+    // ellipsoid, so the radius is scaled by the largest of the three scales.
+    pub fn transform(&self, matrix: &Mat4) -> BoundingSphere {
+        if self.is_empty() {
+            return *self;
+        }
+        BoundingSphere::new(matrix.transform_point3(self.center), self.radius * max_scale(matrix))
+    }
+}
+
+impl Default for BoundingSphere {
+    fn default() -> Self {
+        BoundingSphere::EMPTY
+    }
+}
+
+// The largest scale of the three axes of a transform, how much it can lengthen a distance at most.
+pub fn max_scale(matrix: &Mat4) -> f32 {
+    matrix
+        .x_axis
+        .truncate()
+        .length()
+        .max(matrix.y_axis.truncate().length())
+        .max(matrix.z_axis.truncate().length())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_4;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn box_from_points() {
+        let aabb = Aabb::from_points([vec3(1.0, -2.0, 0.0), vec3(-1.0, 3.0, 0.5), vec3(0.0, 0.0, -4.0)]);
+        assert_eq!(aabb, Aabb::new(vec3(-1.0, -2.0, -4.0), vec3(1.0, 3.0, 0.5)));
+        assert_eq!(aabb.center(), vec3(0.0, 0.5, -1.75));
+        assert!(aabb.contains(Vec3::ZERO) && !aabb.contains(vec3(0.0, 4.0, 0.0)));
+    }
+
+    #[test]
+    fn rotated_box_grows() {
+        let aabb = Aabb::new(vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0));
+        // a quarter turn about y fits the box exactly, an eighth turn pushes the corners out to sqrt(2) in x and z
+        let quarter = aabb.transform(&Mat4::from_rotation_y(2.0 * FRAC_PI_4));
+        assert_near(quarter.min, aabb.min);
+        assert_near(quarter.max, aabb.max);
+        let matrix = Mat4::from_translation(vec3(5.0, 0.0, 0.0)) * Mat4::from_rotation_y(FRAC_PI_4);
+        let eighth = aabb.transform(&matrix);
+        let half = 2.0f32.sqrt();
+        assert_near(eighth.min, vec3(5.0 - half, -1.0, -half));
+        assert_near(eighth.max, vec3(5.0 + half, 1.0, half));
+        // and holds every transformed corner
+        let padded = Aabb::new(eighth.min - 1e-5, eighth.max + 1e-5);
+        assert!(aabb.corners().iter().all(|&corner| padded.contains(matrix.transform_point3(corner))));
+    }
+
+    #[test]
+    fn box_union() {
+        let a = Aabb::new(Vec3::ZERO, Vec3::ONE);
+        let b = Aabb::new(vec3(2.0, -1.0, 0.0), vec3(3.0, 0.0, 1.0));
+        assert_eq!(a.union(&b), Aabb::new(vec3(0.0, -1.0, 0.0), vec3(3.0, 1.0, 1.0)));
+        assert!(!a.intersects(&b));
+    }
+
+    #[test]
+    fn empty_box() {
+        let empty = Aabb::from_points([]);
+        assert!(empty.is_empty());
+        assert_eq!(empty, Aabb::default());
+        assert!(empty.transform(&Mat4::from_scale(Vec3::splat(2.0))).is_empty());
+        // the empty box is left out of unions and grows into a box around a single point
+        let a = Aabb::new(Vec3::ZERO, Vec3::ONE);
+        assert_eq!(empty.union(&a), a);
+        assert_eq!(empty.grow(Vec3::ONE), Aabb::new(Vec3::ONE, Vec3::ONE));
+        assert!(!empty.contains(Vec3::ZERO));
+    }
+
+    #[test]
+    fn sphere_takes_the_largest_scale() {
+        let sphere = BoundingSphere::new(vec3(1.0, 0.0, 0.0), 2.0);
+        let matrix = Mat4::from_translation(vec3(0.0, 10.0, 0.0)) * Mat4::from_scale(vec3(1.0, 3.0, 0.5));
+        assert_eq!(max_scale(&matrix), 3.0);
+        let transformed = sphere.transform(&matrix);
+        assert_near(transformed.center, vec3(1.0, 10.0, 0.0));
+        assert_eq!(transformed.radius, 6.0);
+        // rotations don't change the scale
+        assert!((max_scale(&Mat4::from_rotation_z(1.0)) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn union_of_disjoint_spheres_contains_both() {
+        let a = BoundingSphere::new(vec3(-3.0, 0.0, 0.0), 1.0);
+        let b = BoundingSphere::new(vec3(4.0, 1.0, 0.0), 2.0);
+        let union = a.union(&b);
+        for (sphere, direction) in [(a, Vec3::NEG_X), (b, Vec3::X), (a, Vec3::Y), (b, Vec3::NEG_Y)] {
+            let far_side = sphere.center + direction * sphere.radius;
+            assert!(union.center.distance(far_side) <= union.radius + 1e-5);
+        }
+        // it touches both, so nothing smaller holds them
+        assert!((union.radius - (a.center.distance(b.center) + a.radius + b.radius) * 0.5).abs() < 1e-5);
+
+        // a sphere inside the other adds nothing
+        let inner = BoundingSphere::new(vec3(4.5, 1.0, 0.0), 0.5);
+        assert_eq!(b.union(&inner), b);
+        assert_eq!(inner.union(&b), b);
+    }
+
+    #[test]
+    fn empty_sphere() {
+        let empty = BoundingSphere::from_points(std::iter::empty::<Vec3>());
+        assert!(empty.is_empty());
+        assert!(BoundingSphere::from_aabb(&Aabb::EMPTY).is_empty());
+        assert!(empty.transform(&Mat4::from_scale(Vec3::splat(2.0))).is_empty());
+        let a = BoundingSphere::new(Vec3::ONE, 1.0);
+        assert_eq!(empty.union(&a), a);
+        assert_eq!(a.union(&empty), a);
+    }
+
+    #[test]
+    fn sphere_from_points() {
+        let points = [vec3(-1.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 0.5, 0.0)];
+        let sphere = BoundingSphere::from_points(points);
+        assert_near(sphere.center, vec3(0.0, 0.25, 0.0));
+        assert!(points.iter().all(|&point| sphere.contains(point)));
+        assert!(sphere.radius <= BoundingSphere::from_aabb(&Aabb::from_points(points)).radius);
+    }
+}
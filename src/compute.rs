@@ -0,0 +1,251 @@
+#![allow(dead_code)]
+
+use glad_gl::gl;
+use glad_gl::gl::{GLbitfield, GLenum, GLint, GLintptr, GLsizeiptr, GLuint, GLvoid};
+
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::Deref;
+use std::ptr;
+
+use crate::shader::Shader;
+use crate::shader_error::ShaderError;
+use crate::shader_program::{ShaderProgram, ShaderStage};
+
+// A program made of a single compute stage. Derefs to Shader for the uniform setters.
+//
+// example:
+//
+//    let compute = ComputeShader::new("computeShader.comp").unwrap();
+//    bind_image_texture(0, texture, 0, ImageAccess::ReadWrite, gl::RGBA32F);
+//    compute.set_float("t", currentFrame);
+//    compute.dispatch_for_size(TEXTURE_WIDTH, TEXTURE_HEIGHT, 1);
+//    memory_barrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
+//
+pub struct ComputeShader {
+    shader: Shader,
+    work_group_size: [u32; 3],
+}
+
+impl ComputeShader {
+    pub fn new(path: &str) -> Result<ComputeShader, ShaderError> {
+        ComputeShader::from_program(ShaderProgram::new().compute_file(path))
+    }
+
+    pub fn from_program(program: ShaderProgram) -> Result<ComputeShader, ShaderError> {
+        if !program.has_stage(ShaderStage::Compute) {
+            return Err(ShaderError::InvalidProgram("ComputeShader needs a compute stage".to_string()));
+        }
+        let shader = program.build()?;
+
+        // the local_size_x/y/z the shader was compiled with
+        let mut size: [GLint; 3] = [0; 3];
+        unsafe {
+            gl::GetProgramiv(shader.id, gl::COMPUTE_WORK_GROUP_SIZE, size.as_mut_ptr());
+        }
+
+        Ok(ComputeShader {
+            shader,
+            work_group_size: [size[0] as u32, size[1] as u32, size[2] as u32],
+        })
+    }
+
+    pub fn shader(&self) -> &Shader {
+        &self.shader
+    }
+
+    pub fn work_group_size(&self) -> [u32; 3] {
+        self.work_group_size
+    }
+
+    // Runs x * y * z work groups.
+    pub fn dispatch(&self, x: u32, y: u32, z: u32) {
+        unsafe {
+            gl::UseProgram(self.shader.id);
+            gl::DispatchCompute(x, y, z);
+        }
+    }
+
+    // Runs enough work groups to cover width * height * depth invocations.
+    pub fn dispatch_for_size(&self, width: u32, height: u32, depth: u32) {
+        let [x, y, z] = self.work_group_size;
+        self.dispatch(width.div_ceil(x.max(1)), height.div_ceil(y.max(1)), depth.div_ceil(z.max(1)));
+    }
+
+    // The largest number of work groups the driver accepts along each axis.
+    pub fn max_work_group_count() -> [u32; 3] {
+        let mut count: [GLint; 3] = [0; 3];
+        unsafe {
+            for (axis, value) in count.iter_mut().enumerate() {
+                gl::GetIntegeri_v(gl::MAX_COMPUTE_WORK_GROUP_COUNT, axis as GLuint, value);
+            }
+        }
+        [count[0] as u32, count[1] as u32, count[2] as u32]
+    }
+}
+
+impl Deref for ComputeShader {
+    type Target = Shader;
+
+    fn deref(&self) -> &Shader {
+        &self.shader
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageAccess {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+impl ImageAccess {
+    pub fn gl_access(&self) -> GLenum {
+        match self {
+            ImageAccess::ReadOnly => gl::READ_ONLY,
+            ImageAccess::WriteOnly => gl::WRITE_ONLY,
+            ImageAccess::ReadWrite => gl::READ_WRITE,
+        }
+    }
+}
+
+// Binds a level of a texture to an image unit for imageLoad / imageStore. format is the sized internal
+// format the shader sees, e.g. gl::RGBA32F for layout(rgba32f).
+pub fn bind_image_texture(unit: u32, texture: GLuint, level: i32, access: ImageAccess, format: GLenum) {
+    unsafe {
+        gl::BindImageTexture(unit, texture, level, gl::FALSE, 0, access.gl_access(), format);
+    }
+}
+
+// Binds every layer of an array, cube or 3D texture level to an image unit.
+pub fn bind_image_texture_layered(unit: u32, texture: GLuint, level: i32, access: ImageAccess, format: GLenum) {
+    unsafe {
+        gl::BindImageTexture(unit, texture, level, gl::TRUE, 0, access.gl_access(), format);
+    }
+}
+
+// Makes writes from earlier dispatches visible to the kinds of access named by barriers.
+pub fn memory_barrier(barriers: GLbitfield) {
+    unsafe {
+        gl::MemoryBarrier(barriers);
+    }
+}
+
+// Before sampling or loading an image written by imageStore.
+pub fn image_barrier() {
+    memory_barrier(gl::SHADER_IMAGE_ACCESS_BARRIER_BIT | gl::TEXTURE_FETCH_BARRIER_BIT);
+}
+
+// Before reading a storage buffer written by a previous dispatch.
+pub fn storage_barrier() {
+    memory_barrier(gl::SHADER_STORAGE_BARRIER_BIT);
+}
+
+// A shader storage buffer of T, allocated once with glBufferStorage.
+//
+// T must match the std430 layout of the buffer block, which for scalars, vec2, vec4 and
+// structs of those is the same as #[repr(C)].
+pub struct StorageBuffer<T: Copy> {
+    pub id: GLuint,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Copy> StorageBuffer<T> {
+    pub fn new(data: &[T]) -> StorageBuffer<T> {
+        if data.is_empty() {
+            return StorageBuffer::with_len(0);
+        }
+        StorageBuffer::allocate(data.len(), data.as_ptr() as *const GLvoid)
+    }
+
+    // A zero initialized buffer of len elements.
+    pub fn with_len(len: usize) -> StorageBuffer<T> {
+        let zeros = vec![0u8; StorageBuffer::<T>::storage_size(len)];
+        StorageBuffer::allocate(len, zeros.as_ptr() as *const GLvoid)
+    }
+
+    // glBufferStorage fails with GL_INVALID_VALUE for a size of 0, so an empty buffer still gets room for one element.
+    fn storage_size(len: usize) -> usize {
+        len.max(1) * mem::size_of::<T>()
+    }
+
+    // data must point to storage_size(len) bytes
+    fn allocate(len: usize, data: *const GLvoid) -> StorageBuffer<T> {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, id);
+            gl::BufferStorage(
+                gl::SHADER_STORAGE_BUFFER,
+                StorageBuffer::<T>::storage_size(len) as GLsizeiptr,
+                data,
+                gl::DYNAMIC_STORAGE_BIT | gl::MAP_READ_BIT,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+        StorageBuffer {
+            id,
+            len,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Overwrites elements starting at offset. Panics if data runs past the end of the buffer.
+    pub fn update(&self, offset: usize, data: &[T]) {
+        assert!(offset + data.len() <= self.len, "StorageBuffer update out of range");
+        unsafe {
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+            gl::BufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                (offset * mem::size_of::<T>()) as GLintptr,
+                mem::size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const GLvoid,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+        }
+    }
+
+    // Copies the buffer back to the cpu. Issue storage_barrier() or a fence after the writing dispatch first.
+    pub fn read(&self) -> Vec<T> {
+        let mut data: Vec<T> = Vec::with_capacity(self.len);
+        let spare = &mut data.spare_capacity_mut()[..self.len];
+        unsafe {
+            // zeroed first, so nothing uninitialized ends up in the vector if the driver writes less
+            ptr::write_bytes(spare.as_mut_ptr(), 0, self.len);
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+            gl::GetBufferSubData(
+                gl::SHADER_STORAGE_BUFFER,
+                0,
+                mem::size_of_val(spare) as GLsizeiptr,
+                spare.as_mut_ptr() as *mut GLvoid,
+            );
+            gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, 0);
+            data.set_len(self.len);
+        }
+        data
+    }
+
+    // Attaches the buffer to a shader storage binding point, the layout(binding = N) of a buffer block.
+    pub fn bind(&self, binding: GLuint) {
+        unsafe {
+            gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, self.id);
+        }
+    }
+}
+
+impl<T: Copy> Drop for StorageBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
@@ -0,0 +1,166 @@
+#![allow(dead_code)]
+
+use glad_gl::gl;
+use glad_gl::gl::{GLsizeiptr, GLuint, GLvoid};
+use glam::*;
+use std::marker::PhantomData;
+use std::ptr;
+use std::rc::{Rc, Weak};
+
+use crate::stream_buffer::BufferUsage;
+use crate::vertex_attributes::{attribute, VertexAttribute, VertexLayout};
+
+// A buffer of per instance data for Mesh::draw_instanced and Model::draw_instanced, in any
+// VertexLayout. The attributes of T are placed from first_location on, so they follow the vertex
+// attributes the shader reads. Instance attributes take the place of vertex attributes at the same
+// locations in the vertex array each mesh makes for drawing with the buffer, the mesh's own vertex
+// array is left as it is.
+//
+// example, for a shader with layout (location = 3) in mat4 aInstanceMatrix:
+//
+//    let instances: InstanceBuffer<Mat4> = InstanceBuffer::new(&modelMatrices, 3, BufferUsage::Static);
+//    rock.draw_instanced(&asteroidShader, &instances);
+//
+// or with more than a matrix per instance:
+//
+//    vertex_layout! {
+//        #[derive(Copy, Clone)]
+//        pub struct Particle {
+//            pub Model: Mat4,
+//            pub Color: Vec4,
+//        }
+//    }
+//
+//    let mut particles: InstanceBuffer<Particle> = InstanceBuffer::new(&[], 7, BufferUsage::Stream);
+//    // each frame
+//    particles.update(&live_particles);
+//    quad.draw_instanced(&particleShader, &particles);
+//
+#[derive(Debug)]
+pub struct InstanceBuffer<T: VertexLayout> {
+    pub id: GLuint,
+    pub usage: BufferUsage,
+    // the attributes of T moved to start at first_location
+    attributes: Vec<VertexAttribute>,
+    len: usize,
+    // instances the buffer has room for before it is reallocated
+    capacity: usize,
+    // Meshes keep a Weak of this with the vertex array they made for the buffer. Unlike the buffer id
+    // it can't be handed out again while a mesh still holds it, and it tells them when the buffer is gone.
+    token: Rc<()>,
+    instance: PhantomData<T>,
+}
+
+impl<T: VertexLayout> InstanceBuffer<T> {
+    pub fn new(instances: &[T], first_location: GLuint, usage: BufferUsage) -> InstanceBuffer<T> {
+        let attributes = T::attributes()
+            .iter()
+            .map(|attribute| VertexAttribute {
+                location: attribute.location + first_location,
+                ..*attribute
+            })
+            .collect();
+        let mut buffer = InstanceBuffer {
+            id: 0,
+            usage,
+            attributes,
+            len: 0,
+            capacity: 0,
+            token: Rc::new(()),
+            instance: PhantomData,
+        };
+        unsafe {
+            gl::GenBuffers(1, &mut buffer.id);
+        }
+        buffer.update(instances);
+        buffer
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn attributes(&self) -> &[VertexAttribute] {
+        &self.attributes
+    }
+
+    pub(crate) fn token(&self) -> Weak<()> {
+        Rc::downgrade(&self.token)
+    }
+
+    pub(crate) fn is_token(&self, token: &Weak<()>) -> bool {
+        Weak::ptr_eq(token, &Rc::downgrade(&self.token))
+    }
+
+    // Replaces the instances, orphaning the old storage so draws still reading it don't stall the update.
+    // The buffer keeps its id, so meshes already set up to draw with it don't need setting up again.
+    pub fn update(&mut self, instances: &[T]) {
+        if instances.len() > self.capacity {
+            // grow by half again, so a count that creeps up doesn't reallocate every frame
+            self.capacity = instances.len().max(self.capacity + self.capacity / 2);
+        }
+        self.len = instances.len();
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.id);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                (self.capacity * T::stride()) as GLsizeiptr,
+                ptr::null(),
+                self.usage.gl_usage(),
+            );
+            if !instances.is_empty() {
+                gl::BufferSubData(
+                    gl::ARRAY_BUFFER,
+                    0,
+                    (instances.len() * T::stride()) as GLsizeiptr,
+                    instances.as_ptr() as *const GLvoid,
+                );
+            }
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    // Points the instance attributes at this buffer, advancing once per instance, on the vertex
+    // array that is bound. Mesh::draw_instanced does this on a vertex array of its own.
+    pub fn bind_attributes(&self) {
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.id);
+        }
+        for attribute in &self.attributes {
+            attribute.enable(T::stride());
+            attribute.set_divisor(1);
+        }
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+}
+
+impl<T: VertexLayout> Drop for InstanceBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
+// model matrices, the most common instance data, as a mat4 at the first location
+static MAT4_ATTRIBUTES: [VertexAttribute; 1] = [attribute::<Mat4>("Model", 0, 0)];
+
+impl VertexLayout for Mat4 {
+    fn attributes() -> &'static [VertexAttribute] {
+        &MAT4_ATTRIBUTES
+    }
+
+    fn position(&self) -> Vec3 {
+        self.w_axis.truncate()
+    }
+}
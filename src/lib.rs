@@ -1,13 +1,36 @@
 #![feature(offset_of)]
 
 pub mod aiscene;
+pub mod assets;
+pub mod batch;
+pub mod bounds;
 pub mod camera;
+pub mod compute;
+pub mod instance_buffer;
+pub mod lights;
+pub mod lod;
 pub mod macros;
 pub mod mesh;
+pub mod mesh_optimizer;
 pub mod model;
+pub mod primitives;
 pub mod shader;
+pub mod shader_cache;
+pub mod shader_error;
+pub mod shader_include;
 pub mod shader_m;
+pub mod shader_program;
+pub mod shader_reload;
 pub mod shader_s;
+pub mod shader_variants;
+pub mod simplify;
+pub mod stream_buffer;
+pub mod tangent_space;
+pub mod texture_units;
+pub mod uniform_buffer;
+pub mod uniform_struct;
+pub mod uniforms;
+pub mod vertex_attributes;
 
 type ShaderId = u32;
 
@@ -0,0 +1,138 @@
+#![allow(dead_code)]
+#![allow(non_snake_case)]
+
+use glam::*;
+
+use crate::shader::Shader;
+use crate::uniform_struct;
+
+// The light structs of the lighting chapters, laid out with the same member names as the glsl
+// so they can be uploaded with Shader::set_struct.
+
+uniform_struct! {
+    #[derive(Debug, Copy, Clone)]
+    pub struct DirLight {
+        pub direction: Vec3,
+        pub ambient: Vec3,
+        pub diffuse: Vec3,
+        pub specular: Vec3,
+    }
+}
+
+uniform_struct! {
+    #[derive(Debug, Copy, Clone)]
+    pub struct PointLight {
+        pub position: Vec3,
+        pub constant: f32,
+        pub linear: f32,
+        pub quadratic: f32,
+        pub ambient: Vec3,
+        pub diffuse: Vec3,
+        pub specular: Vec3,
+    }
+}
+
+uniform_struct! {
+    #[derive(Debug, Copy, Clone)]
+    pub struct SpotLight {
+        pub position: Vec3,
+        pub direction: Vec3,
+        pub cutOff: f32,
+        pub outerCutOff: f32,
+        pub constant: f32,
+        pub linear: f32,
+        pub quadratic: f32,
+        pub ambient: Vec3,
+        pub diffuse: Vec3,
+        pub specular: Vec3,
+    }
+}
+
+impl DirLight {
+    pub fn new(direction: Vec3, ambient: Vec3, diffuse: Vec3, specular: Vec3) -> DirLight {
+        DirLight {
+            direction,
+            ambient,
+            diffuse,
+            specular,
+        }
+    }
+}
+
+impl PointLight {
+    // attenuation defaults to the book's values for a range of about 50 units
+    pub fn new(position: Vec3, ambient: Vec3, diffuse: Vec3, specular: Vec3) -> PointLight {
+        PointLight {
+            position,
+            constant: 1.0,
+            linear: 0.09,
+            quadratic: 0.032,
+            ambient,
+            diffuse,
+            specular,
+        }
+    }
+
+    pub fn attenuation(mut self, constant: f32, linear: f32, quadratic: f32) -> PointLight {
+        self.constant = constant;
+        self.linear = linear;
+        self.quadratic = quadratic;
+        self
+    }
+}
+
+impl SpotLight {
+    // cut off angles are in degrees, the struct holds their cosines like the shader expects
+    pub fn new(position: Vec3, direction: Vec3, cutOff: f32, outerCutOff: f32, ambient: Vec3, diffuse: Vec3, specular: Vec3) -> SpotLight {
+        SpotLight {
+            position,
+            direction,
+            cutOff: cutOff.to_radians().cos(),
+            outerCutOff: outerCutOff.to_radians().cos(),
+            constant: 1.0,
+            linear: 0.09,
+            quadratic: 0.032,
+            ambient,
+            diffuse,
+            specular,
+        }
+    }
+
+    pub fn attenuation(mut self, constant: f32, linear: f32, quadratic: f32) -> SpotLight {
+        self.constant = constant;
+        self.linear = linear;
+        self.quadratic = quadratic;
+        self
+    }
+}
+
+// The lights of a scene, uploaded to the dirLight, pointLights and spotLight uniforms used by
+// the multiple lights shaders.
+//
+// example:
+//
+//    let mut lights = Lights::default();
+//    lights.dirLight = Some(DirLight::new(vec3(-0.2, -1.0, -0.3), ambient, diffuse, specular));
+//    lights.pointLights.push(PointLight::new(vec3(0.7, 0.2, 2.0), ambient, diffuse, specular));
+//    ...
+//    lightingShader.use_shader();
+//    lights.upload(&lightingShader);
+//
+#[derive(Debug, Clone, Default)]
+pub struct Lights {
+    pub dirLight: Option<DirLight>,
+    pub pointLights: Vec<PointLight>,
+    pub spotLight: Option<SpotLight>,
+}
+
+impl Lights {
+    pub fn upload(&self, shader: &Shader) {
+        if let Some(dirLight) = &self.dirLight {
+            shader.set_struct("dirLight", dirLight);
+        }
+        shader.set_struct("pointLights", self.pointLights.as_slice());
+        if let Some(spotLight) = &self.spotLight {
+            shader.set_struct("spotLight", spotLight);
+        }
+    }
+}
@@ -0,0 +1,291 @@
+#![allow(dead_code)]
+
+use glad_gl::gl::GLuint;
+use glam::*;
+
+use crate::bounds::{max_scale, BoundingSphere};
+use crate::camera::Camera;
+use crate::instance_buffer::InstanceBuffer;
+use crate::mesh::Mesh;
+use crate::model::Model;
+use crate::shader::Shader;
+use crate::simplify::simplify_mesh;
+use crate::stream_buffer::BufferUsage;
+use crate::vertex_attributes::VertexLayout;
+
+// Levels of detail: simplified copies of a model, and picking one for each copy drawn by how large
+// its simplification error would be on screen.
+//
+// example, the asteroid field with a bucket of instances per level:
+//
+//    let rocks = LodChain::generate(rock, &[0.5, 0.25, 0.1]);
+//    let mut instances: LodInstances<Mat4> = LodInstances::new(&rocks, 3, BufferUsage::Stream);
+//    ...
+//    // each frame
+//    let selector = LodSelector::new(&camera, SCR_HEIGHT, 1.0);
+//    instances.assign(&selector, &rocks, &modelMatrices, |model| *model);
+//    instances.draw(&rocks, &asteroidShader);
+//
+
+#[derive(Debug)]
+pub struct LodLevel {
+    pub model: Model,
+    // how far the surface of this level may be from the full model, in model units
+    pub error: f32,
+}
+
+// A model and its levels of detail, from the full model at level 0 to the coarsest.
+#[derive(Debug)]
+pub struct LodChain {
+    pub levels: Vec<LodLevel>,
+}
+
+impl LodChain {
+    // Simplifies every mesh of model to each of the ratios of its triangles, like [0.5, 0.25, 0.1],
+    // with quadric edge collapse, see simplify. The simplified meshes share the model's textures.
+    pub fn generate(model: Model, ratios: &[f32]) -> LodChain {
+        let mut level_meshes: Vec<Vec<Mesh>> = vec![];
+        let mut mesh_errors: Vec<Vec<f32>> = vec![];
+        for &ratio in ratios {
+            let (meshes, errors) = model.meshes.iter().map(|mesh| simplify_mesh(mesh, ratio, f32::MAX)).unzip();
+            level_meshes.push(meshes);
+            mesh_errors.push(errors);
+        }
+        let errors = level_errors(&mesh_errors, &model.mesh_transforms);
+
+        let mut levels: Vec<LodLevel> = level_meshes
+            .into_iter()
+            .zip(errors)
+            .map(|(meshes, error)| LodLevel {
+                model: Model::from_meshes(meshes, model.mesh_transforms.clone()),
+                error,
+            })
+            .collect();
+        levels.insert(0, LodLevel { model, error: 0.0 });
+        LodChain { levels }
+    }
+
+    // a chain from levels made some other way, like loaded from files, finest first
+    pub fn from_levels(levels: Vec<LodLevel>) -> LodChain {
+        assert!(!levels.is_empty(), "a LodChain needs at least the full model as its first level");
+        LodChain { levels }
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    pub fn level(&self, level: usize) -> &Model {
+        &self.levels[level].model
+    }
+
+    // the bounds of the full model
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        self.levels[0].model.bounding_sphere
+    }
+
+    pub fn draw(&self, level: usize, shader: &Shader) {
+        self.levels[level].model.draw(shader);
+    }
+}
+
+// The error of each level from the simplification errors of its meshes, the largest of them scaled by
+// the transforms of the meshes. Coarser levels are never taken to be closer to the model than finer ones.
+fn level_errors(mesh_errors: &[Vec<f32>], transforms: &[Mat4]) -> Vec<f32> {
+    let mut error: f32 = 0.0;
+    mesh_errors
+        .iter()
+        .map(|errors| {
+            for (mesh_error, transform) in errors.iter().zip(transforms) {
+                error = error.max(mesh_error * max_scale(transform));
+            }
+            error
+        })
+        .collect()
+}
+
+// Picks levels of detail for a camera: the coarsest level whose error covers no more than pixel_error
+// pixels on screen, at the distance of the nearest point of the model's bounding sphere.
+#[derive(Debug, Copy, Clone)]
+pub struct LodSelector {
+    pub eye: Vec3,
+    // pixels covered by one unit at a distance of one unit
+    pub pixels_per_unit: f32,
+    pub pixel_error: f32,
+}
+
+impl LodSelector {
+    // screen_height in pixels, with the camera's Zoom as the vertical field of view
+    pub fn new(camera: &Camera, screen_height: f32, pixel_error: f32) -> LodSelector {
+        LodSelector {
+            eye: camera.Position,
+            pixels_per_unit: screen_height / (2.0 * (camera.Zoom.to_radians() * 0.5).tan()),
+            pixel_error,
+        }
+    }
+
+    // how many pixels a length covers at distance from the eye
+    pub fn pixels(&self, length: f32, distance: f32) -> f32 {
+        length * self.pixels_per_unit / distance.max(1e-3)
+    }
+
+    // the height in pixels of a sphere on screen, its diameter at the distance of its center
+    pub fn screen_size(&self, sphere: &BoundingSphere) -> f32 {
+        self.pixels(sphere.radius * 2.0, sphere.center.distance(self.eye))
+    }
+
+    // the level to draw lods with, placed by transform
+    pub fn select(&self, lods: &LodChain, transform: &Mat4) -> usize {
+        let sphere = lods.bounding_sphere().transform(transform);
+        if sphere.is_empty() {
+            return 0;
+        }
+        let distance = sphere.center.distance(self.eye) - sphere.radius;
+        let scale = max_scale(transform);
+        lods.levels
+            .iter()
+            .rposition(|level| self.pixels(level.error * scale, distance) <= self.pixel_error)
+            .unwrap_or(0)
+    }
+}
+
+// Instances sorted into a bucket per level of detail, each bucket drawn with one instanced draw
+// of its level. T is the per instance data, as for InstanceBuffer.
+#[derive(Debug)]
+pub struct LodInstances<T: VertexLayout> {
+    buckets: Vec<Vec<T>>,
+    buffers: Vec<InstanceBuffer<T>>,
+}
+
+impl<T: VertexLayout> LodInstances<T> {
+    pub fn new(lods: &LodChain, first_location: GLuint, usage: BufferUsage) -> LodInstances<T> {
+        LodInstances {
+            buckets: (0..lods.len()).map(|_| vec![]).collect(),
+            buffers: (0..lods.len()).map(|_| InstanceBuffer::new(&[], first_location, usage)).collect(),
+        }
+    }
+
+    // Sorts instances into the buckets of the levels selector picks for them and uploads the buckets.
+    // transform gives the model matrix an instance places the model with.
+    pub fn assign(&mut self, selector: &LodSelector, lods: &LodChain, instances: &[T], transform: impl Fn(&T) -> Mat4) {
+        assert_eq!(
+            lods.len(),
+            self.buckets.len(),
+            "LodInstances::assign with a chain of a different length than new was given"
+        );
+        for bucket in &mut self.buckets {
+            bucket.clear();
+        }
+        for instance in instances {
+            let level = selector.select(lods, &transform(instance));
+            self.buckets[level].push(*instance);
+        }
+        for (bucket, buffer) in self.buckets.iter().zip(&mut self.buffers) {
+            buffer.update(bucket);
+        }
+    }
+
+    // the instances of each level after the last assign
+    pub fn counts(&self) -> Vec<usize> {
+        self.buckets.iter().map(|bucket| bucket.len()).collect()
+    }
+
+    pub fn draw(&self, lods: &LodChain, shader: &Shader) {
+        assert_eq!(
+            lods.len(),
+            self.buffers.len(),
+            "LodInstances::draw with a chain of a different length than new was given"
+        );
+        for (level, buffer) in self.buffers.iter().enumerate() {
+            lods.level(level).draw_instanced(shader, buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bounds::BoundingSphere;
+    use crate::primitives::uv_sphere_geometry;
+    use crate::simplify::simplify;
+
+    // a chain of levels with the given errors around a unit sphere, no meshes are needed to select from it
+    fn chain(errors: &[f32]) -> LodChain {
+        let levels = errors
+            .iter()
+            .map(|&error| {
+                let mut model = Model::from_meshes(vec![], vec![]);
+                model.bounding_sphere = BoundingSphere::new(Vec3::ZERO, 1.0);
+                LodLevel { model, error }
+            })
+            .collect();
+        LodChain::from_levels(levels)
+    }
+
+    fn at_distance(distance: f32) -> Mat4 {
+        Mat4::from_translation(vec3(0.0, 0.0, -distance))
+    }
+
+    #[test]
+    fn near_is_fine_and_far_is_coarse() {
+        let lods = chain(&[0.0, 0.01, 0.05, 0.2]);
+        // the camera looks down -z from the origin, at 45 degrees over 800 pixels
+        let selector = LodSelector::new(&Camera::camera_vec3(Vec3::ZERO), 800.0, 1.0);
+        assert_eq!(selector.select(&lods, &at_distance(2.0)), 0);
+        assert_eq!(selector.select(&lods, &at_distance(1000.0)), 3);
+        // the camera inside the sphere always gets the full model
+        assert_eq!(selector.select(&lods, &at_distance(0.5)), 0);
+        // scaling the model up scales its error, so it needs to be further away for the same level
+        assert_eq!(selector.select(&lods, &at_distance(20.0)), 1);
+        assert_eq!(selector.select(&lods, &(at_distance(20.0) * Mat4::from_scale(Vec3::splat(4.0)))), 0);
+    }
+
+    #[test]
+    fn pixel_error_moves_the_switch() {
+        let lods = chain(&[0.0, 0.01, 0.05, 0.2]);
+        let camera = Camera::camera_vec3(Vec3::ZERO);
+        let levels: Vec<usize> = [0.25, 1.0, 4.0, 20.0]
+            .iter()
+            .map(|&pixel_error| LodSelector::new(&camera, 800.0, pixel_error).select(&lods, &at_distance(20.0)))
+            .collect();
+        assert_eq!(levels, vec![0, 1, 2, 3]);
+
+        // level 1 takes over where its error covers a pixel, and twice as far away for half a pixel
+        let selector = LodSelector::new(&camera, 800.0, 1.0);
+        let switch = 0.01 * selector.pixels_per_unit + 1.0;
+        assert_eq!(selector.select(&lods, &at_distance(switch * 0.99)), 0);
+        assert_eq!(selector.select(&lods, &at_distance(switch * 1.01)), 1);
+        let selector = LodSelector::new(&camera, 800.0, 0.5);
+        let switch = 0.02 * selector.pixels_per_unit + 1.0;
+        assert_eq!(selector.select(&lods, &at_distance(switch * 0.99)), 0);
+        assert_eq!(selector.select(&lods, &at_distance(switch * 1.01)), 1);
+    }
+
+    #[test]
+    fn errors_never_decrease() {
+        // a coarser level whose mesh came out closer still counts as far as the level before it,
+        // and errors are scaled by the mesh transforms
+        let transforms = [Mat4::IDENTITY, Mat4::from_scale(vec3(1.0, 2.0, 1.0))];
+        let errors = level_errors(&[vec![0.1, 0.05], vec![0.08, 0.2], vec![0.3, 0.1]], &transforms);
+        assert_eq!(errors, vec![0.1, 0.4, 0.4]);
+
+        let sphere = uv_sphere_geometry(32, 16);
+        let mesh_errors: Vec<Vec<f32>> = [0.5, 0.25, 0.1, 0.05]
+            .iter()
+            .map(|&ratio| vec![simplify(&sphere.vertices, &sphere.indices, ratio, f32::MAX).1])
+            .collect();
+        let errors = level_errors(&mesh_errors, &[Mat4::IDENTITY]);
+        assert!(errors.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", errors);
+        assert!(errors[3] > 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least the full model")]
+    fn empty_chains_are_rejected() {
+        LodChain::from_levels(vec![]);
+    }
+}
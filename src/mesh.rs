@@ -4,16 +4,28 @@
 #![allow(unused_assignments)]
 #![allow(unused_variables)]
 
+use crate::bounds::{Aabb, BoundingSphere};
+use crate::instance_buffer::InstanceBuffer;
+use crate::mesh_optimizer;
+use crate::mesh_optimizer::OptimizeReport;
+use crate::shader::Shader;
+use crate::stream_buffer::BufferUsage;
+use crate::vertex_attributes::{VertexAttribute, VertexLayout};
 use crate::ShaderId;
 use glad_gl::gl;
-use glad_gl::gl::{GLsizei, GLsizeiptr, GLuint, GLvoid};
+use glad_gl::gl::{GLenum, GLint, GLintptr, GLsizei, GLsizeiptr, GLuint, GLvoid};
 use glam::*;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::ffi::CString;
 use std::mem;
 use std::ops::Add;
+use std::rc::{Rc, Weak};
 
 const MAX_BONE_INFLUENCE: usize = 4;
 
+// The full vertex the model loader fills in. Meshes that only need some of it, like a textured quad,
+// can use a smaller vertex declared with vertex_layout!.
 #[derive(Debug, Copy, Clone)]
 #[repr(C, packed)]
 pub struct Vertex {
@@ -52,7 +64,94 @@ const OFFSET_OF_BITANGENT: usize = mem::offset_of!(Vertex, Bitangent);
 const OFFSET_OF_BONE_IDS: usize = mem::offset_of!(Vertex, m_BoneIDs);
 const OFFSET_OF_WEIGHTS: usize = mem::offset_of!(Vertex, m_Weights);
 
-#[derive(Debug, Clone)]
+// The attribute locations of the Vertex fields, which the model shaders declare as layout (location = N) in.
+// Vertex is packed, so these are written out instead of using vertex_layout!.
+pub static VERTEX_ATTRIBUTES: [VertexAttribute; 7] = [
+    // vertex Positions
+    VertexAttribute {
+        name: "Position",
+        location: 0,
+        components: 3,
+        columns: 1,
+        data_type: gl::FLOAT,
+        integer: false,
+        offset: 0,
+    },
+    // vertex normals
+    VertexAttribute {
+        name: "Normal",
+        location: 1,
+        components: 3,
+        columns: 1,
+        data_type: gl::FLOAT,
+        integer: false,
+        offset: OFFSET_OF_NORMAL,
+    },
+    // vertex texture coordinates
+    VertexAttribute {
+        name: "TexCoords",
+        location: 2,
+        components: 2,
+        columns: 1,
+        data_type: gl::FLOAT,
+        integer: false,
+        offset: OFFSET_OF_TEXCOORDS,
+    },
+    // vertex tangent
+    VertexAttribute {
+        name: "Tangent",
+        location: 3,
+        components: 3,
+        columns: 1,
+        data_type: gl::FLOAT,
+        integer: false,
+        offset: OFFSET_OF_TANGENT,
+    },
+    // vertex bitangent
+    VertexAttribute {
+        name: "Bitangent",
+        location: 4,
+        components: 3,
+        columns: 1,
+        data_type: gl::FLOAT,
+        integer: false,
+        offset: OFFSET_OF_BITANGENT,
+    },
+    // ids
+    VertexAttribute {
+        name: "m_BoneIDs",
+        location: 5,
+        components: 4,
+        columns: 1,
+        data_type: gl::INT,
+        integer: true,
+        offset: OFFSET_OF_BONE_IDS,
+    },
+    // weights
+    VertexAttribute {
+        name: "m_Weights",
+        location: 6,
+        components: 4,
+        columns: 1,
+        data_type: gl::FLOAT,
+        integer: false,
+        offset: OFFSET_OF_WEIGHTS,
+    },
+];
+
+impl VertexLayout for Vertex {
+    fn attributes() -> &'static [VertexAttribute] {
+        &VERTEX_ATTRIBUTES
+    }
+
+    fn position(&self) -> Vec3 {
+        self.Position
+    }
+}
+
+// A texture object owned by this value and deleted when it is dropped. Meshes share their textures
+// through Rc<Texture>, so a texture used by several meshes of a model is deleted with the last of them.
+#[derive(Debug)]
 pub struct Texture {
     pub id: u32,
     pub texture_type: String,
@@ -69,26 +168,91 @@ impl Texture {
     }
 }
 
-#[derive(Debug, Clone)]
-pub struct Mesh {
-    pub vertices: Vec<Vertex>,
-    pub indices: Vec<u32>,
-    pub textures: Vec<Texture>,
-    pub VAO: u32,
+impl Drop for Texture {
+    fn drop(&mut self) {
+        if self.id != 0 {
+            unsafe {
+                gl::DeleteTextures(1, &self.id);
+            }
+        }
+    }
 }
 
-impl Mesh {
-    pub fn new(vertices: Vec<Vertex>, indices: Vec<u32>, textures: Vec<Texture>) -> Mesh {
-        let mut mesh = Mesh {
-            vertices,
-            indices,
-            textures,
-            VAO: 99999,
-        };
-        mesh.setupMesh();
-        mesh
+// How the vertices of a mesh are put together into points, lines or triangles.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PrimitiveMode {
+    Points,
+    Lines,
+    LineStrip,
+    LineLoop,
+    Triangles,
+    TriangleStrip,
+    TriangleFan,
+    // patches of n control points for the tessellation stages
+    Patches(u32),
+}
+
+impl PrimitiveMode {
+    pub fn gl_mode(&self) -> GLenum {
+        match self {
+            PrimitiveMode::Points => gl::POINTS,
+            PrimitiveMode::Lines => gl::LINES,
+            PrimitiveMode::LineStrip => gl::LINE_STRIP,
+            PrimitiveMode::LineLoop => gl::LINE_LOOP,
+            PrimitiveMode::Triangles => gl::TRIANGLES,
+            PrimitiveMode::TriangleStrip => gl::TRIANGLE_STRIP,
+            PrimitiveMode::TriangleFan => gl::TRIANGLE_FAN,
+            PrimitiveMode::Patches(_) => gl::PATCHES,
+        }
     }
+}
+
+// The edges of a triangle list as a line list, each edge once, for drawing a wireframe over a mesh
+// with the same vertices.
+//
+//    let wireframe = Mesh::new(mesh.vertices.clone(), edge_indices(&mesh.indices), vec![]).with_mode(PrimitiveMode::Lines);
+//
+pub fn edge_indices(triangles: &[u32]) -> Vec<u32> {
+    let mut seen: HashSet<(u32, u32)> = HashSet::new();
+    let mut edges: Vec<u32> = vec![];
+    for triangle in triangles.chunks_exact(3) {
+        for (a, b) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+            if seen.insert((a.min(b), a.max(b))) {
+                edges.extend([a, b]);
+            }
+        }
+    }
+    edges
+}
 
+// A mesh of vertices in any VertexLayout, Vertex unless given otherwise.
+// The mesh owns its vertex array and buffers and deletes them when dropped, so it can't be cloned.
+//
+// Meshes draw triangles unless given another mode with with_mode. A mesh without indices draws its
+// vertices in order with glDrawArrays. Indices are uploaded as u16 when they all fit, halving the
+// size of the index buffer for most meshes.
+#[derive(Debug)]
+pub struct Mesh<V: VertexLayout = Vertex> {
+    pub vertices: Vec<V>,
+    pub indices: Vec<u32>,
+    pub mode: PrimitiveMode,
+    // gl::UNSIGNED_SHORT or gl::UNSIGNED_INT, the type the indices were uploaded as
+    pub index_type: GLenum,
+    pub textures: Vec<Rc<Texture>>,
+    pub VAO: u32,
+    pub VBO: u32,
+    pub EBO: u32,
+    // bounds of the vertex positions, computed in new
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    // the usage hint of the vertex and index buffers
+    pub usage: BufferUsage,
+    // a vertex array per instance buffer drawn with, with the mesh's attributes and the buffer's,
+    // so the mesh's own vertex array is never changed. Dropped buffers are cleared out on the next draw.
+    instance_vaos: RefCell<Vec<(Weak<()>, GLuint)>>,
+}
+
+impl Mesh<Vertex> {
     pub fn debug(&self) {
         println!("mesh: {:#?}", self);
 
@@ -113,18 +277,60 @@ impl Mesh {
                 + mem::size_of::<[f32; MAX_BONE_INFLUENCE]>()
         );
     }
+}
 
-    pub fn Draw(&self, shader_id: ShaderId) {
-        // bind appropriate textures
+impl<V: VertexLayout> Mesh<V> {
+    pub fn new(vertices: Vec<V>, indices: Vec<u32>, textures: Vec<Rc<Texture>>) -> Mesh<V> {
+        Mesh::with_usage(vertices, indices, textures, BufferUsage::Static)
+    }
+
+    // A mesh whose vertices are meant to be changed with update_vertices or set_vertices.
+    pub fn with_usage(vertices: Vec<V>, indices: Vec<u32>, textures: Vec<Rc<Texture>>, usage: BufferUsage) -> Mesh<V> {
+        let mut mesh = Mesh {
+            vertices,
+            indices,
+            mode: PrimitiveMode::Triangles,
+            index_type: gl::UNSIGNED_INT,
+            textures,
+            VAO: 0,
+            VBO: 0,
+            EBO: 0,
+            aabb: Aabb::EMPTY,
+            bounding_sphere: BoundingSphere::EMPTY,
+            usage,
+            instance_vaos: RefCell::new(vec![]),
+        };
+        mesh.compute_bounds();
+        mesh.setupMesh();
+        mesh
+    }
+
+    pub fn with_mode(mut self, mode: PrimitiveMode) -> Mesh<V> {
+        self.mode = mode;
+        self
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        !self.indices.is_empty()
+    }
+
+    // Updates aabb and bounding_sphere after changing the vertices.
+    pub fn compute_bounds(&mut self) {
+        let positions = self.vertices.iter().map(|vertex| vertex.position());
+        self.aabb = Aabb::from_points(positions.clone());
+        self.bounding_sphere = BoundingSphere::from_points(positions);
+    }
+
+    // the sampler uniform name for each texture, texture_diffuse1, texture_diffuse2, texture_specular1, ...
+    pub fn sampler_names(&self) -> Vec<String> {
         let mut diffuseNr: u32 = 0;
         let mut specularNr: u32 = 0;
         let mut normalNr: u32 = 0;
         let mut heightNr: u32 = 0;
 
-        unsafe {
-            for (i, texture) in self.textures.iter().enumerate() {
-                gl::ActiveTexture(gl::TEXTURE0 + i as u32); // active proper texture unit before binding
-
+        self.textures
+            .iter()
+            .map(|texture| {
                 // retrieve texture number (the N in diffuse_textureN)
                 let num = match texture.texture_type.as_str() {
                     "texture_diffuse" => {
@@ -145,124 +351,274 @@ impl Mesh {
                     }
                     _ => panic!("Unknown texture type"),
                 };
+                texture.texture_type.clone().add(&num.to_string())
+            })
+            .collect()
+    }
 
-                // now set the sampler to the correct texture unit
-                let name = texture.texture_type.clone().add(&num.to_string());
+    // Binds the textures to units counted from 0 for every mesh, which clash with other textures the
+    // shader samples, like a shadow map bound to unit 0 or 1. draw takes the units from the shader instead.
+    #[deprecated(note = "use draw(&Shader), which takes texture units from the shader")]
+    pub fn Draw(&self, shader_id: ShaderId) {
+        unsafe {
+            // bind appropriate textures
+            for (i, (texture, name)) in self.textures.iter().zip(self.sampler_names()).enumerate() {
+                gl::ActiveTexture(gl::TEXTURE0 + i as u32); // active proper texture unit before binding
 
+                // now set the sampler to the correct texture unit
                 let c_string = CString::new(name).unwrap();
                 gl::Uniform1i(gl::GetUniformLocation(shader_id, c_string.as_ptr()), i as i32);
                 // and finally bind the texture
                 gl::BindTexture(gl::TEXTURE_2D, texture.id);
             }
 
+            self.draw_elements(self.VAO, None);
+        }
+    }
+
+    // Draws the mesh with texture units allocated by the shader, so they don't clash with
+    // other textures the shader has bound this frame, like a shadow map.
+    // Textures without a matching sampler in the shader are skipped, and the first draw with a shader
+    // warns about vertex inputs that don't match the vertex layout.
+    pub fn draw(&self, shader: &Shader) {
+        shader.warn_vertex_layout(V::attributes());
+        self.set_textures(shader);
+        unsafe {
+            self.draw_elements(self.VAO, None);
+        }
+    }
+
+    // Draws one copy of the mesh per instance in instances, like draw. The first draw with an instance
+    // buffer makes a vertex array for the pair, with the instance attributes in place of any vertex
+    // attributes at the same locations. Later draws with the same buffer reuse it, and draw and
+    // draw_ranges keep using the mesh's own vertex array.
+    pub fn draw_instanced<T: VertexLayout>(&self, shader: &Shader, instances: &InstanceBuffer<T>) {
+        if instances.is_empty() {
+            return;
+        }
+        shader.warn_instanced_layout(V::attributes(), instances.attributes());
+        self.set_textures(shader);
+        let vao = self.instance_vao(instances);
+        unsafe {
+            self.draw_elements(vao, Some(instances.len()));
+        }
+    }
+
+    fn instance_vao<T: VertexLayout>(&self, instances: &InstanceBuffer<T>) -> GLuint {
+        let mut vaos = self.instance_vaos.borrow_mut();
+        vaos.retain(|(token, vao)| {
+            let alive = token.strong_count() > 0;
+            if !alive {
+                unsafe {
+                    gl::DeleteVertexArrays(1, vao);
+                }
+            }
+            alive
+        });
+        if let Some((_, vao)) = vaos.iter().find(|(token, _)| instances.is_token(token)) {
+            return *vao;
+        }
+
+        let mut vao: GLuint = 0;
+        unsafe {
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.VBO);
+            for attribute in V::attributes() {
+                attribute.enable(V::stride());
+            }
+            if self.EBO != 0 {
+                gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.EBO);
+            }
+            instances.bind_attributes();
+            gl::BindVertexArray(0);
+        }
+        vaos.push((instances.token(), vao));
+        vao
+    }
+
+    fn delete_instance_vaos(&self) {
+        for (_, vao) in self.instance_vaos.borrow_mut().drain(..) {
+            unsafe {
+                gl::DeleteVertexArrays(1, &vao);
+            }
+        }
+    }
+
+    // Draws parts of the mesh with one glMultiDrawElements, each part a first index and an index count,
+    // or a first vertex and a vertex count for meshes without indices. Like draw otherwise.
+    pub fn draw_ranges(&self, shader: &Shader, ranges: &[(usize, usize)]) {
+        if ranges.is_empty() {
+            return;
+        }
+        shader.warn_vertex_layout(V::attributes());
+        self.set_textures(shader);
+        let counts: Vec<GLsizei> = ranges.iter().map(|&(_, count)| count as GLsizei).collect();
+        unsafe {
             gl::BindVertexArray(self.VAO);
-            gl::DrawElements(gl::TRIANGLES, self.indices.len() as i32, gl::UNSIGNED_INT, 0 as *const GLvoid);
+            if let PrimitiveMode::Patches(vertices) = self.mode {
+                gl::PatchParameteri(gl::PATCH_VERTICES, vertices as GLint);
+            }
+            if self.is_indexed() {
+                let index_size = if self.index_type == gl::UNSIGNED_SHORT { 2 } else { 4 };
+                let offsets: Vec<*const GLvoid> = ranges.iter().map(|&(first, _)| (first * index_size) as *const GLvoid).collect();
+                gl::MultiDrawElements(self.mode.gl_mode(), counts.as_ptr(), self.index_type, offsets.as_ptr(), ranges.len() as GLsizei);
+            } else {
+                let firsts: Vec<GLint> = ranges.iter().map(|&(first, _)| first as GLint).collect();
+                gl::MultiDrawArrays(self.mode.gl_mode(), firsts.as_ptr(), counts.as_ptr(), ranges.len() as GLsizei);
+            }
             gl::BindVertexArray(0);
         }
     }
 
-    fn setupMesh(&mut self) {
-        let mut VBO: GLuint = 0;
-        let mut EBO: GLuint = 0;
+    fn set_textures(&self, shader: &Shader) {
+        for (texture, name) in self.textures.iter().zip(self.sampler_names()) {
+            if shader.has_uniform(&name) {
+                shader.set_texture(&name, texture);
+            }
+        }
+    }
+
+    // Overwrites vertices starting at offset, in the mesh and on the GPU, without reallocating the buffer.
+    // The bounds are not updated, call compute_bounds if the vertices moved far.
+    // Panics if vertices runs past the end of the mesh.
+    pub fn update_vertices(&mut self, offset: usize, vertices: &[V]) {
+        assert!(offset + vertices.len() <= self.vertices.len(), "Mesh update_vertices out of range");
+        self.vertices[offset..offset + vertices.len()].copy_from_slice(vertices);
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.VBO);
+            gl::BufferSubData(
+                gl::ARRAY_BUFFER,
+                (offset * V::stride()) as GLintptr,
+                (vertices.len() * V::stride()) as GLsizeiptr,
+                vertices.as_ptr() as *const GLvoid,
+            );
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    // Replaces all vertices, which may change their number. The old buffer storage is orphaned
+    // first, so the driver can hand out fresh memory instead of waiting for draws still using it.
+    pub fn set_vertices(&mut self, vertices: Vec<V>) {
+        self.vertices = vertices;
+        self.compute_bounds();
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.VBO);
+            orphan_and_upload(gl::ARRAY_BUFFER, &self.vertices, V::stride(), self.usage);
+            gl::BindBuffer(gl::ARRAY_BUFFER, 0);
+        }
+    }
+
+    // Welds duplicate vertices and reorders triangles and vertices for the vertex cache, see
+    // mesh_optimizer::optimize, and uploads the result. None for meshes that aren't triangle lists.
+    pub fn optimize(&mut self) -> Option<OptimizeReport> {
+        if self.mode != PrimitiveMode::Triangles {
+            return None;
+        }
+        let (vertices, indices, report) = mesh_optimizer::optimize(&self.vertices, &self.indices);
+        self.set_vertices(vertices);
+        self.set_indices(indices);
+        Some(report)
+    }
 
+    // Replaces all indices, orphaning the old index buffer like set_vertices.
+    pub fn set_indices(&mut self, indices: Vec<u32>) {
+        self.indices = indices;
+        unsafe {
+            // the element array binding is part of the vertex array
+            gl::BindVertexArray(self.VAO);
+            self.upload_indices();
+            gl::BindVertexArray(0);
+        }
+    }
+
+    // Uploads the indices to the element array buffer bound to the vertex array, as u16 when they fit.
+    unsafe fn upload_indices(&mut self) {
+        if self.EBO == 0 {
+            gl::GenBuffers(1, &mut self.EBO);
+            // made without an index buffer, make them again with it
+            self.delete_instance_vaos();
+        }
+        gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.EBO);
+        if self.indices.iter().all(|&index| index <= u16::MAX as u32) {
+            let indices: Vec<u16> = self.indices.iter().map(|&index| index as u16).collect();
+            orphan_and_upload(gl::ELEMENT_ARRAY_BUFFER, &indices, mem::size_of::<u16>(), self.usage);
+            self.index_type = gl::UNSIGNED_SHORT;
+        } else {
+            orphan_and_upload(gl::ELEMENT_ARRAY_BUFFER, &self.indices, mem::size_of::<u32>(), self.usage);
+            self.index_type = gl::UNSIGNED_INT;
+        }
+    }
+
+    // draws the whole mesh once, or instances times with the instanced draw calls
+    unsafe fn draw_elements(&self, vao: GLuint, instances: Option<usize>) {
+        gl::BindVertexArray(vao);
+        if let PrimitiveMode::Patches(vertices) = self.mode {
+            gl::PatchParameteri(gl::PATCH_VERTICES, vertices as GLint);
+        }
+        let mode = self.mode.gl_mode();
+        match (self.is_indexed(), instances) {
+            (true, None) => gl::DrawElements(mode, self.indices.len() as GLsizei, self.index_type, std::ptr::null()),
+            (true, Some(instances)) => gl::DrawElementsInstanced(
+                mode,
+                self.indices.len() as GLsizei,
+                self.index_type,
+                std::ptr::null(),
+                instances as GLsizei,
+            ),
+            (false, None) => gl::DrawArrays(mode, 0, self.vertices.len() as GLsizei),
+            (false, Some(instances)) => gl::DrawArraysInstanced(mode, 0, self.vertices.len() as GLsizei, instances as GLsizei),
+        }
+        gl::BindVertexArray(0);
+    }
+
+    fn setupMesh(&mut self) {
         unsafe {
             gl::GenVertexArrays(1, &mut self.VAO);
-            gl::GenBuffers(1, &mut VBO);
-            gl::GenBuffers(1, &mut EBO);
+            gl::GenBuffers(1, &mut self.VBO);
 
             gl::BindVertexArray(self.VAO);
             // load data into vertex buffers
-            gl::BindBuffer(gl::ARRAY_BUFFER, VBO);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.VBO);
             // A great thing about structs is that their memory layout is sequential for all its items. (original comment from cpp code)
             // The effect is that we can simply pass a pointer to the struct and it translates perfectly to a glm::vec3/2 array which
             // again translates to 3/2 floats which translates to a byte array.
             gl::BufferData(
                 gl::ARRAY_BUFFER,
-                (self.vertices.len() * mem::size_of::<Vertex>()) as GLsizeiptr,
+                (self.vertices.len() * V::stride()) as GLsizeiptr,
                 self.vertices.as_ptr() as *const GLvoid,
-                gl::STATIC_DRAW,
+                self.usage.gl_usage(),
             );
 
-            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, EBO);
-            gl::BufferData(
-                gl::ELEMENT_ARRAY_BUFFER,
-                (self.indices.len() * mem::size_of::<u32>()) as GLsizeiptr,
-                self.indices.as_ptr() as *const GLvoid,
-                gl::STATIC_DRAW,
-            );
+            if self.is_indexed() {
+                self.upload_indices();
+            }
 
             // set the vertex attribute pointers
-            // vertex Positions
-            gl::EnableVertexAttribArray(0);
-            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, mem::size_of::<Vertex>() as GLsizei, 0 as *const GLvoid);
-
-            // vertex normals
-            gl::EnableVertexAttribArray(1);
-            gl::VertexAttribPointer(
-                1,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                mem::size_of::<Vertex>() as GLsizei,
-                (OFFSET_OF_NORMAL) as *const GLvoid,
-            );
-
-            // vertex texture coordinates
-            gl::EnableVertexAttribArray(2);
-            gl::VertexAttribPointer(
-                2,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                mem::size_of::<Vertex>() as GLsizei,
-                (OFFSET_OF_TEXCOORDS) as *const GLvoid,
-            );
-
-            // vertex tangent
-            gl::EnableVertexAttribArray(3);
-            gl::VertexAttribPointer(
-                3,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                mem::size_of::<Vertex>() as GLsizei,
-                (OFFSET_OF_TANGENT) as *const GLvoid,
-            );
-
-            // vertex bitangent
-            gl::EnableVertexAttribArray(4);
-            gl::VertexAttribPointer(
-                4,
-                3,
-                gl::FLOAT,
-                gl::FALSE,
-                mem::size_of::<Vertex>() as GLsizei,
-                (OFFSET_OF_BITANGENT) as *const GLvoid,
-            );
+            for attribute in V::attributes() {
+                attribute.enable(V::stride());
+            }
 
-            // ids
-            gl::EnableVertexAttribArray(5);
-            gl::VertexAttribPointer(
-                5,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                mem::size_of::<Vertex>() as GLsizei,
-                (OFFSET_OF_BONE_IDS) as *const GLvoid,
-            );
+            gl::BindVertexArray(0);
+        }
+    }
+}
 
-            // weights
-            gl::EnableVertexAttribArray(6);
-            gl::VertexAttribPointer(
-                6,
-                4,
-                gl::FLOAT,
-                gl::FALSE,
-                mem::size_of::<Vertex>() as GLsizei,
-                (OFFSET_OF_WEIGHTS) as *const GLvoid,
-            );
+// Uploads data to the buffer bound to target, orphaning its old storage with a null glBufferData first.
+unsafe fn orphan_and_upload<T>(target: GLenum, data: &[T], stride: usize, usage: BufferUsage) {
+    let size = (data.len() * stride) as GLsizeiptr;
+    gl::BufferData(target, size, std::ptr::null(), usage.gl_usage());
+    gl::BufferSubData(target, 0, size, data.as_ptr() as *const GLvoid);
+}
 
-            gl::BindVertexArray(0);
+impl<V: VertexLayout> Drop for Mesh<V> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteVertexArrays(1, &self.VAO);
+            self.delete_instance_vaos();
+            gl::DeleteBuffers(1, &self.VBO);
+            // 0 for meshes without indices, which glDeleteBuffers ignores
+            gl::DeleteBuffers(1, &self.EBO);
         }
     }
 }
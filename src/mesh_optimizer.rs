@@ -0,0 +1,403 @@
+#![allow(dead_code)]
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::mem;
+
+use crate::vertex_attributes::VertexLayout;
+
+// Mesh optimization on the CPU, for triangle lists in any VertexLayout. Nothing here touches the GPU,
+// so it runs on vertices and indices before they become a Mesh, or in tools.
+//
+//   - weld_vertices merges vertices that are exactly the same, which assimp leaves in without JoinIdenticalVertices
+//   - optimize_vertex_cache reorders triangles so vertices are reused while still in the post transform cache
+//   - optimize_vertex_fetch reorders vertices into the order the triangles first use them
+//
+// optimize does all three and reports the change in vertex count and ACMR, the average number of
+// vertices the vertex shader runs for per triangle: 3 with no reuse, approaching 0.5 for a large regular grid.
+//
+//    let (vertices, indices, report) = mesh_optimizer::optimize(&vertices, &indices);
+//    println!("{}", report);
+
+// the size of the FIFO cache acmr simulates, a typical size for desktop GPUs
+pub const ACMR_CACHE_SIZE: usize = 16;
+
+// the LRU cache size optimize_vertex_cache optimizes for
+const CACHE_SIZE: usize = 32;
+const LAST_TRIANGLE_SCORE: f32 = 0.75;
+const CACHE_DECAY_POWER: f32 = 1.5;
+const VALENCE_BOOST_SCALE: f32 = 2.0;
+const VALENCE_BOOST_POWER: f32 = 0.5;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OptimizeReport {
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+    pub acmr_before: f32,
+    pub acmr_after: f32,
+}
+
+impl fmt::Display for OptimizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "vertices {} -> {}, ACMR {:.3} -> {:.3}",
+            self.vertices_before, self.vertices_after, self.acmr_before, self.acmr_after
+        )
+    }
+}
+
+// Welds duplicate vertices, then optimizes for the vertex cache and for vertex fetch.
+// Empty indices are taken as a non indexed triangle list, the result is always indexed.
+pub fn optimize<V: VertexLayout>(vertices: &[V], indices: &[u32]) -> (Vec<V>, Vec<u32>, OptimizeReport) {
+    let indices_before = list_indices(vertices, indices);
+    let acmr_before = acmr(&indices_before, ACMR_CACHE_SIZE);
+
+    let (welded, indices) = weld_vertices(vertices, &indices_before);
+    let mut indices = optimize_vertex_cache(&indices, welded.len());
+    let vertices_after = optimize_vertex_fetch(&welded, &mut indices);
+
+    let report = OptimizeReport {
+        vertices_before: vertices.len(),
+        vertices_after: vertices_after.len(),
+        acmr_before,
+        acmr_after: acmr(&indices, ACMR_CACHE_SIZE),
+    };
+    (vertices_after, indices, report)
+}
+
+// Merges vertices with the same attribute values, keeping the first of each in order, and points the
+// indices at the ones kept. Only the attributes of the layout are compared, bit for bit as they would
+// be uploaded, so -0.0 and 0.0 count as different and padding between fields is never read.
+pub fn weld_vertices<V: VertexLayout>(vertices: &[V], indices: &[u32]) -> (Vec<V>, Vec<u32>) {
+    let indices = list_indices(vertices, indices);
+    let mut welded: Vec<V> = Vec::with_capacity(vertices.len());
+    let mut remap: Vec<u32> = Vec::with_capacity(vertices.len());
+    let mut seen: HashMap<Vec<u32>, u32> = HashMap::with_capacity(vertices.len());
+
+    for vertex in vertices {
+        let index = *seen.entry(attribute_words(vertex)).or_insert_with(|| {
+            welded.push(*vertex);
+            welded.len() as u32 - 1
+        });
+        remap.push(index);
+    }
+    let indices = indices.iter().map(|&index| remap[index as usize]).collect();
+    (welded, indices)
+}
+
+// Reorders the triangles of a triangle list to make the most of the post transform vertex cache,
+// with Tom Forsyth's linear speed vertex cache optimization. The triangles keep their winding.
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return vec![];
+    }
+
+    // the triangles using each vertex, the first remaining[vertex] of them not yet emitted
+    let mut offsets: Vec<usize> = vec![0; vertex_count + 1];
+    for &index in &indices[..triangle_count * 3] {
+        offsets[index as usize + 1] += 1;
+    }
+    for vertex in 0..vertex_count {
+        offsets[vertex + 1] += offsets[vertex];
+    }
+    let mut remaining: Vec<usize> = (0..vertex_count).map(|vertex| offsets[vertex + 1] - offsets[vertex]).collect();
+    let mut adjacency: Vec<usize> = vec![0; triangle_count * 3];
+    let mut filled = offsets.clone();
+    for (corner, &index) in indices[..triangle_count * 3].iter().enumerate() {
+        adjacency[filled[index as usize]] = corner / 3;
+        filled[index as usize] += 1;
+    }
+
+    let mut cache_position: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = (0..vertex_count).map(|vertex| vertex_score(None, remaining[vertex])).collect();
+    let triangle_score = |triangle: usize, vertex_scores: &[f32]| -> f32 {
+        indices[triangle * 3..triangle * 3 + 3]
+            .iter()
+            .map(|&index| vertex_scores[index as usize])
+            .sum()
+    };
+    let mut triangle_scores: Vec<f32> = (0..triangle_count).map(|triangle| triangle_score(triangle, &vertex_scores)).collect();
+    let mut emitted: Vec<bool> = vec![false; triangle_count];
+
+    let mut optimized: Vec<u32> = Vec::with_capacity(triangle_count * 3);
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    // scratch for each step, kept to not allocate for every triangle
+    let mut updated: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut touched: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut candidates: Vec<usize> = vec![];
+    // where to look for a triangle when none in the cache is left
+    let mut cursor = 0;
+    let mut best = best_triangle(&triangle_scores, &emitted, 0..triangle_count);
+
+    while optimized.len() < triangle_count * 3 {
+        let triangle = match best {
+            Some(triangle) => triangle,
+            None => {
+                while emitted[cursor] {
+                    cursor += 1;
+                }
+                cursor
+            }
+        };
+        emitted[triangle] = true;
+        let corners = &indices[triangle * 3..triangle * 3 + 3];
+        optimized.extend_from_slice(corners);
+
+        for &index in corners {
+            let vertex = index as usize;
+            let triangles = &mut adjacency[offsets[vertex]..offsets[vertex] + remaining[vertex]];
+            if let Some(position) = triangles.iter().position(|&other| other == triangle) {
+                let last = triangles.len() - 1;
+                triangles.swap(position, last);
+                remaining[vertex] -= 1;
+            }
+        }
+
+        // move the triangle's vertices to the front of the cache, pushing the oldest out the back
+        updated.clear();
+        updated.extend_from_slice(corners);
+        updated.extend(cache.iter().copied().filter(|index| !corners.contains(index)));
+        // the ones just pushed out are rescored below along with the ones in the cache
+        touched.clear();
+        touched.extend(updated.drain(updated.len().min(CACHE_SIZE)..));
+        for &index in &touched {
+            cache_position[index as usize] = None;
+        }
+        mem::swap(&mut cache, &mut updated);
+        for (position, &index) in cache.iter().enumerate() {
+            cache_position[index as usize] = Some(position);
+        }
+
+        // rescore the vertices in the cache and the ones just pushed out, and their triangles
+        touched.extend_from_slice(&cache);
+        for &index in &touched {
+            let vertex = index as usize;
+            vertex_scores[vertex] = vertex_score(cache_position[vertex], remaining[vertex]);
+        }
+        candidates.clear();
+        for &index in &touched {
+            let vertex = index as usize;
+            for &other in &adjacency[offsets[vertex]..offsets[vertex] + remaining[vertex]] {
+                triangle_scores[other] = triangle_score(other, &vertex_scores);
+                candidates.push(other);
+            }
+        }
+        best = best_triangle(&triangle_scores, &emitted, candidates.iter().copied());
+    }
+    optimized
+}
+
+// Reorders vertices into the order the indices first use them and updates the indices to match, so
+// the vertex shader reads memory mostly front to back. Vertices no index uses are dropped.
+pub fn optimize_vertex_fetch<V: VertexLayout>(vertices: &[V], indices: &mut [u32]) -> Vec<V> {
+    let mut remap: Vec<Option<u32>> = vec![None; vertices.len()];
+    let mut reordered: Vec<V> = Vec::with_capacity(vertices.len());
+    for index in indices.iter_mut() {
+        *index = *remap[*index as usize].get_or_insert_with(|| {
+            reordered.push(vertices[*index as usize]);
+            reordered.len() as u32 - 1
+        });
+    }
+    reordered
+}
+
+// The average cache miss ratio of a triangle list: the vertices transformed per triangle with a FIFO
+// post transform cache of cache_size vertices. 3 is the worst, lower is better.
+pub fn acmr(indices: &[u32], cache_size: usize) -> f32 {
+    let triangle_count = indices.len() / 3;
+    if triangle_count == 0 {
+        return 0.0;
+    }
+    let mut cache: VecDeque<u32> = VecDeque::with_capacity(cache_size + 1);
+    let mut misses = 0;
+    for &index in &indices[..triangle_count * 3] {
+        if !cache.contains(&index) {
+            misses += 1;
+            cache.push_back(index);
+            if cache.len() > cache_size {
+                cache.pop_front();
+            }
+        }
+    }
+    misses as f32 / triangle_count as f32
+}
+
+// Forsyth's vertex score: high for vertices used by the last triangles and for vertices with few
+// triangles left, so they get finished off instead of being left behind.
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+    let cache_score = match cache_position {
+        None => 0.0,
+        Some(position) if position < 3 => LAST_TRIANGLE_SCORE,
+        Some(position) => (1.0 - (position - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(CACHE_DECAY_POWER),
+    };
+    cache_score + VALENCE_BOOST_SCALE * (remaining as f32).powf(-VALENCE_BOOST_POWER)
+}
+
+fn best_triangle(scores: &[f32], emitted: &[bool], candidates: impl Iterator<Item = usize>) -> Option<usize> {
+    candidates
+        .filter(|&triangle| !emitted[triangle])
+        .max_by(|&a, &b| scores[a].total_cmp(&scores[b]))
+}
+
+// the indices of a list, or 0..n for a non indexed one
+fn list_indices<V>(vertices: &[V], indices: &[u32]) -> Vec<u32> {
+    if indices.is_empty() {
+        (0..vertices.len() as u32).collect()
+    } else {
+        indices.to_vec()
+    }
+}
+
+// The attribute values of a vertex as 32 bit words. Every data type used in layouts is 4 bytes,
+// and a field may be unaligned in a packed vertex like Vertex.
+fn attribute_words<V: VertexLayout>(vertex: &V) -> Vec<u32> {
+    let start = vertex as *const V as *const u8;
+    let mut words: Vec<u32> = vec![];
+    for attribute in V::attributes() {
+        let count = (attribute.components * attribute.columns.max(1)) as usize;
+        assert!(
+            attribute.offset + count * 4 <= mem::size_of::<V>(),
+            "attribute {} outside the vertex",
+            attribute.name
+        );
+        for word in 0..count {
+            words.push(unsafe { (start.add(attribute.offset + word * 4) as *const u32).read_unaligned() });
+        }
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::Vertex;
+    use glam::*;
+
+    fn vertex(x: f32, y: f32) -> Vertex {
+        let mut vertex = Vertex::new();
+        vertex.Position = vec3(x, y, 0.0);
+        vertex.Normal = Vec3::Z;
+        vertex.TexCoords = vec2(x, y);
+        vertex
+    }
+
+    // a grid of size by size quads, triangles in rows like the primitives lay them out
+    fn grid(size: u32) -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices = vec![];
+        for y in 0..=size {
+            for x in 0..=size {
+                vertices.push(vertex(x as f32, y as f32));
+            }
+        }
+        let mut indices = vec![];
+        for y in 0..size {
+            for x in 0..size {
+                let a = y * (size + 1) + x;
+                let b = a + size + 1;
+                indices.extend_from_slice(&[a, a + 1, b, b, a + 1, b + 1]);
+            }
+        }
+        (vertices, indices)
+    }
+
+    // the triangles as positions, sorted, to compare meshes with different vertices and triangle order
+    fn triangles(vertices: &[Vertex], indices: &[u32]) -> Vec<[u32; 9]> {
+        let mut triangles: Vec<[u32; 9]> = indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let mut key = [0; 9];
+                for (corner, &index) in triangle.iter().enumerate() {
+                    let position = vertices[index as usize].Position;
+                    key[corner * 3..corner * 3 + 3].copy_from_slice(&position.to_array().map(f32::to_bits));
+                }
+                // start from the smallest corner so the same winding compares equal
+                let first = (0..3).min_by_key(|&corner| key[corner * 3..corner * 3 + 3].to_vec()).unwrap();
+                key.rotate_left(first * 3);
+                key
+            })
+            .collect();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn acmr_counts_cache_misses() {
+        assert_eq!(acmr(&[], ACMR_CACHE_SIZE), 0.0);
+        assert_eq!(acmr(&[0, 1, 2], ACMR_CACHE_SIZE), 3.0);
+        // two triangles sharing an edge transform 4 vertices
+        assert_eq!(acmr(&[0, 1, 2, 2, 1, 3], ACMR_CACHE_SIZE), 2.0);
+        // with a cache of 3 the first triangle is pushed out by the time it is drawn again
+        assert_eq!(acmr(&[0, 1, 2, 3, 4, 5, 0, 1, 2], 3), 3.0);
+        assert_eq!(acmr(&[0, 1, 2, 3, 4, 5, 0, 1, 2], 6), 2.0);
+    }
+
+    #[test]
+    fn weld_merges_duplicates() {
+        // a quad as a non indexed list, the two shared corners repeated
+        let vertices = vec![
+            vertex(0.0, 0.0),
+            vertex(1.0, 0.0),
+            vertex(0.0, 1.0),
+            vertex(0.0, 1.0),
+            vertex(1.0, 0.0),
+            vertex(1.0, 1.0),
+        ];
+        let (welded, indices) = weld_vertices(&vertices, &[]);
+        assert_eq!(welded.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 2, 1, 3]);
+        assert_eq!(triangles(&vertices, &(0..6).collect::<Vec<u32>>()), triangles(&welded, &indices));
+    }
+
+    #[test]
+    fn weld_keeps_vertices_differing_in_any_attribute() {
+        let mut seam = vertex(1.0, 0.0);
+        seam.TexCoords = vec2(0.0, 0.0);
+        let vertices = vec![vertex(0.0, 0.0), vertex(1.0, 0.0), seam, vertex(0.0, 0.0)];
+        let (welded, indices) = weld_vertices(&vertices, &[0, 1, 2, 3, 1, 2]);
+        assert_eq!(welded.len(), 3);
+        assert_eq!(indices, vec![0, 1, 2, 0, 1, 2]);
+    }
+
+    #[test]
+    fn vertex_fetch_orders_vertices_by_first_use() {
+        let vertices: Vec<Vertex> = (0..5).map(|x| vertex(x as f32, 0.0)).collect();
+        let mut indices = vec![3, 1, 4, 4, 1, 0];
+        let reordered = optimize_vertex_fetch(&vertices, &mut indices);
+        // vertex 2 is not used and dropped
+        assert_eq!(reordered.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 2, 1, 3]);
+        let xs: Vec<f32> = reordered.iter().map(|vertex| vertex.Position.x).collect();
+        assert_eq!(xs, vec![3.0, 1.0, 4.0, 0.0]);
+    }
+
+    #[test]
+    fn vertex_cache_lowers_grid_acmr() {
+        // rows of 65 vertices don't fit the cache, so the row order reuses only about half of them
+        let (vertices, indices) = grid(64);
+        let optimized = optimize_vertex_cache(&indices, vertices.len());
+        let before = acmr(&indices, ACMR_CACHE_SIZE);
+        let after = acmr(&optimized, ACMR_CACHE_SIZE);
+        assert!(after < before * 0.8, "ACMR {} -> {}", before, after);
+        assert_eq!(triangles(&vertices, &indices), triangles(&vertices, &optimized));
+    }
+
+    #[test]
+    fn optimize_shrinks_an_unwelded_grid() {
+        let (vertices, indices) = grid(16);
+        let unwelded: Vec<Vertex> = indices.iter().map(|&index| vertices[index as usize]).collect();
+        let (optimized_vertices, optimized_indices, report) = optimize(&unwelded, &[]);
+        assert_eq!(report.vertices_before, unwelded.len());
+        assert_eq!(report.vertices_after, vertices.len());
+        assert_eq!(optimized_vertices.len(), vertices.len());
+        assert!(report.acmr_after < report.acmr_before);
+        assert_eq!(
+            triangles(&unwelded, &(0..unwelded.len() as u32).collect::<Vec<u32>>()),
+            triangles(&optimized_vertices, &optimized_indices)
+        );
+    }
+}
@@ -5,7 +5,12 @@
 #![allow(unused_variables)]
 
 use crate::aiscene::*;
+use crate::assets::{asset_file, open_image};
+use crate::bounds::{Aabb, BoundingSphere};
+use crate::instance_buffer::InstanceBuffer;
 use crate::mesh::{Mesh, Texture, Vertex};
+use crate::shader::Shader;
+use crate::vertex_attributes::VertexLayout;
 use crate::ShaderId;
 use glad_gl::gl;
 use glad_gl::gl::{GLint, GLsizei, GLuint, GLvoid};
@@ -16,13 +21,21 @@ use russimp::sys::*;
 use std::os::raw::c_uint;
 use std::path::{Path, PathBuf};
 use std::ptr::*;
+use std::rc::Rc;
 
 // model data
+// The meshes and textures are freed when the model is dropped. Share a model with Rc<Model> rather than loading it twice.
 #[derive(Debug)]
 pub struct Model {
     // stores all the textures loaded so far, optimization to make sure textures aren't loaded more than once.
-    pub textures_loaded: Vec<Texture>,
+    pub textures_loaded: Vec<Rc<Texture>>,
     pub meshes: Vec<Mesh>,
+    // the transform of the node each mesh hangs from, relative to the model. draw doesn't apply these,
+    // which is fine for the obj models the examples load, where they are all the identity.
+    pub mesh_transforms: Vec<Mat4>,
+    // bounds of all meshes placed by their node transforms
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere,
     pub directory: String,
     pub gammaCorrection: bool,
     pub flipv: bool,
@@ -36,24 +49,89 @@ impl Model {
         let mut model = Model {
             textures_loaded: vec![],
             meshes: vec![],
+            mesh_transforms: vec![],
+            aabb: Aabb::EMPTY,
+            bounding_sphere: BoundingSphere::EMPTY,
             directory: "".to_string(),
             gammaCorrection: gamma.0,
             flipv: flipv.0,
         };
         model.load_model(path);
+        model.compute_bounds();
         model
     }
 
+    // A model of meshes built in code, like the levels of detail of a loaded model, each placed by
+    // its transform. textures_loaded is filled in from the textures of the meshes.
+    pub fn from_meshes(meshes: Vec<Mesh>, mesh_transforms: Vec<Mat4>) -> Model {
+        assert_eq!(meshes.len(), mesh_transforms.len(), "Model::from_meshes needs a transform per mesh");
+        let mut textures_loaded: Vec<Rc<Texture>> = vec![];
+        for texture in meshes.iter().flat_map(|mesh| mesh.textures.iter()) {
+            if !textures_loaded.iter().any(|loaded| Rc::ptr_eq(loaded, texture)) {
+                textures_loaded.push(texture.clone());
+            }
+        }
+        let mut model = Model {
+            textures_loaded,
+            meshes,
+            mesh_transforms,
+            aabb: Aabb::EMPTY,
+            bounding_sphere: BoundingSphere::EMPTY,
+            directory: "".to_string(),
+            gammaCorrection: false,
+            flipv: false,
+        };
+        model.compute_bounds();
+        model
+    }
+
+    // Updates aabb and bounding_sphere from the bounds of the meshes.
+    pub fn compute_bounds(&mut self) {
+        let placed = || self.meshes.iter().zip(self.mesh_transforms.iter());
+        self.aabb = placed().fold(Aabb::EMPTY, |aabb, (mesh, transform)| aabb.union(&mesh.aabb.transform(transform)));
+        if self.aabb.is_empty() {
+            self.bounding_sphere = BoundingSphere::EMPTY;
+            return;
+        }
+        // centered on the box, large enough for every mesh's sphere
+        let center = self.aabb.center();
+        let radius = placed()
+            .map(|(mesh, transform)| mesh.bounding_sphere.transform(transform))
+            .filter(|sphere| !sphere.is_empty())
+            .map(|sphere| sphere.center.distance(center) + sphere.radius)
+            .fold(0.0, f32::max);
+        // the sphere around the box can be the tighter one after rotations
+        self.bounding_sphere = BoundingSphere::new(center, radius.min(self.aabb.extents().length()));
+    }
+
+    #[deprecated(note = "use draw(&Shader), which takes texture units from the shader")]
+    #[allow(deprecated)]
     pub fn Draw(&self, shader_id: ShaderId) {
         for mesh in &self.meshes {
             mesh.Draw(shader_id);
         }
     }
 
+    // draws with texture units allocated by the shader, see Mesh::draw
+    pub fn draw(&self, shader: &Shader) {
+        for mesh in &self.meshes {
+            mesh.draw(shader);
+        }
+    }
+
+    // draws every mesh once per instance, see Mesh::draw_instanced
+    pub fn draw_instanced<T: VertexLayout>(&self, shader: &Shader, instances: &InstanceBuffer<T>) {
+        for mesh in &self.meshes {
+            mesh.draw_instanced(shader, instances);
+        }
+    }
+
     // loads a model with supported ASSIMP extensions from file and stores the resulting meshes in the meshes vector.
     fn load_model(&mut self, path: &str) {
+        // assimp reads from disk, embedded models are extracted first
+        let file = asset_file(path).unwrap_or_else(|error| panic!("Failed to extract model {}: {}", path, error));
         let scene = AiScene::from_file(
-            path,
+            file.to_str().unwrap(),
             vec![
                 PostProcess::Triangulate,
                 PostProcess::GenerateSmoothNormals,
@@ -70,7 +148,7 @@ impl Model {
                 self.directory = Path::new(path).parent().expect("path error").to_str().unwrap().to_string();
 
                 if let Some(aiscene) = scene.assimp_scene {
-                    self.process_node(aiscene.mRootNode, aiscene);
+                    self.process_node(aiscene.mRootNode, aiscene, Mat4::IDENTITY);
                 }
             }
             Err(err) => panic!("{}", err),
@@ -78,16 +156,23 @@ impl Model {
         // println!("Model:\n{:#?}", self);
     }
 
-    fn process_node(&mut self, node: *mut aiNode, scene: &aiScene) {
-        // process each mesh located at the current node
+    fn process_node(&mut self, node: *mut aiNode, scene: &aiScene, parent_transform: Mat4) {
+        let transform = parent_transform * mat4_from_ai(unsafe { &(*node).mTransformation });
+
+        // Process each mesh located at the current node: the node's mMeshes index into the scene's meshes.
+        // Loading every scene mesh at every node instead duplicated meshes in files with more than one node,
+        // and placed them with the transform of a node they don't belong to.
         // println!("{:?}", unsafe { (*node).mName });
 
-        let slice = slice_from_raw_parts(scene.mMeshes, scene.mNumMeshes as usize);
-        let ai_meshes = unsafe { slice.as_ref() }.unwrap();
+        let slice = unsafe { slice_from_raw_parts((*node).mMeshes, (*node).mNumMeshes as usize) };
+        let ai_meshes = unsafe { slice_from_raw_parts(scene.mMeshes, scene.mNumMeshes as usize).as_ref() }.unwrap();
 
-        for i in 0..ai_meshes.len() {
-            let mesh = self.process_mesh(ai_meshes[i], scene);
-            self.meshes.push(mesh);
+        if let Some(mesh_indices) = unsafe { slice.as_ref() } {
+            for mesh_index in mesh_indices {
+                let mesh = self.process_mesh(ai_meshes[*mesh_index as usize], scene);
+                self.meshes.push(mesh);
+                self.mesh_transforms.push(transform);
+            }
         }
 
         // Process childern nodes
@@ -96,7 +181,7 @@ impl Model {
         if let Some(child_nodes) = unsafe { slice.as_ref() } {
             for i in 0..child_nodes.len() {
                 // println!("{:#?}", unsafe { (*child_nodes[i]).mName });
-                self.process_node(child_nodes[i], scene);
+                self.process_node(child_nodes[i], scene, transform);
             }
         }
     }
@@ -106,7 +191,7 @@ impl Model {
 
         let mut vertices: Vec<Vertex> = vec![];
         let mut indices: Vec<u32> = vec![];
-        let mut textures: Vec<Texture> = vec![];
+        let mut textures: Vec<Rc<Texture>> = vec![];
 
         let ai_vertices = get_vec_from_parts(scene_mesh.mVertices, scene_mesh.mNumVertices);
         let ai_normals = get_vec_from_parts(scene_mesh.mNormals, scene_mesh.mNumVertices);
@@ -182,8 +267,8 @@ impl Model {
         mesh
     }
 
-    fn loadMaterialTextures(&mut self, ai_material: *mut aiMaterial, ai_texture_type: c_uint, typeName: &str) -> Vec<Texture> {
-        let mut textures: Vec<Texture> = vec![];
+    fn loadMaterialTextures(&mut self, ai_material: *mut aiMaterial, ai_texture_type: c_uint, typeName: &str) -> Vec<Rc<Texture>> {
+        let mut textures: Vec<Rc<Texture>> = vec![];
 
         let texture_count = unsafe { aiGetMaterialTextureCount(ai_material, ai_texture_type) };
 
@@ -192,17 +277,17 @@ impl Model {
             if let Ok(filename) = texture_file {
                 let loaded_texture = self.textures_loaded.iter().find(|t| t.path == filename);
                 if let Some(texture) = loaded_texture {
-                    textures.push(texture.clone());
+                    textures.push(Rc::clone(texture));
                 } else {
                     let mut filepath = PathBuf::from(&self.directory);
                     filepath.push(&filename);
                     let id = self.textureFromFile(&filepath);
-                    let texture = Texture {
+                    let texture = Rc::new(Texture {
                         id,
                         texture_type: typeName.to_string(),
                         path: filename,
-                    };
-                    textures.push(texture.clone());
+                    });
+                    textures.push(Rc::clone(&texture));
                     self.textures_loaded.push(texture);
                 }
             }
@@ -213,7 +298,7 @@ impl Model {
     fn textureFromFile(&self, filepath: &Path) -> u32 {
         let mut texture_id: GLuint = 0;
 
-        let img = image::open(filepath).expect("Texture failed to load");
+        let img = open_image(filepath).expect("Texture failed to load");
         let (width, height) = (img.width() as GLsizei, img.height() as GLsizei);
 
         let color_type = img.color();
@@ -285,3 +370,14 @@ fn get_vec_from_parts(raw_data: *mut aiVector3D, size: c_uint) -> Vec<Vec3> {
     let raw_array = unsafe { slice.as_ref() }.unwrap();
     raw_array.iter().map(|aiv| vec3(aiv.x, aiv.y, aiv.z)).collect()
 }
+
+// assimp matrices are row major
+#[rustfmt::skip]
+fn mat4_from_ai(m: &aiMatrix4x4) -> Mat4 {
+    Mat4::from_cols_array(&[
+        m.a1, m.b1, m.c1, m.d1,
+        m.a2, m.b2, m.c2, m.d2,
+        m.a3, m.b3, m.c3, m.d3,
+        m.a4, m.b4, m.c4, m.d4,
+    ])
+}
@@ -0,0 +1,380 @@
+#![allow(dead_code)]
+
+use glam::*;
+use std::f32::consts::PI;
+
+use crate::mesh::{Mesh, Vertex};
+use crate::mesh_optimizer;
+use crate::mesh_optimizer::OptimizeReport;
+use crate::tangent_space::{compute_normals, compute_tangents};
+
+// Procedural meshes to stand in for the hand written vertex arrays of renderCube and friends.
+//
+// Every vertex has a position, normal, texture coordinates and a tangent and bitangent pointing along
+// +u and +v, so the meshes work with the normal mapping shaders as well. Triangles wind counter
+// clockwise seen from outside, matching the default glFrontFace.
+//
+//    let cube = primitives::cube();
+//    let floor = primitives::plane(25.0, 1);
+//    ...
+//    cube.draw(&shader);
+//
+// The *_geometry functions are the same shapes as CPU data, for changing before upload.
+
+// vertices and indices that have not been uploaded to the GPU yet
+#[derive(Debug, Clone, Default)]
+pub struct Geometry {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl Geometry {
+    pub fn mesh(self) -> Mesh {
+        Mesh::new(self.vertices, self.indices, vec![])
+    }
+
+    // see tangent_space::compute_normals
+    pub fn compute_normals(&mut self, smoothing_angle: f32) {
+        compute_normals(&mut self.vertices, &mut self.indices, smoothing_angle);
+    }
+
+    // see tangent_space::compute_tangents
+    pub fn compute_tangents(&mut self) -> bool {
+        compute_tangents(&mut self.vertices, &mut self.indices)
+    }
+
+    // see mesh_optimizer::optimize
+    pub fn optimize(&mut self) -> OptimizeReport {
+        let (vertices, indices, report) = mesh_optimizer::optimize(&self.vertices, &self.indices);
+        self.vertices = vertices;
+        self.indices = indices;
+        report
+    }
+
+    fn append(&mut self, other: Geometry) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.indices.extend(other.indices.iter().map(|index| base + index));
+    }
+}
+
+pub fn vertex(position: Vec3, normal: Vec3, tex_coords: Vec2, tangent: Vec3) -> Vertex {
+    Vertex {
+        Position: position,
+        Normal: normal,
+        TexCoords: tex_coords,
+        Tangent: tangent,
+        Bitangent: normal.cross(tangent),
+        ..Vertex::new()
+    }
+}
+
+// A (columns + 1) x (rows + 1) grid of vertices over s and t from 0 to 1, made into two triangles per cell.
+// The surface must wind counter clockwise as s then t increase, seen from the side the normal points to.
+// Triangles collapsed to a line or point, like those around the pole of a sphere, are left out.
+fn grid(columns: u32, rows: u32, point: impl Fn(f32, f32) -> Vertex) -> Geometry {
+    let mut geometry = Geometry::default();
+    for row in 0..=rows {
+        for column in 0..=columns {
+            geometry.vertices.push(point(column as f32 / columns as f32, row as f32 / rows as f32));
+        }
+    }
+
+    let stride = columns + 1;
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = row * stride + column;
+            let b = a + 1;
+            let c = a + stride + 1;
+            let d = a + stride;
+            for triangle in [[a, b, c], [a, c, d]] {
+                if !is_degenerate(&geometry.vertices, triangle) {
+                    geometry.indices.extend(triangle);
+                }
+            }
+        }
+    }
+    geometry
+}
+
+fn is_degenerate(vertices: &[Vertex], [a, b, c]: [u32; 3]) -> bool {
+    let (a, b, c) = (vertices[a as usize].Position, vertices[b as usize].Position, vertices[c as usize].Position);
+    (b - a).cross(c - a).length_squared() < 1e-12
+}
+
+// a 2 x 2 x 2 cube centered on the origin, with its own vertices per face so the edges stay sharp
+pub fn cube_geometry() -> Geometry {
+    // normal, +u, +v for each face, with u x v = normal
+    let faces = [
+        (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+        (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+        (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+        (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+        (Vec3::Z, Vec3::X, Vec3::Y),
+        (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+    ];
+    let mut geometry = Geometry::default();
+    for (normal, u, v) in faces {
+        geometry.append(grid(1, 1, |s, t| {
+            vertex(normal + u * (s * 2.0 - 1.0) + v * (t * 2.0 - 1.0), normal, vec2(s, t), u)
+        }));
+    }
+    geometry
+}
+
+// a size x size square in the xz plane facing +y, split into subdivisions x subdivisions cells
+pub fn plane_geometry(size: f32, subdivisions: u32) -> Geometry {
+    let subdivisions = subdivisions.max(1);
+    grid(subdivisions, subdivisions, |s, t| {
+        vertex(vec3((s - 0.5) * size, 0.0, (0.5 - t) * size), Vec3::Y, vec2(s, t), Vec3::X)
+    })
+}
+
+// A sphere of radius 1 from segments slices around the y axis and rings stacks from pole to pole.
+// The texture wraps around once, with u following the slices and v going from the bottom to the top.
+pub fn uv_sphere_geometry(segments: u32, rings: u32) -> Geometry {
+    grid(segments.max(3), rings.max(2), |s, t| {
+        let phi = s * 2.0 * PI;
+        let theta = t * PI;
+        let radius = theta.sin();
+        let normal = vec3(radius * phi.cos(), -theta.cos(), -radius * phi.sin());
+        vertex(normal, normal, vec2(s, t), vec3(-phi.sin(), 0.0, -phi.cos()))
+    })
+}
+
+// A sphere of radius 1 from an icosahedron with each triangle split into four, subdivisions times.
+// The triangles are all about the same size, unlike the uv sphere's. Texture coordinates are the
+// same spherical mapping as the uv sphere, but the vertices are shared across the seam, so a texture
+// smears on the triangles crossing it.
+pub fn icosphere_geometry(subdivisions: u32) -> Geometry {
+    let g = (1.0 + 5f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vec3> = [
+        vec3(-1.0, g, 0.0),
+        vec3(1.0, g, 0.0),
+        vec3(-1.0, -g, 0.0),
+        vec3(1.0, -g, 0.0),
+        vec3(0.0, -1.0, g),
+        vec3(0.0, 1.0, g),
+        vec3(0.0, -1.0, -g),
+        vec3(0.0, 1.0, -g),
+        vec3(g, 0.0, -1.0),
+        vec3(g, 0.0, 1.0),
+        vec3(-g, 0.0, -1.0),
+        vec3(-g, 0.0, 1.0),
+    ]
+    .iter()
+    .map(|position| position.normalize())
+    .collect();
+
+    #[rustfmt::skip]
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints: std::collections::HashMap<(u32, u32), u32> = std::collections::HashMap::new();
+        let mut midpoint = |a: u32, b: u32| -> u32 {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                positions.push(((positions[a as usize] + positions[b as usize]) / 2.0).normalize());
+                positions.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|&[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let vertices = positions
+        .iter()
+        .map(|&normal| {
+            let phi = (-normal.z).atan2(normal.x).rem_euclid(2.0 * PI);
+            let theta = (-normal.y).clamp(-1.0, 1.0).acos();
+            vertex(normal, normal, vec2(phi / (2.0 * PI), theta / PI), vec3(-phi.sin(), 0.0, -phi.cos()))
+        })
+        .collect();
+
+    Geometry {
+        vertices,
+        indices: triangles.into_iter().flatten().collect(),
+    }
+}
+
+// A flat disc of the given radius at height y, facing up or down, for the caps of cylinders and cones.
+fn disc(radius: f32, y: f32, segments: u32, facing_up: bool) -> Geometry {
+    let normal = if facing_up { Vec3::Y } else { Vec3::NEG_Y };
+    grid(segments, 1, |s, t| {
+        let phi = s * 2.0 * PI;
+        // the rim is at t = 0 on top, so the triangles wind the right way for each side
+        let rho = if facing_up { 1.0 - t } else { t };
+        let x = rho * phi.cos();
+        let z = -rho * phi.sin();
+        let v = if facing_up { 0.5 - z / 2.0 } else { 0.5 + z / 2.0 };
+        vertex(vec3(x * radius, y, z * radius), normal, vec2(0.5 + x / 2.0, v), Vec3::X)
+    })
+}
+
+// a capped cylinder of radius 1 from y = -1 to 1
+pub fn cylinder_geometry(segments: u32) -> Geometry {
+    let segments = segments.max(3);
+    let mut geometry = grid(segments, 1, |s, t| {
+        let phi = s * 2.0 * PI;
+        let normal = vec3(phi.cos(), 0.0, -phi.sin());
+        vertex(normal + Vec3::Y * (t * 2.0 - 1.0), normal, vec2(s, t), vec3(-phi.sin(), 0.0, -phi.cos()))
+    });
+    geometry.append(disc(1.0, 1.0, segments, true));
+    geometry.append(disc(1.0, -1.0, segments, false));
+    geometry
+}
+
+// a cone with a base of radius 1 at y = -1 and its tip at y = 1
+pub fn cone_geometry(segments: u32) -> Geometry {
+    let segments = segments.max(3);
+    let (radius, height) = (1.0, 2.0);
+    let mut geometry = grid(segments, 1, |s, t| {
+        let phi = s * 2.0 * PI;
+        let rho = (1.0 - t) * radius;
+        let position = vec3(rho * phi.cos(), t * height - height / 2.0, -rho * phi.sin());
+        let normal = vec3(height * phi.cos(), radius, -height * phi.sin()).normalize();
+        vertex(position, normal, vec2(s, t), vec3(-phi.sin(), 0.0, -phi.cos()))
+    });
+    geometry.append(disc(radius, -height / 2.0, segments, false));
+    geometry
+}
+
+// A torus around the y axis. major_radius is the distance from the center to the middle of the tube,
+// minor_radius the radius of the tube. u goes around the y axis, v around the tube.
+pub fn torus_geometry(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> Geometry {
+    grid(segments.max(3), sides.max(3), |s, t| {
+        let phi = s * 2.0 * PI;
+        let theta = t * 2.0 * PI;
+        let center = vec3(phi.cos(), 0.0, -phi.sin()) * major_radius;
+        let normal = vec3(theta.cos() * phi.cos(), theta.sin(), -theta.cos() * phi.sin());
+        vertex(center + normal * minor_radius, normal, vec2(s, t), vec3(-phi.sin(), 0.0, -phi.cos()))
+    })
+}
+
+// One triangle covering the whole of clip space, for post processing passes. Cheaper than a quad since
+// no pixels are shaded twice along the diagonal. Texture coordinates are 0 to 1 over the screen.
+pub fn fullscreen_triangle_geometry() -> Geometry {
+    let corners = [vec2(-1.0, -1.0), vec2(3.0, -1.0), vec2(-1.0, 3.0)];
+    Geometry {
+        vertices: corners
+            .iter()
+            .map(|corner| vertex(corner.extend(0.0), Vec3::Z, (*corner + 1.0) / 2.0, Vec3::X))
+            .collect(),
+        indices: vec![0, 1, 2],
+    }
+}
+
+pub fn cube() -> Mesh {
+    cube_geometry().mesh()
+}
+
+pub fn plane(size: f32, subdivisions: u32) -> Mesh {
+    plane_geometry(size, subdivisions).mesh()
+}
+
+pub fn uv_sphere(segments: u32, rings: u32) -> Mesh {
+    uv_sphere_geometry(segments, rings).mesh()
+}
+
+pub fn icosphere(subdivisions: u32) -> Mesh {
+    icosphere_geometry(subdivisions).mesh()
+}
+
+pub fn cylinder(segments: u32) -> Mesh {
+    cylinder_geometry(segments).mesh()
+}
+
+pub fn cone(segments: u32) -> Mesh {
+    cone_geometry(segments).mesh()
+}
+
+pub fn torus(major_radius: f32, minor_radius: f32, segments: u32, sides: u32) -> Mesh {
+    torus_geometry(major_radius, minor_radius, segments, sides).mesh()
+}
+
+pub fn fullscreen_triangle() -> Mesh {
+    fullscreen_triangle_geometry().mesh()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(name: &str, geometry: &Geometry, vertex_count: usize, triangle_count: usize) {
+        assert_eq!(geometry.vertices.len(), vertex_count, "{} vertices", name);
+        assert_eq!(geometry.indices.len(), triangle_count * 3, "{} indices", name);
+        assert!(geometry.indices.iter().all(|&index| (index as usize) < vertex_count), "{} index out of range", name);
+
+        for (i, vertex) in geometry.vertices.iter().enumerate() {
+            let normal = vertex.Normal;
+            assert!((normal.length() - 1.0).abs() < 1e-4, "{} vertex {} normal {:?}", name, i, normal);
+        }
+
+        // counter clockwise seen from the side every corner's normal points to
+        for (i, triangle) in geometry.indices.chunks_exact(3).enumerate() {
+            let [a, b, c] = [0, 1, 2].map(|corner| geometry.vertices[triangle[corner] as usize]);
+            let face = (b.Position - a.Position).cross(c.Position - a.Position);
+            for corner in [a, b, c] {
+                assert!(face.dot(corner.Normal) > 0.0, "{} triangle {} winds the wrong way", name, i);
+            }
+        }
+    }
+
+    #[test]
+    fn cube() {
+        check("cube", &cube_geometry(), 24, 12);
+    }
+
+    #[test]
+    fn plane() {
+        check("plane", &plane_geometry(10.0, 1), 4, 2);
+        check("plane", &plane_geometry(10.0, 4), 25, 32);
+        // no subdivisions is taken as one
+        check("plane", &plane_geometry(10.0, 0), 4, 2);
+    }
+
+    #[test]
+    fn uv_sphere() {
+        // the triangles with two corners on a pole are left out
+        check("uv_sphere", &uv_sphere_geometry(16, 8), 17 * 9, 2 * 16 * 8 - 2 * 16);
+        check("uv_sphere", &uv_sphere_geometry(3, 2), 4 * 3, 2 * 3 * 2 - 2 * 3);
+    }
+
+    #[test]
+    fn icosphere() {
+        check("icosphere", &icosphere_geometry(0), 12, 20);
+        check("icosphere", &icosphere_geometry(1), 42, 80);
+        check("icosphere", &icosphere_geometry(3), 642, 1280);
+    }
+
+    #[test]
+    fn cylinder() {
+        // the side, then a cap at each end with the triangles at the center left out
+        check("cylinder", &cylinder_geometry(12), 3 * 13 * 2, 2 * 12 + 12 + 12);
+    }
+
+    #[test]
+    fn cone() {
+        // the side, with the triangles at the tip left out, then the base
+        check("cone", &cone_geometry(12), 2 * 13 * 2, 12 + 12);
+    }
+
+    #[test]
+    fn torus() {
+        check("torus", &torus_geometry(1.0, 0.3, 24, 12), 25 * 13, 2 * 24 * 12);
+    }
+
+    #[test]
+    fn fullscreen_triangle() {
+        check("fullscreen_triangle", &fullscreen_triangle_geometry(), 3, 1);
+    }
+}
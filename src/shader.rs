@@ -4,102 +4,204 @@
 #![allow(unused_assignments)]
 
 use glad_gl::gl;
-use glad_gl::gl::{GLchar, GLint, GLuint};
 use glam::*;
 
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::Error;
-use std::path::Path;
-use std::ptr;
-
+use crate::shader_error::ShaderError;
+use crate::shader_program::ShaderProgram;
+use crate::texture_units::*;
+use crate::uniform_buffer::Std140;
+use crate::uniform_struct::UniformStruct;
+use crate::uniforms::*;
+use crate::vertex_attributes::*;
 use crate::*;
 
+use std::cell::RefCell;
+use std::collections::HashSet;
+
 pub struct Shader {
     pub id: ShaderId,
+    uniforms: UniformCache,
+    attributes: Vec<AttributeInfo>,
+    // the vertex and instance layouts warn_vertex_layout has already checked, by address
+    checked_layouts: RefCell<HashSet<(usize, usize)>>,
+    texture_units: TextureUnits,
 }
 
 impl Shader {
-    pub fn new(vertexPath: &str, fragmentPath: &str, geometryPath: Option<&str>) -> Result<Self, String> {
-        let mut shader = Shader { id: 0 };
-        let mut vertexCode: String = Default::default();
-        let mut fragmentCode: String = Default::default();
-        let mut geometryCode: String = Default::default();
-
-        match read_file(vertexPath) {
-            Ok(content) => vertexCode = content,
-            Err(error) => return Err(error.to_string()),
+    pub fn new(vertexPath: &str, fragmentPath: &str, geometryPath: Option<&str>) -> Result<Self, ShaderError> {
+        let mut program = ShaderProgram::new().vertex_file(vertexPath).fragment_file(fragmentPath);
+        if let Some(geometryPath) = geometryPath {
+            program = program.geometry_file(geometryPath);
         }
+        program.build()
+    }
 
-        match read_file(fragmentPath) {
-            Ok(content) => fragmentCode = content,
-            Err(error) => return Err(error.to_string()),
+    // wraps an already linked program, reflecting its active uniforms and vertex inputs
+    pub fn from_id(id: ShaderId) -> Shader {
+        Shader {
+            id,
+            uniforms: UniformCache::reflect(id),
+            attributes: reflect_attributes(id),
+            checked_layouts: RefCell::new(HashSet::new()),
+            texture_units: TextureUnits::new(),
         }
+    }
 
-        if let Some(geometryPath) = geometryPath {
-            match read_file(geometryPath) {
-                Ok(content) => geometryCode = content,
-                Err(error) => return Err(error.to_string()),
-            }
-        }
+    // the active uniforms of the program, sorted by location
+    pub fn uniforms(&self) -> Vec<UniformInfo> {
+        self.uniforms.list()
+    }
 
-        unsafe {
-            // vertex shader
-            let vertexShader = gl::CreateShader(gl::VERTEX_SHADER);
-            let c_string = c_string!(vertexCode);
-            gl::ShaderSource(vertexShader, 1, &c_string.as_ptr(), ptr::null());
-            gl::CompileShader(vertexShader);
-
-            if let Err(error) = checkCompileErrors(vertexShader, "VERTEX") {
-                return Err(error);
-            }
+    pub fn uniform(&self, name: &str) -> Option<UniformInfo> {
+        self.uniforms.get(self.id, name)
+    }
 
-            // fragment shader
-            let fragmentShader = gl::CreateShader(gl::FRAGMENT_SHADER);
-            let c_string = c_string!(fragmentCode);
-            gl::ShaderSource(fragmentShader, 1, &c_string.as_ptr(), ptr::null());
-            gl::CompileShader(fragmentShader);
+    pub fn has_uniform(&self, name: &str) -> bool {
+        self.uniform(name).is_some()
+    }
 
-            if let Err(error) = checkCompileErrors(fragmentShader, "FRAGMENT") {
-                return Err(error);
-            }
+    // the active vertex inputs of the program, sorted by location
+    pub fn attributes(&self) -> &[AttributeInfo] {
+        &self.attributes
+    }
 
-            // geometry shader
-            let mut geometryShader: GLuint = 0;
-            if let Some(_geometryPath) = geometryPath {
-                geometryShader = gl::CreateShader(gl::GEOMETRY_SHADER);
-                let c_string = c_string!(geometryCode);
-                gl::ShaderSource(geometryShader, 1, &c_string.as_ptr(), ptr::null());
-                gl::CompileShader(geometryShader);
-
-                if let Err(error) = checkCompileErrors(geometryShader, "GEOMETRY") {
-                    return Err(error);
-                }
-            }
+    // Describes each vertex input of the program that the layout does not provide, or provides with the
+    // wrong type, like an ivec4 of bone ids fed from floats. Empty when the layout fits the program.
+    pub fn check_vertex_layout(&self, layout: &[VertexAttribute]) -> Vec<String> {
+        check_attributes(&self.attributes, layout)
+    }
 
-            // shader program
-            shader.id = gl::CreateProgram();
-            // link the first program object
-            gl::AttachShader(shader.id, vertexShader);
-            gl::AttachShader(shader.id, fragmentShader);
-            if let Some(_geometryPath) = geometryPath {
-                gl::AttachShader(shader.id, geometryShader);
-            }
-            gl::LinkProgram(shader.id);
+    // check_vertex_layout, printing the mismatches the first time a layout is used with this shader.
+    pub fn warn_vertex_layout(&self, layout: &[VertexAttribute]) {
+        self.warn_instanced_layout(layout, &[]);
+    }
+
+    // warn_vertex_layout for instanced draws, where the instance attributes take the place of any
+    // vertex attributes at the same locations.
+    pub fn warn_instanced_layout(&self, vertex: &[VertexAttribute], instance: &[VertexAttribute]) {
+        let key = (vertex.as_ptr() as usize, instance.as_ptr() as usize);
+        if !self.checked_layouts.borrow_mut().insert(key) {
+            return;
+        }
+        let mut layout: Vec<VertexAttribute> = vertex
+            .iter()
+            .filter(|attribute| !instance.iter().any(|other| other.overlaps(attribute)))
+            .copied()
+            .collect();
+        layout.extend_from_slice(instance);
+        for mismatch in self.check_vertex_layout(&layout) {
+            eprintln!("Shader warning: program {}: {}", self.id, mismatch);
+        }
+    }
 
-            if let Err(error) = checkCompileErrors(shader.id, "PROGRAM") {
-                return Err(error);
+    // the active uniform blocks of the program, sorted by index
+    pub fn uniform_blocks(&self) -> Vec<UniformBlockInfo> {
+        self.uniforms.blocks()
+    }
+
+    // Links the named uniform block to a binding point, after checking that the block the linker
+    // laid out is the same size as T in std140 layout.
+    pub fn bind_uniform_block<T: Std140>(&self, name: &str, binding: u32) -> Result<(), ShaderError> {
+        let block = match self.uniforms.block(name) {
+            Some(block) => block,
+            None => {
+                return Err(ShaderError::UniformBlock {
+                    name: name.to_string(),
+                    message: "not an active uniform block of this program".to_string(),
+                })
             }
+        };
+        if block.size != T::SIZE {
+            return Err(ShaderError::UniformBlock {
+                name: name.to_string(),
+                message: format!("block is {} bytes but {} is {} bytes in std140 layout", block.size, std::any::type_name::<T>(), T::SIZE),
+            });
+        }
+        unsafe {
+            gl::UniformBlockBinding(self.id, block.index, binding);
+        }
+        Ok(())
+    }
 
-            // delete the shaders as they're linked into our program now and no longer necessary
-            gl::DeleteShader(vertexShader);
-            gl::DeleteShader(fragmentShader);
-            if let Some(_geometryPath) = geometryPath {
-                gl::DeleteShader(geometryShader);
+    // Links the named shader storage block to a binding point, for blocks without a layout(binding = N).
+    pub fn bind_storage_block(&self, name: &str, binding: u32) -> Result<(), ShaderError> {
+        let index = unsafe {
+            let c_string = c_string!(name);
+            gl::GetProgramResourceIndex(self.id, gl::SHADER_STORAGE_BLOCK, c_string.as_ptr())
+        };
+        if index == gl::INVALID_INDEX {
+            return Err(ShaderError::UniformBlock {
+                name: name.to_string(),
+                message: "not an active shader storage block of this program".to_string(),
+            });
+        }
+        unsafe {
+            gl::ShaderStorageBlockBinding(self.id, index, binding);
+        }
+        Ok(())
+    }
+
+    fn location(&self, name: &str, setter: &str, accepts: fn(gl::GLenum) -> bool) -> gl::GLint {
+        self.uniforms.location(self.id, name, setter, accepts)
+    }
+
+    // Location and element count for an array setter. name is the array, "offsets" or "offsets[0]", or an
+    // element to start from, "offsets[2]". Values past the end of the uniform array are dropped with a warning.
+    fn array_location(&self, name: &str, setter: &str, accepts: fn(gl::GLenum) -> bool, len: usize) -> (gl::GLint, gl::GLsizei) {
+        let location = self.location(name, setter, accepts);
+        if location == -1 {
+            return (-1, 0);
+        }
+        let size = self.uniform(name).map_or(1, |uniform| uniform.size.max(1) as usize);
+        if len > size {
+            self.uniforms
+                .warn(name, &format!("{} given {} values for '{}' which only has room for {}", setter, len, name, size));
+        }
+        (location, len.min(size) as gl::GLsizei)
+    }
+
+    // Binds a texture for a sampler uniform on a texture unit allocated by this shader, and points the
+    // sampler at it. The target (2D, cube map, 2D array, multisample, ...) comes from the sampler's type.
+    // A sampler keeps its unit until texture_units::begin_frame() is called.
+    //
+    //    texture_units::begin_frame();
+    //    shader.use_shader();
+    //    shader.set_texture("diffuseTexture", &woodTexture);
+    //    shader.set_texture("shadowMap", &depthMap);
+    //
+    pub fn set_texture<T: TextureHandle + ?Sized>(&self, name: &str, texture: &T) {
+        let location = self.location(name, "set_texture", is_sampler);
+        if location == -1 {
+            return;
+        }
+        let target = self
+            .uniform(name)
+            .and_then(|uniform| sampler_target(uniform.gl_type))
+            .unwrap_or(gl::TEXTURE_2D);
+
+        let unit = match self.texture_units.unit_for(name) {
+            Some(unit) => unit,
+            None => {
+                eprintln!("set_texture: no free texture unit for '{}' in program {}", name, self.id);
+                return;
             }
+        };
+        unsafe {
+            gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::BindTexture(target, texture.texture_id());
+            gl::Uniform1i(location, unit as i32);
         }
+    }
+
+    // the unit set_texture gave a sampler this frame
+    pub fn texture_unit(&self, name: &str) -> Option<u32> {
+        self.texture_units.get(name)
+    }
 
-        Ok(shader)
+    // Frees this shader's texture units before the next frame, for a shader used more than once per frame
+    // with different sets of samplers.
+    pub fn reset_texture_units(&self) {
+        self.texture_units.reset();
     }
 
     pub fn use_shader(&self) {
@@ -113,8 +215,7 @@ impl Shader {
     pub fn set_bool(&self, name: &str, value: bool) {
         unsafe {
             let v = if value { 1 } else { 0 };
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_bool", accepts_bool);
             gl::Uniform1i(location, v);
         }
     }
@@ -122,8 +223,7 @@ impl Shader {
     // ------------------------------------------------------------------------
     pub fn set_int(&self, name: &str, value: i32) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_int", accepts_int);
             gl::Uniform1i(location, value);
         }
     }
@@ -131,8 +231,7 @@ impl Shader {
     // ------------------------------------------------------------------------
     pub fn set_float(&self, name: &str, value: f32) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_float", accepts_float);
             gl::Uniform1f(location, value);
         }
     }
@@ -140,8 +239,7 @@ impl Shader {
     // ------------------------------------------------------------------------
     pub fn set_vec2(&self, name: &str, value: &Vec2) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_vec2", accepts_vec2);
             gl::Uniform2fv(location, 1, value.to_array().as_ptr());
         }
     }
@@ -149,8 +247,7 @@ impl Shader {
     // ------------------------------------------------------------------------
     pub fn set_xy(&self, name: &str, x: f32, y: f32) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_xy", accepts_vec2);
             gl::Uniform2f(location, x, y);
         }
     }
@@ -158,8 +255,7 @@ impl Shader {
     // ------------------------------------------------------------------------
     pub fn set_vec3(&self, name: &str, value: &Vec3) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_vec3", accepts_vec3);
             gl::Uniform3fv(location, 1, value.to_array().as_ptr());
         }
     }
@@ -167,8 +263,7 @@ impl Shader {
     // ------------------------------------------------------------------------
     pub fn set_xyz(&self, name: &str, x: f32, y: f32, z: f32) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_xyz", accepts_vec3);
             gl::Uniform3f(location, x, y, z);
         }
     }
@@ -176,8 +271,7 @@ impl Shader {
     // ------------------------------------------------------------------------
     pub fn set_vec4(&self, name: &str, value: &Vec4) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_vec4", accepts_vec4);
             gl::Uniform4fv(location, 1, value.to_array().as_ptr());
         }
     }
@@ -185,8 +279,7 @@ impl Shader {
     // ------------------------------------------------------------------------
     pub fn set_xyzw(&self, name: &str, x: f32, y: f32, z: f32, w: f32) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_xyzw", accepts_vec4);
             gl::Uniform4f(location, x, y, z, w);
         }
     }
@@ -194,8 +287,7 @@ impl Shader {
     // ------------------------------------------------------------------------
     pub fn set_mat2(&self, name: &str, mat: &Mat2) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_mat2", accepts_mat2);
             gl::UniformMatrix2fv(location, 1, gl::FALSE, mat.to_cols_array().as_ptr());
         }
     }
@@ -203,8 +295,7 @@ impl Shader {
     // ------------------------------------------------------------------------
     pub fn set_mat3(&self, name: &str, mat: &Mat3) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_mat3", accepts_mat3);
             gl::UniformMatrix3fv(location, 1, gl::FALSE, mat.to_cols_array().as_ptr());
         }
     }
@@ -212,46 +303,46 @@ impl Shader {
     // ------------------------------------------------------------------------
     pub fn set_mat4(&self, name: &str, matrix: &Mat4) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "set_mat4", accepts_mat4);
             gl::UniformMatrix4fv(location, 1, gl::FALSE, matrix.to_cols_array().as_ptr());
         }
     }
-}
 
-fn read_file(filename: &str) -> Result<String, Error> {
-    let mut content: String = Default::default();
-    let mut file = File::open(Path::new(filename))?;
-    file.read_to_string(&mut content)?;
-    Ok(content)
-}
+    // array uniform functions, one call uploads every element
+    // ------------------------------------------------------------------------
+    pub fn set_float_array(&self, name: &str, values: &[f32]) {
+        let (location, count) = self.array_location(name, "set_float_array", accepts_float, values.len());
+        if count > 0 {
+            unsafe {
+                gl::Uniform1fv(location, count, values.as_ptr());
+            }
+        }
+    }
 
-fn checkCompileErrors(shaderId: u32, checkType: &str) -> Result<(), String> {
-    unsafe {
-        let mut status = gl::FALSE as GLint;
-
-        if checkType != "PROGRAM" {
-            gl::GetShaderiv(shaderId, gl::COMPILE_STATUS, &mut status);
-            if status != (gl::TRUE as GLint) {
-                let mut len = 0;
-                gl::GetShaderiv(shaderId, gl::INFO_LOG_LENGTH, &mut len);
-                // Subtract 1 to skip the trailing null character.
-                let mut infoLog = vec![0; len as usize - 1];
-                gl::GetProgramInfoLog(shaderId, 1024, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                return Err(String::from_utf8_lossy(&infoLog).to_string());
+    // ------------------------------------------------------------------------
+    pub fn set_vec3_array(&self, name: &str, values: &[Vec3]) {
+        let (location, count) = self.array_location(name, "set_vec3_array", accepts_vec3, values.len());
+        if count > 0 {
+            // Vec3 is three packed f32s, the layout glUniform3fv reads
+            unsafe {
+                gl::Uniform3fv(location, count, values.as_ptr() as *const f32);
             }
-        } else {
-            gl::GetProgramiv(shaderId, gl::LINK_STATUS, &mut status);
-            if status != (gl::TRUE as GLint) {
-                let mut len = 0;
-                gl::GetProgramiv(shaderId, gl::INFO_LOG_LENGTH, &mut len);
-                // Subtract 1 to skip the trailing null character.
-                let mut infoLog = vec![0; len as usize - 1];
-                gl::GetProgramInfoLog(shaderId, 1024, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                let error_msg = String::from_utf8_lossy(&infoLog).to_string();
-                return Err(error_msg);
+        }
+    }
+
+    // ------------------------------------------------------------------------
+    pub fn set_mat4_array(&self, name: &str, values: &[Mat4]) {
+        let (location, count) = self.array_location(name, "set_mat4_array", accepts_mat4, values.len());
+        if count > 0 {
+            // Mat4 is sixteen column major f32s
+            unsafe {
+                gl::UniformMatrix4fv(location, count, gl::FALSE, values.as_ptr() as *const f32);
             }
         }
     }
-    Ok(())
+
+    // Sets every field of a glsl struct, or every element of an array of structs, see uniform_struct!
+    pub fn set_struct<T: UniformStruct + ?Sized>(&self, name: &str, value: &T) {
+        value.set_uniforms(self, name);
+    }
 }
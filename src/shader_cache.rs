@@ -0,0 +1,290 @@
+#![allow(dead_code)]
+
+use glad_gl::gl;
+use glad_gl::gl::{GLenum, GLint, GLsizei, GLuint, GLvoid};
+
+use std::ffi::CStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::shader_include::PreprocessedSource;
+use crate::shader_program::ShaderStage;
+
+// Stores linked program binaries on disk so later runs can skip compiling glsl.
+//
+// Entries are keyed by a hash of the preprocessed sources of every stage together with the
+// driver's vendor, renderer and version strings, so editing a shader or an include, or updating
+// the driver, simply misses the cache. Drivers may still reject a binary, in which case the entry
+// is removed and the caller compiles from source.
+//
+// file layout: the magic bytes "LOPB", the binary format and the binary's length (u32s, little endian),
+// then the program binary. A file that doesn't have that layout, like one cut short while it was
+// written, is a miss.
+#[derive(Debug, Clone)]
+pub struct ProgramCache {
+    dir: PathBuf,
+}
+
+impl ProgramCache {
+    pub fn new(dir: impl AsRef<Path>) -> ProgramCache {
+        ProgramCache {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    // Program binaries need GL 4.1 or ARB_get_program_binary, and at least one binary format.
+    pub fn is_supported() -> bool {
+        let mut formats: GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats);
+        }
+        formats > 0
+    }
+
+    pub fn key(sources: &[(ShaderStage, PreprocessedSource)]) -> String {
+        let driver = [gl::VENDOR, gl::RENDERER, gl::VERSION].map(driver_string);
+        key_for_driver(&driver, sources)
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.bin", key))
+    }
+
+    // Creates a program from a cached binary. Returns None on a miss or when the driver rejects the binary.
+    pub fn load(&self, key: &str) -> Option<GLuint> {
+        let path = self.path(key);
+        let data = fs::read(&path).ok()?;
+        let Some((format, binary)) = decode_entry(&data) else {
+            let _ = fs::remove_file(&path);
+            return None;
+        };
+
+        unsafe {
+            let program = gl::CreateProgram();
+            gl::ProgramBinary(program, format, binary.as_ptr() as *const GLvoid, binary.len() as GLsizei);
+
+            let mut status = gl::FALSE as GLint;
+            gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
+            if status != (gl::TRUE as GLint) {
+                gl::DeleteProgram(program);
+                let _ = fs::remove_file(&path);
+                return None;
+            }
+            Some(program)
+        }
+    }
+
+    // Saves the binary of a linked program. The program should have been linked with
+    // PROGRAM_BINARY_RETRIEVABLE_HINT set. Failures only mean the next run compiles again, so they are reported and ignored.
+    pub fn store(&self, key: &str, program: GLuint) {
+        let mut length: GLint = 0;
+        unsafe {
+            gl::GetProgramiv(program, gl::PROGRAM_BINARY_LENGTH, &mut length);
+        }
+        if length <= 0 {
+            return;
+        }
+
+        let mut binary = vec![0u8; length as usize];
+        let mut written: GLsizei = 0;
+        let mut format: GLenum = 0;
+        unsafe {
+            gl::GetProgramBinary(program, length, &mut written, &mut format, binary.as_mut_ptr() as *mut GLvoid);
+        }
+        binary.truncate(written as usize);
+
+        let data = encode_entry(format, &binary);
+        let result = fs::create_dir_all(&self.dir).and_then(|_| fs::write(self.path(key), data));
+        if let Err(error) = result {
+            eprintln!("Failed to write program cache {}: {}", self.path(key).display(), error);
+        }
+    }
+}
+
+// the key for sources on the driver with the given vendor, renderer and version strings
+fn key_for_driver(driver: &[String], sources: &[(ShaderStage, PreprocessedSource)]) -> String {
+    let mut hash = Fnv1a::new();
+    for name in driver {
+        hash.write(name.as_bytes());
+        hash.write(&[0]);
+    }
+    for (stage, source) in sources {
+        hash.write(stage.name().as_bytes());
+        hash.write(&[0]);
+        hash.write(source.code.as_bytes());
+        hash.write(&[0]);
+    }
+    format!("{:016x}", hash.finish())
+}
+
+const MAGIC: &[u8; 4] = b"LOPB";
+
+fn encode_entry(format: GLenum, binary: &[u8]) -> Vec<u8> {
+    let mut data = MAGIC.to_vec();
+    data.extend(format.to_le_bytes());
+    data.extend((binary.len() as u32).to_le_bytes());
+    data.extend_from_slice(binary);
+    data
+}
+
+// The binary format and the binary of a cache file, None unless the file is laid out as encode_entry
+// writes it.
+fn decode_entry(data: &[u8]) -> Option<(GLenum, &[u8])> {
+    let rest = data.strip_prefix(MAGIC)?;
+    let read_u32 = |offset: usize| Some(u32::from_le_bytes(rest.get(offset..offset + 4)?.try_into().ok()?));
+    let format = read_u32(0)? as GLenum;
+    let length = read_u32(4)? as usize;
+    let binary = rest.get(8..)?;
+    if length == 0 || binary.len() != length {
+        return None;
+    }
+    Some((format, binary))
+}
+
+fn driver_string(name: GLenum) -> String {
+    unsafe {
+        let value = gl::GetString(name);
+        if value.is_null() {
+            return String::new();
+        }
+        CStr::from_ptr(value as *const _).to_string_lossy().to_string()
+    }
+}
+
+// 64 bit FNV-1a. Unlike std's DefaultHasher its output is stable across Rust releases, which matters for file names.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Fnv1a {
+        Fnv1a(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader_include::preprocess_code;
+
+    fn driver(version: &str) -> Vec<String> {
+        vec!["NVIDIA Corporation".to_string(), "GeForce RTX 3070".to_string(), version.to_string()]
+    }
+
+    fn sources(vertex: &str, fragment: &str, defines: &[(&str, &str)]) -> Vec<(ShaderStage, PreprocessedSource)> {
+        [(ShaderStage::Vertex, vertex), (ShaderStage::Fragment, fragment)]
+            .iter()
+            .map(|(stage, code)| {
+                let mut source = preprocess_code(code, "test.glsl", Path::new(".")).unwrap();
+                source.inject_defines(
+                    &defines
+                        .iter()
+                        .map(|(name, value)| (name.to_string(), value.to_string()))
+                        .collect::<Vec<_>>(),
+                );
+                (*stage, source)
+            })
+            .collect()
+    }
+
+    const VERTEX: &str = "#version 330 core\nlayout (location = 0) in vec3 aPos;\nvoid main() { gl_Position = vec4(aPos, 1.0); }\n";
+    const FRAGMENT: &str = "#version 330 core\nout vec4 FragColor;\nvoid main() { FragColor = vec4(1.0); }\n";
+
+    #[test]
+    fn fnv1a() {
+        // the published test vectors, the key must not change between builds
+        let hash = |bytes: &[u8]| {
+            let mut hash = Fnv1a::new();
+            hash.write(bytes);
+            hash.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf29ce484222325);
+        assert_eq!(hash(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(hash(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn key_is_stable() {
+        let key = key_for_driver(&driver("4.6.0 NVIDIA 535.54"), &sources(VERTEX, FRAGMENT, &[("LIGHTS", "4")]));
+        assert_eq!(
+            key,
+            key_for_driver(&driver("4.6.0 NVIDIA 535.54"), &sources(VERTEX, FRAGMENT, &[("LIGHTS", "4")]))
+        );
+        assert_eq!(key.len(), 16);
+        assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn key_changes_with_driver_defines_and_source() {
+        let key = |driver_version: &str, fragment: &str, defines: &[(&str, &str)]| {
+            key_for_driver(&driver(driver_version), &sources(VERTEX, fragment, defines))
+        };
+        let base = key("4.6.0 NVIDIA 535.54", FRAGMENT, &[("LIGHTS", "4")]);
+        assert_ne!(base, key("4.6.0 NVIDIA 545.29", FRAGMENT, &[("LIGHTS", "4")]));
+        assert_ne!(base, key("4.6.0 NVIDIA 535.54", FRAGMENT, &[("LIGHTS", "8")]));
+        assert_ne!(base, key("4.6.0 NVIDIA 535.54", FRAGMENT, &[("LIGHTS", "4"), ("SHADOWS", "1")]));
+        assert_ne!(base, key("4.6.0 NVIDIA 535.54", FRAGMENT, &[]));
+        // one byte of the source
+        let edited = FRAGMENT.replace("vec4(1.0)", "vec4(0.0)");
+        assert_ne!(base, key("4.6.0 NVIDIA 535.54", &edited, &[("LIGHTS", "4")]));
+        // the same code in another stage is another program
+        let swapped = key_for_driver(&driver("4.6.0 NVIDIA 535.54"), &sources(FRAGMENT, VERTEX, &[("LIGHTS", "4")]));
+        assert_ne!(
+            key_for_driver(&driver("4.6.0 NVIDIA 535.54"), &sources(VERTEX, FRAGMENT, &[("LIGHTS", "4")])),
+            swapped
+        );
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let data = encode_entry(0x8e21, &[1, 2, 3, 4, 5]);
+        assert_eq!(&data[..4], b"LOPB");
+        assert_eq!(decode_entry(&data), Some((0x8e21, [1u8, 2, 3, 4, 5].as_slice())));
+    }
+
+    #[test]
+    fn truncated_and_corrupt_entries_are_misses() {
+        let data = encode_entry(0x8e21, &[1, 2, 3, 4, 5]);
+        for length in 0..data.len() {
+            assert_eq!(decode_entry(&data[..length]), None, "cut to {} bytes", length);
+        }
+        let mut longer = data.clone();
+        longer.push(6);
+        assert_eq!(decode_entry(&longer), None);
+        let mut wrong_magic = data.clone();
+        wrong_magic[0] = b'X';
+        assert_eq!(decode_entry(&wrong_magic), None);
+        // the layout before the magic and length were added
+        let mut old = 0x8e21u32.to_le_bytes().to_vec();
+        old.extend([1, 2, 3, 4, 5]);
+        assert_eq!(decode_entry(&old), None);
+        assert_eq!(decode_entry(&encode_entry(0x8e21, &[])), None);
+    }
+
+    #[test]
+    fn corrupt_files_are_removed_without_touching_gl() {
+        let dir = std::env::temp_dir().join(format!("learn_opengl_program_cache-{}", std::process::id()));
+        let cache = ProgramCache::new(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let data = encode_entry(0x8e21, &[1, 2, 3, 4, 5]);
+        fs::write(cache.path("truncated"), &data[..data.len() - 2]).unwrap();
+        fs::write(cache.path("garbage"), b"not a program binary").unwrap();
+
+        // load returns before any gl call, which would fail here without a context
+        assert_eq!(cache.load("truncated"), None);
+        assert_eq!(cache.load("garbage"), None);
+        assert_eq!(cache.load("missing"), None);
+        assert!(!cache.path("truncated").exists());
+        assert!(!cache.path("garbage").exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
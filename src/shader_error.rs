@@ -0,0 +1,239 @@
+#![allow(dead_code)]
+
+use std::error::Error;
+use std::fmt;
+
+use crate::shader_include::{find_line_reference, PreprocessedSource};
+use crate::shader_program::ShaderStage;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+// One message from a driver info log. file and line point at the original source, after include mapping.
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub file: String,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for LogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        };
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, "{}:{}:{}: {}: {}", self.file, line, column, severity, self.message),
+            (Some(line), None) => write!(f, "{}:{}: {}: {}", self.file, line, severity, self.message),
+            _ => write!(f, "{}: {}: {}", self.file, severity, self.message),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ShaderError {
+    // a source or include file could not be read
+    Io { path: String, message: String },
+    // a bad #include directive or an include cycle
+    Include { path: String, message: String },
+    // the stages given to ShaderProgram can not form a program
+    InvalidProgram(String),
+    Compile {
+        stage: ShaderStage,
+        path: String,
+        log: String,
+        entries: Vec<LogEntry>,
+    },
+    Link {
+        paths: Vec<String>,
+        log: String,
+        entries: Vec<LogEntry>,
+    },
+    // a uniform or storage block is missing, or does not match the Rust type bound to it
+    UniformBlock { name: String, message: String },
+}
+
+impl ShaderError {
+    // The parsed log messages of a compile or link failure.
+    pub fn entries(&self) -> &[LogEntry] {
+        match self {
+            ShaderError::Compile { entries, .. } | ShaderError::Link { entries, .. } => entries,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::Io { path, message } => write!(f, "failed to read shader file {}: {}", path, message),
+            ShaderError::Include { path, message } => write!(f, "include error in {}: {}", path, message),
+            ShaderError::InvalidProgram(message) => write!(f, "invalid shader program: {}", message),
+            ShaderError::Compile { stage, path, log, entries } => {
+                writeln!(f, "{} shader compile error in {}", stage.name(), path)?;
+                write_entries(f, log, entries)
+            }
+            ShaderError::Link { paths, log, entries } => {
+                writeln!(f, "shader program link error ({})", paths.join(", "))?;
+                write_entries(f, log, entries)
+            }
+            ShaderError::UniformBlock { name, message } => write!(f, "uniform block {}: {}", name, message),
+        }
+    }
+}
+
+fn write_entries(f: &mut fmt::Formatter<'_>, log: &str, entries: &[LogEntry]) -> fmt::Result {
+    if entries.is_empty() {
+        return write!(f, "{}", log.trim_end());
+    }
+    for entry in entries {
+        writeln!(f, "{}", entry)?;
+    }
+    Ok(())
+}
+
+impl Error for ShaderError {}
+
+// Splits a driver info log into entries. Lines are mapped through source when given, otherwise
+// attributed to default_file. Lines that don't look like messages are appended to the previous entry.
+pub fn parse_log(log: &str, source: Option<&PreprocessedSource>, default_file: &str) -> Vec<LogEntry> {
+    let mut entries: Vec<LogEntry> = vec![];
+
+    for log_line in log.lines() {
+        let trimmed = log_line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let (file, line, column, prefix_severity, rest) = match find_line_reference(log_line) {
+            Some((span, line)) => {
+                let mut rest = &log_line[span.end..];
+                let column = parse_column(&mut rest);
+                let (file, line) = match source.and_then(|source| source.location(line)) {
+                    Some((file, file_line)) => (file.to_string(), file_line),
+                    None => (default_file.to_string(), line),
+                };
+                // "ERROR: 0:12: ..." carries the severity in front of the location
+                let prefix_severity = parse_severity(log_line[..span.start].trim());
+                (file, Some(line), column, prefix_severity, rest)
+            }
+            None => (default_file.to_string(), None, None, None, trimmed),
+        };
+
+        let (severity, message) = match split_severity(rest) {
+            Some((severity, message)) => (severity, message),
+            None if line.is_none() && !entries.is_empty() => {
+                // continuation of a multi line message
+                let last = entries.last_mut().unwrap();
+                last.message.push('\n');
+                last.message.push_str(trimmed);
+                continue;
+            }
+            None => (prefix_severity.unwrap_or(Severity::Error), rest.trim_start_matches([':', ' ']).trim().to_string()),
+        };
+
+        entries.push(LogEntry {
+            file,
+            line,
+            column,
+            severity,
+            message,
+        });
+    }
+    entries
+}
+
+// mesa puts the column in parentheses right after the line: "0:12(5): error: ..."
+fn parse_column(rest: &mut &str) -> Option<u32> {
+    let inner = rest.strip_prefix('(')?;
+    let close = inner.find(')')?;
+    let column = inner[..close].parse().ok()?;
+    *rest = &inner[close + 1..];
+    Some(column)
+}
+
+fn parse_severity(text: &str) -> Option<Severity> {
+    let lower = text.to_ascii_lowercase();
+    if lower.starts_with("error") {
+        Some(Severity::Error)
+    } else if lower.starts_with("warning") {
+        Some(Severity::Warning)
+    } else if lower.starts_with("note") || lower.starts_with("info") {
+        Some(Severity::Note)
+    } else {
+        None
+    }
+}
+
+// Finds "error ...: message" / "warning ...: message" in the remainder of a log line.
+fn split_severity(rest: &str) -> Option<(Severity, String)> {
+    let text = rest.trim_start_matches([':', ' ']);
+    let severity = parse_severity(text)?;
+    // skip the severity word and an optional code such as "C0000"
+    let message = match text.find(':') {
+        Some(colon) => text[colon + 1..].trim(),
+        None => text.split_once(' ').map(|(_, message)| message.trim()).unwrap_or(""),
+    };
+    Some((severity, message.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader_include::preprocess_code;
+    use std::path::Path;
+
+    fn located(entry: &LogEntry) -> (&str, Option<u32>, Option<u32>, Severity, &str) {
+        (entry.file.as_str(), entry.line, entry.column, entry.severity, entry.message.as_str())
+    }
+
+    #[test]
+    fn nvidia_mesa_and_amd_logs() {
+        let log = "0(12) : error C0000: syntax error, unexpected '}' at token \"}\"\n\
+                   0:7(14): warning: `color' used uninitialized\n\
+                   ERROR: 0:23: 'lightPos' : undeclared identifier\n\
+                   WARNING: 0:3: '' : extension not supported: GL_ARB_gpu_shader5\n";
+        let entries = parse_log(log, None, "shader.frag");
+        assert_eq!(entries.len(), 4);
+        // nvidia
+        assert_eq!(
+            located(&entries[0]),
+            ("shader.frag", Some(12), None, Severity::Error, "syntax error, unexpected '}' at token \"}\"")
+        );
+        // mesa
+        assert_eq!(located(&entries[1]), ("shader.frag", Some(7), Some(14), Severity::Warning, "`color' used uninitialized"));
+        // amd, with the severity in front of the location
+        assert_eq!(located(&entries[2]), ("shader.frag", Some(23), None, Severity::Error, "'lightPos' : undeclared identifier"));
+        assert_eq!(entries[3].severity, Severity::Warning);
+        assert_eq!(entries[3].line, Some(3));
+    }
+
+    #[test]
+    fn lines_map_through_the_source() {
+        let mut source = preprocess_code("#version 330 core\nout vec4 FragColor;\nvoid main() {}\n", "pass.frag", Path::new(".")).unwrap();
+        source.inject_defines(&[("LIGHTS".to_string(), "4".to_string())]);
+        let entries = parse_log("0(4) : error C1013: function \"main\" is already defined", Some(&source), "shader.frag");
+        assert_eq!(located(&entries[0]).0, "pass.frag");
+        assert_eq!(entries[0].line, Some(3));
+        assert_eq!(entries[0].to_string(), "pass.frag:3: error: function \"main\" is already defined");
+    }
+
+    #[test]
+    fn unlocated_lines() {
+        let log = "error: linking with uncompiled/unspecialized shader\n    see the compile log\n";
+        let entries = parse_log(log, None, "program");
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            located(&entries[0]),
+            ("program", None, None, Severity::Error, "linking with uncompiled/unspecialized shader\nsee the compile log")
+        );
+    }
+}
@@ -0,0 +1,352 @@
+#![allow(dead_code)]
+
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::assets::asset_path;
+use crate::shader_error::ShaderError;
+use crate::shader_program::read_file;
+
+// Glsl source with its #include "file" directives expanded.
+//
+// Drivers only know about the single string they were given, so each line of the expanded code
+// remembers which file and line it came from. That lets compile errors point back at the original files.
+//
+// A file is only expanded the first time it is included (like #pragma once), and a file that ends up
+// including itself is an error.
+#[derive(Debug, Clone, Default)]
+pub struct PreprocessedSource {
+    pub code: String,
+    // every file that contributed to code, the root file first
+    pub files: Vec<String>,
+    // (index into files, 1-based line in that file) for each line of code
+    line_map: Vec<(usize, u32)>,
+}
+
+impl PreprocessedSource {
+    // Maps a 1-based line of the expanded code to its file and line.
+    pub fn location(&self, line: u32) -> Option<(&str, u32)> {
+        let (file_index, file_line) = self.line_map.get((line as usize).checked_sub(1)?)?;
+        Some((self.files[*file_index].as_str(), *file_line))
+    }
+
+    // Adds "#define name value" lines right after #version (or at the top when there is none).
+    // The injected lines map to the pseudo file "<defines>".
+    pub fn inject_defines(&mut self, defines: &[(String, String)]) {
+        if defines.is_empty() {
+            return;
+        }
+
+        let lines: Vec<&str> = self.code.lines().collect();
+        let insert_at = lines
+            .iter()
+            .position(|line| {
+                line.trim_start()
+                    .strip_prefix('#')
+                    .is_some_and(|rest| rest.trim_start().starts_with("version"))
+            })
+            .map_or(0, |index| index + 1);
+
+        let file_index = self.files.len();
+        self.files.push("<defines>".to_string());
+
+        let mut code = String::new();
+        let mut line_map: Vec<(usize, u32)> = vec![];
+        for (index, line) in lines.iter().enumerate() {
+            if index == insert_at {
+                for (i, (name, value)) in defines.iter().enumerate() {
+                    code.push_str(&format!("#define {} {}\n", name, value));
+                    line_map.push((file_index, i as u32 + 1));
+                }
+            }
+            code.push_str(line);
+            code.push('\n');
+            line_map.push(self.line_map[index]);
+        }
+        if insert_at >= lines.len() {
+            for (i, (name, value)) in defines.iter().enumerate() {
+                code.push_str(&format!("#define {} {}\n", name, value));
+                line_map.push((file_index, i as u32 + 1));
+            }
+        }
+
+        self.code = code;
+        self.line_map = line_map;
+    }
+
+    // Rewrites driver log references like "0(12)" or "0:12" to "file:line".
+    pub fn map_log(&self, log: &str) -> String {
+        let mut mapped: Vec<String> = vec![];
+        for log_line in log.lines() {
+            match find_line_reference(log_line) {
+                Some((span, line)) => match self.location(line) {
+                    Some((file, file_line)) => mapped.push(format!("{}{}:{}{}", &log_line[..span.start], file, file_line, &log_line[span.end..])),
+                    None => mapped.push(log_line.to_string()),
+                },
+                None => mapped.push(log_line.to_string()),
+            }
+        }
+        mapped.join("\n")
+    }
+}
+
+// Expands the includes of a shader file. Include paths are relative to the file containing the directive.
+pub fn preprocess_file(path: &str) -> Result<PreprocessedSource, ShaderError> {
+    let mut preprocessor = Preprocessor::default();
+    preprocessor.include_file(Path::new(path), path)?;
+    Ok(preprocessor.output)
+}
+
+// Expands the includes of glsl held in a string. Include paths are relative to base_dir.
+pub fn preprocess_code(code: &str, name: &str, base_dir: &Path) -> Result<PreprocessedSource, ShaderError> {
+    let mut preprocessor = Preprocessor::default();
+    preprocessor.expand(code, name, base_dir)?;
+    Ok(preprocessor.output)
+}
+
+#[derive(Default)]
+struct Preprocessor {
+    output: PreprocessedSource,
+    // files currently being expanded, for cycle detection
+    stack: Vec<PathBuf>,
+    // files already expanded, later includes of these are skipped
+    included: Vec<PathBuf>,
+}
+
+impl Preprocessor {
+    fn include_file(&mut self, path: &Path, included_from: &str) -> Result<(), ShaderError> {
+        let key = asset_path(path).canonicalize().unwrap_or_else(|_| path.to_path_buf());
+
+        if self.stack.contains(&key) {
+            let mut cycle: Vec<String> = self.stack.iter().map(|p| p.display().to_string()).collect();
+            cycle.push(key.display().to_string());
+            return Err(ShaderError::Include {
+                path: included_from.to_string(),
+                message: format!("include cycle: {}", cycle.join(" -> ")),
+            });
+        }
+        if self.included.contains(&key) {
+            return Ok(());
+        }
+
+        let name = path.to_str().unwrap_or_default().to_string();
+        let code = read_file(&name).map_err(|error| ShaderError::Io {
+            path: name.clone(),
+            message: error.to_string(),
+        })?;
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+
+        self.stack.push(key.clone());
+        self.included.push(key);
+        let result = self.expand(&code, &name, &base_dir);
+        self.stack.pop();
+        result
+    }
+
+    fn expand(&mut self, code: &str, name: &str, base_dir: &Path) -> Result<(), ShaderError> {
+        let file_index = self.output.files.len();
+        self.output.files.push(name.to_string());
+
+        for (i, line) in code.lines().enumerate() {
+            match parse_include(line) {
+                Some(Ok(include)) => self.include_file(&base_dir.join(include), &format!("{}:{}", name, i + 1))?,
+                Some(Err(message)) => {
+                    return Err(ShaderError::Include {
+                        path: format!("{}:{}", name, i + 1),
+                        message,
+                    })
+                }
+                None => {
+                    self.output.code.push_str(line);
+                    self.output.code.push('\n');
+                    self.output.line_map.push((file_index, i as u32 + 1));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Returns the quoted path of an #include line, None for any other line.
+fn parse_include(line: &str) -> Option<Result<&str, String>> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start().strip_prefix("include")?;
+    let rest = rest.trim();
+    let path = rest.strip_prefix('"').and_then(|r| r.strip_suffix('"'));
+    match path {
+        Some(path) if !path.is_empty() => Some(Ok(path)),
+        _ => Some(Err(format!("malformed include directive: {}", line.trim()))),
+    }
+}
+
+// Finds the line number in a driver log line. The common formats are
+//
+//    0(12) : error C0000: ...          (nvidia)
+//    0:12(5): error: ...               (mesa)
+//    ERROR: 0:12: ...                  (amd, apple)
+//
+// where the first number is the source string index. Returns the span of "0(12)" / "0:12" and the line.
+pub(crate) fn find_line_reference(log_line: &str) -> Option<(Range<usize>, u32)> {
+    let trimmed = log_line.trim_start();
+    let mut start = log_line.len() - trimmed.len();
+    for prefix in ["ERROR: ", "WARNING: "] {
+        if log_line[start..].starts_with(prefix) {
+            start += prefix.len();
+        }
+    }
+
+    let rest = &log_line[start..];
+    let string_digits = rest.find(|c: char| !c.is_ascii_digit())?;
+    if string_digits == 0 {
+        return None;
+    }
+
+    let (separator, closing) = match rest[string_digits..].chars().next()? {
+        ':' => (':', None),
+        '(' => ('(', Some(')')),
+        _ => return None,
+    };
+    let line_start = string_digits + separator.len_utf8();
+    let line_digits = rest[line_start..].find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len() - line_start);
+    if line_digits == 0 {
+        return None;
+    }
+    let line: u32 = rest[line_start..line_start + line_digits].parse().ok()?;
+
+    let mut end = line_start + line_digits;
+    if let Some(closing) = closing {
+        if !rest[end..].starts_with(closing) {
+            return None;
+        }
+        end += closing.len_utf8();
+    }
+    Some((start..start + end, line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn defines(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    // writes files under a directory of their own in the temp dir, returning the paths of the files
+    fn write_files(test: &str, files: &[(&str, &str)]) -> Vec<String> {
+        let dir = env::temp_dir().join(format!("learn_opengl_shader_include-{}-{}", test, std::process::id()));
+        files
+            .iter()
+            .map(|(name, code)| {
+                let path = dir.join(name);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(&path, code).unwrap();
+                path.to_str().unwrap().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nested_includes() {
+        let paths = write_files(
+            "nested",
+            &[
+                (
+                    "main.frag",
+                    "#version 330 core\n#include \"lighting.glsl\"\n#include \"common/constants.glsl\"\nout vec4 FragColor;\n",
+                ),
+                ("lighting.glsl", "#include \"common/constants.glsl\"\nvec3 light() { return vec3(PI); }\n"),
+                ("common/constants.glsl", "const float PI = 3.14159;\n"),
+            ],
+        );
+        let source = preprocess_file(&paths[0]).unwrap();
+        // constants.glsl is found next to lighting.glsl, which included it, and is only expanded once
+        assert_eq!(
+            source.code,
+            "#version 330 core\nconst float PI = 3.14159;\nvec3 light() { return vec3(PI); }\nout vec4 FragColor;\n"
+        );
+        assert_eq!(source.files.len(), 3);
+        assert_eq!(source.location(1), Some((paths[0].as_str(), 1)));
+        assert_eq!(source.location(2), Some((source.files[2].as_str(), 1)));
+        assert_eq!(source.location(3), Some((source.files[1].as_str(), 2)));
+        assert_eq!(source.location(4), Some((paths[0].as_str(), 4)));
+        assert_eq!(source.location(5), None);
+        assert!(source.files[1].ends_with("lighting.glsl"));
+        assert!(source.files[2].ends_with("constants.glsl"));
+    }
+
+    #[test]
+    fn include_cycles_are_errors() {
+        let paths = write_files("cycle", &[("a.glsl", "#include \"b.glsl\"\n"), ("b.glsl", "#include \"a.glsl\"\n")]);
+        match preprocess_file(&paths[0]) {
+            Err(ShaderError::Include { path, message }) => {
+                assert!(path.ends_with("b.glsl:1"), "{}", path);
+                assert!(message.starts_with("include cycle"), "{}", message);
+            }
+            result => panic!("expected an include cycle, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn defines_go_after_version() {
+        let code = "// lighting pass\n#version 330 core\nout vec4 FragColor;\n";
+        let mut source = preprocess_code(code, "pass.frag", Path::new(".")).unwrap();
+        source.inject_defines(&defines(&[("LIGHTS", "4"), ("SHADOWS", "1")]));
+        assert_eq!(
+            source.code,
+            "// lighting pass\n#version 330 core\n#define LIGHTS 4\n#define SHADOWS 1\nout vec4 FragColor;\n"
+        );
+        assert_eq!(source.location(2), Some(("pass.frag", 2)));
+        assert_eq!(source.location(3), Some(("<defines>", 1)));
+        assert_eq!(source.location(4), Some(("<defines>", 2)));
+        // the lines after the defines still map to where they were
+        assert_eq!(source.location(5), Some(("pass.frag", 3)));
+    }
+
+    #[test]
+    fn defines_go_first_without_version() {
+        let mut source = preprocess_code("out vec4 FragColor;\n", "pass.frag", Path::new(".")).unwrap();
+        source.inject_defines(&defines(&[("LIGHTS", "4")]));
+        assert_eq!(source.code, "#define LIGHTS 4\nout vec4 FragColor;\n");
+        assert_eq!(source.location(2), Some(("pass.frag", 1)));
+
+        // a #version on the last line still gets the defines after it
+        let mut source = preprocess_code("#version 330 core", "pass.frag", Path::new(".")).unwrap();
+        source.inject_defines(&defines(&[("LIGHTS", "4")]));
+        assert_eq!(source.code, "#version 330 core\n#define LIGHTS 4\n");
+    }
+
+    #[test]
+    fn line_references() {
+        fn reference(log_line: &str) -> Option<(&str, u32)> {
+            find_line_reference(log_line).map(|(span, line)| (&log_line[span], line))
+        }
+        assert_eq!(reference("0(12) : error C0000: syntax error"), Some(("0(12)", 12)));
+        assert_eq!(reference("0:12(5): error: `foo' undeclared"), Some(("0:12", 12)));
+        assert_eq!(reference("ERROR: 0:7: 'foo' : undeclared identifier"), Some(("0:7", 7)));
+        assert_eq!(reference("WARNING: 0:3: extension not supported"), Some(("0:3", 3)));
+        assert_eq!(reference("error: linking failed"), None);
+        assert_eq!(reference("0(12 : error"), None);
+    }
+
+    #[test]
+    fn logs_map_to_files() {
+        let paths = write_files(
+            "log",
+            &[
+                ("main.frag", "#version 330 core\n#include \"util.glsl\"\nvoid main() {}\n"),
+                ("util.glsl", "float f() {}\n"),
+            ],
+        );
+        let mut source = preprocess_file(&paths[0]).unwrap();
+        source.inject_defines(&defines(&[("LIGHTS", "4")]));
+        let util = source.files[1].clone();
+        assert_eq!(
+            source.map_log("0(3) : error C1110: function \"f\" has no return statement\nERROR: 0:4: oops\n0:9: past the end"),
+            format!(
+                "{}:1 : error C1110: function \"f\" has no return statement\nERROR: {}:3: oops\n0:9: past the end",
+                util, paths[0]
+            )
+        );
+    }
+}
@@ -4,77 +4,27 @@
 #![allow(unused_assignments)]
 
 use glad_gl::gl;
-use glad_gl::gl::{GLchar, GLint};
 use glam::*;
 
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::Error;
-use std::path::Path;
-use std::ptr;
-
+use crate::shader_error::ShaderError;
+use crate::shader_program::ShaderProgram;
+use crate::uniforms::*;
 use crate::*;
 
+// The setters look names up in the uniforms reflected when the program is linked, like those of Shader,
+// and warn once about a name that isn't an active uniform of the program or is of another type.
 pub struct Shader_M {
     pub id: ShaderId,
+    uniforms: UniformCache,
 }
 
 impl Shader_M {
-    pub fn new(vertexPath: &str, fragmentPath: &str) -> Result<Self, String> {
-        let mut shader = Shader_M { id: 0 };
-        let mut vertexCode: String = Default::default();
-        let mut fragmentCode: String = Default::default();
-
-        match read_file(vertexPath) {
-            Ok(content) => vertexCode = content,
-            Err(error) => return Err(error.to_string()),
-        }
-
-        match read_file(fragmentPath) {
-            Ok(content) => fragmentCode = content,
-            Err(error) => return Err(error.to_string()),
-        }
-
-        unsafe {
-            // vertex shader
-            let vertexShader = gl::CreateShader(gl::VERTEX_SHADER);
-            let fragmentShader = gl::CreateShader(gl::FRAGMENT_SHADER);
-            shader.id = gl::CreateProgram();
-
-            let c_string = c_string!(vertexCode);
-            gl::ShaderSource(vertexShader, 1, &c_string.as_ptr(), ptr::null());
-            gl::CompileShader(vertexShader);
-
-            match checkCompileErrors(vertexShader, "VERTEX") {
-                Ok(_) => {}
-                Err(error) => return Err(error),
-            }
-
-            let c_string = c_string!(fragmentCode);
-            gl::ShaderSource(fragmentShader, 1, &c_string.as_ptr(), ptr::null());
-            gl::CompileShader(fragmentShader);
-
-            match checkCompileErrors(fragmentShader, "FRAGMENT") {
-                Ok(_) => {}
-                Err(error) => return Err(error),
-            }
-
-            // link the first program object
-            gl::AttachShader(shader.id, vertexShader);
-            gl::AttachShader(shader.id, fragmentShader);
-            gl::LinkProgram(shader.id);
-
-            match checkCompileErrors(shader.id, "PROGRAM") {
-                Ok(_) => {}
-                Err(error) => return Err(error),
-            }
-
-            // Now that the shader programs have been built we can free up memory by deleting the shaders.
-            gl::DeleteShader(vertexShader);
-            gl::DeleteShader(fragmentShader);
-        }
-
-        Ok(shader)
+    pub fn new(vertexPath: &str, fragmentPath: &str) -> Result<Self, ShaderError> {
+        let shader = ShaderProgram::new().vertex_file(vertexPath).fragment_file(fragmentPath).build()?;
+        Ok(Shader_M {
+            id: shader.id,
+            uniforms: UniformCache::reflect(shader.id),
+        })
     }
 
     pub fn use_shader(&self) {
@@ -83,13 +33,16 @@ impl Shader_M {
         }
     }
 
+    fn location(&self, name: &str, setter: &str, accepts: fn(gl::GLenum) -> bool) -> gl::GLint {
+        self.uniforms.location(self.id, name, setter, accepts)
+    }
+
     // utility uniform functions
     // ------------------------------------------------------------------------
     pub fn setBool(&self, name: &str, value: bool) {
         unsafe {
             let v = if value { 1 } else { 0 };
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setBool", accepts_bool);
             gl::Uniform1i(location, v);
         }
     }
@@ -97,8 +50,7 @@ impl Shader_M {
     // ------------------------------------------------------------------------
     pub fn setInt(&self, name: &str, value: i32) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setInt", accepts_int);
             gl::Uniform1i(location, value);
         }
     }
@@ -106,8 +58,7 @@ impl Shader_M {
     // ------------------------------------------------------------------------
     pub fn setFloat(&self, name: &str, value: f32) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setFloat", accepts_float);
             gl::Uniform1f(location, value);
         }
     }
@@ -115,8 +66,7 @@ impl Shader_M {
     // ------------------------------------------------------------------------
     pub fn setVec2(&self, name: &str, value: &Vec2) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setVec2", accepts_vec2);
             gl::Uniform2fv(location, 1, value.to_array().as_ptr());
         }
     }
@@ -124,8 +74,7 @@ impl Shader_M {
     // ------------------------------------------------------------------------
     pub fn setVec2_xy(&self, name: &str, x: f32, y: f32) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setVec2_xy", accepts_vec2);
             gl::Uniform2f(location, x, y);
         }
     }
@@ -133,8 +82,7 @@ impl Shader_M {
     // ------------------------------------------------------------------------
     pub fn setVec3(&self, name: &str, value: &Vec3) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setVec3", accepts_vec3);
             gl::Uniform3fv(location, 1, value.to_array().as_ptr());
         }
     }
@@ -142,8 +90,7 @@ impl Shader_M {
     // ------------------------------------------------------------------------
     pub fn setVec3_xyz(&self, name: &str, x: f32, y: f32, z: f32) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setVec3_xyz", accepts_vec3);
             gl::Uniform3f(location, x, y, z);
         }
     }
@@ -151,8 +98,7 @@ impl Shader_M {
     // ------------------------------------------------------------------------
     pub fn setVec4(&self, name: &str, value: &Vec4) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setVec4", accepts_vec4);
             gl::Uniform4fv(location, 1, value.to_array().as_ptr());
         }
     }
@@ -160,8 +106,7 @@ impl Shader_M {
     // ------------------------------------------------------------------------
     pub fn setVec4_xyzw(&self, name: &str, x: f32, y: f32, z: f32, w: f32) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setVec4_xyzw", accepts_vec4);
             gl::Uniform4f(location, x, y, z, w);
         }
     }
@@ -169,8 +114,7 @@ impl Shader_M {
     // ------------------------------------------------------------------------
     pub fn setMat2(&self, name: &str, mat: &Mat2) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setMat2", accepts_mat2);
             gl::UniformMatrix2fv(location, 1, gl::FALSE, mat.to_cols_array().as_ptr());
         }
     }
@@ -178,8 +122,7 @@ impl Shader_M {
     // ------------------------------------------------------------------------
     pub fn setMat3(&self, name: &str, mat: &Mat3) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setMat3", accepts_mat3);
             gl::UniformMatrix3fv(location, 1, gl::FALSE, mat.to_cols_array().as_ptr());
         }
     }
@@ -187,46 +130,8 @@ impl Shader_M {
     // ------------------------------------------------------------------------
     pub fn setMat4(&self, name: &str, matrix: &Mat4) {
         unsafe {
-            let c_string = c_string!(name);
-            let location = gl::GetUniformLocation(self.id, c_string.as_ptr());
+            let location = self.location(name, "setMat4", accepts_mat4);
             gl::UniformMatrix4fv(location, 1, gl::FALSE, matrix.to_cols_array().as_ptr());
         }
     }
 }
-
-fn read_file(filename: &str) -> Result<String, Error> {
-    let mut content: String = Default::default();
-    let mut file = File::open(Path::new(filename))?;
-    file.read_to_string(&mut content)?;
-    Ok(content)
-}
-
-fn checkCompileErrors(shaderId: u32, checkType: &str) -> Result<(), String> {
-    unsafe {
-        let mut status = gl::FALSE as GLint;
-
-        if checkType != "PROGRAM" {
-            gl::GetShaderiv(shaderId, gl::COMPILE_STATUS, &mut status);
-            if status != (gl::TRUE as GLint) {
-                let mut len = 0;
-                gl::GetShaderiv(shaderId, gl::INFO_LOG_LENGTH, &mut len);
-                // Subtract 1 to skip the trailing null character.
-                let mut infoLog = vec![0; len as usize - 1];
-                gl::GetProgramInfoLog(shaderId, 1024, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                return Err(String::from_utf8_lossy(&infoLog).to_string());
-            }
-        } else {
-            gl::GetProgramiv(shaderId, gl::LINK_STATUS, &mut status);
-            if status != (gl::TRUE as GLint) {
-                let mut len = 0;
-                gl::GetProgramiv(shaderId, gl::INFO_LOG_LENGTH, &mut len);
-                // Subtract 1 to skip the trailing null character.
-                let mut infoLog = vec![0; len as usize - 1];
-                gl::GetProgramInfoLog(shaderId, 1024, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                let error_msg = String::from_utf8_lossy(&infoLog).to_string();
-                return Err(error_msg);
-            }
-        }
-    }
-    Ok(())
-}
@@ -0,0 +1,350 @@
+#![allow(dead_code)]
+#![allow(non_snake_case)]
+
+use glad_gl::gl;
+use glad_gl::gl::{GLchar, GLenum, GLint, GLsizei, GLuint};
+
+use std::collections::BTreeMap;
+use std::io::Error;
+use std::path::Path;
+use std::ptr;
+
+use crate::assets::read_asset_to_string;
+use crate::shader::Shader;
+use crate::shader_cache::ProgramCache;
+use crate::shader_error::*;
+use crate::shader_include::*;
+use crate::*;
+
+// The programmable stages of the pipeline, in the order they run.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    TessControl,
+    TessEvaluation,
+    Geometry,
+    Fragment,
+    Compute,
+}
+
+impl ShaderStage {
+    pub fn gl_type(&self) -> GLenum {
+        match self {
+            ShaderStage::Vertex => gl::VERTEX_SHADER,
+            ShaderStage::TessControl => gl::TESS_CONTROL_SHADER,
+            ShaderStage::TessEvaluation => gl::TESS_EVALUATION_SHADER,
+            ShaderStage::Geometry => gl::GEOMETRY_SHADER,
+            ShaderStage::Fragment => gl::FRAGMENT_SHADER,
+            ShaderStage::Compute => gl::COMPUTE_SHADER,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShaderStage::Vertex => "VERTEX",
+            ShaderStage::TessControl => "TESS_CONTROL",
+            ShaderStage::TessEvaluation => "TESS_EVALUATION",
+            ShaderStage::Geometry => "GEOMETRY",
+            ShaderStage::Fragment => "FRAGMENT",
+            ShaderStage::Compute => "COMPUTE",
+        }
+    }
+}
+
+// Where the glsl for a stage comes from.
+#[derive(Debug, Clone)]
+pub enum StageSource {
+    File(String),
+    Code(String),
+}
+
+impl StageSource {
+    // Loads the glsl and expands its #include directives. Includes in code strings are relative to the working directory.
+    fn load(&self) -> Result<PreprocessedSource, ShaderError> {
+        match self {
+            StageSource::File(path) => preprocess_file(path),
+            StageSource::Code(code) => preprocess_code(code, "<string>", Path::new(".")),
+        }
+    }
+}
+
+// Builder for a Shader made from any combination of pipeline stages.
+//
+// example:
+//
+//    let shader = ShaderProgram::new()
+//        .vertex_file("terrain.vert")
+//        .tess_control_file("terrain.tesc")
+//        .tess_evaluation_file("terrain.tese")
+//        .fragment_file("terrain.frag")
+//        .build()
+//        .unwrap();
+//
+#[derive(Debug, Clone, Default)]
+pub struct ShaderProgram {
+    stages: Vec<(ShaderStage, StageSource)>,
+    defines: BTreeMap<String, String>,
+    cache: Option<ProgramCache>,
+}
+
+impl ShaderProgram {
+    pub fn new() -> ShaderProgram {
+        ShaderProgram {
+            stages: vec![],
+            defines: BTreeMap::new(),
+            cache: None,
+        }
+    }
+
+    // Adds "#define name value" to every stage, right after its #version line.
+    pub fn define(mut self, name: &str, value: &str) -> Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    // Adds "#define name 1", which works with both #ifdef and #if.
+    pub fn define_flag(self, name: &str) -> Self {
+        self.define(name, "1")
+    }
+
+    pub fn defines(&self) -> Vec<(String, String)> {
+        self.defines.iter().map(|(name, value)| (name.clone(), value.clone())).collect()
+    }
+
+    // Keeps linked program binaries in dir and reuses them on later runs. See ProgramCache.
+    pub fn cache_dir(mut self, dir: &str) -> Self {
+        self.cache = Some(ProgramCache::new(dir));
+        self
+    }
+
+    // Setting a stage a second time replaces the earlier source.
+    pub fn stage(mut self, stage: ShaderStage, source: StageSource) -> Self {
+        self.stages.retain(|(s, _)| *s != stage);
+        self.stages.push((stage, source));
+        self
+    }
+
+    pub fn stage_file(self, stage: ShaderStage, path: &str) -> Self {
+        self.stage(stage, StageSource::File(path.to_string()))
+    }
+
+    pub fn stage_code(self, stage: ShaderStage, code: &str) -> Self {
+        self.stage(stage, StageSource::Code(code.to_string()))
+    }
+
+    pub fn vertex_file(self, path: &str) -> Self {
+        self.stage_file(ShaderStage::Vertex, path)
+    }
+
+    pub fn vertex_code(self, code: &str) -> Self {
+        self.stage_code(ShaderStage::Vertex, code)
+    }
+
+    pub fn tess_control_file(self, path: &str) -> Self {
+        self.stage_file(ShaderStage::TessControl, path)
+    }
+
+    pub fn tess_control_code(self, code: &str) -> Self {
+        self.stage_code(ShaderStage::TessControl, code)
+    }
+
+    pub fn tess_evaluation_file(self, path: &str) -> Self {
+        self.stage_file(ShaderStage::TessEvaluation, path)
+    }
+
+    pub fn tess_evaluation_code(self, code: &str) -> Self {
+        self.stage_code(ShaderStage::TessEvaluation, code)
+    }
+
+    pub fn geometry_file(self, path: &str) -> Self {
+        self.stage_file(ShaderStage::Geometry, path)
+    }
+
+    pub fn geometry_code(self, code: &str) -> Self {
+        self.stage_code(ShaderStage::Geometry, code)
+    }
+
+    pub fn fragment_file(self, path: &str) -> Self {
+        self.stage_file(ShaderStage::Fragment, path)
+    }
+
+    pub fn fragment_code(self, code: &str) -> Self {
+        self.stage_code(ShaderStage::Fragment, code)
+    }
+
+    pub fn compute_file(self, path: &str) -> Self {
+        self.stage_file(ShaderStage::Compute, path)
+    }
+
+    pub fn compute_code(self, code: &str) -> Self {
+        self.stage_code(ShaderStage::Compute, code)
+    }
+
+    pub fn has_stage(&self, stage: ShaderStage) -> bool {
+        self.stages.iter().any(|(s, _)| *s == stage)
+    }
+
+    // Checks that the stages can be linked into a single program.
+    fn validate(&self) -> Result<(), ShaderError> {
+        if self.stages.is_empty() {
+            return Err(ShaderError::InvalidProgram("shader program has no stages".to_string()));
+        }
+        if self.has_stage(ShaderStage::Compute) {
+            if self.stages.len() > 1 {
+                return Err(ShaderError::InvalidProgram("a compute shader can not be linked with other stages".to_string()));
+            }
+            return Ok(());
+        }
+        if !self.has_stage(ShaderStage::Vertex) {
+            return Err(ShaderError::InvalidProgram("shader program is missing a vertex stage".to_string()));
+        }
+        if self.has_stage(ShaderStage::TessControl) && !self.has_stage(ShaderStage::TessEvaluation) {
+            return Err(ShaderError::InvalidProgram("a tessellation control stage requires a tessellation evaluation stage".to_string()));
+        }
+        Ok(())
+    }
+
+    pub fn build(&self) -> Result<Shader, ShaderError> {
+        let sources = self.preprocess()?;
+
+        let cache = match &self.cache {
+            Some(cache) if ProgramCache::is_supported() => cache,
+            _ => return ShaderProgram::link(&sources),
+        };
+
+        let key = ProgramCache::key(&sources);
+        if let Some(id) = cache.load(&key) {
+            return Ok(Shader::from_id(id));
+        }
+
+        let id = unsafe { link_program(&sources, true)? };
+        cache.store(&key, id);
+        Ok(Shader::from_id(id))
+    }
+
+    // Loads every stage, expands its includes and injects the defines, without touching GL.
+    pub fn preprocess(&self) -> Result<Vec<(ShaderStage, PreprocessedSource)>, ShaderError> {
+        self.validate()?;
+
+        let defines = self.defines();
+        let mut sources: Vec<(ShaderStage, PreprocessedSource)> = vec![];
+        for (stage, source) in self.stages_in_pipeline_order() {
+            let mut source = source.load()?;
+            source.inject_defines(&defines);
+            sources.push((stage, source));
+        }
+        Ok(sources)
+    }
+
+    pub fn link(sources: &[(ShaderStage, PreprocessedSource)]) -> Result<Shader, ShaderError> {
+        let id = unsafe { link_program(sources, false)? };
+        Ok(Shader::from_id(id))
+    }
+
+    // The files given for stages, not including anything they #include.
+    pub fn stage_files(&self) -> Vec<String> {
+        self.stages
+            .iter()
+            .filter_map(|(_, source)| match source {
+                StageSource::File(path) => Some(path.clone()),
+                StageSource::Code(_) => None,
+            })
+            .collect()
+    }
+
+    fn stages_in_pipeline_order(&self) -> Vec<(ShaderStage, &StageSource)> {
+        let mut stages: Vec<(ShaderStage, &StageSource)> = self.stages.iter().map(|(stage, source)| (*stage, source)).collect();
+        stages.sort_by_key(|(stage, _)| *stage as u32);
+        stages
+    }
+}
+
+unsafe fn compile_stage(stage: ShaderStage, source: &PreprocessedSource) -> Result<GLuint, ShaderError> {
+    let shader = gl::CreateShader(stage.gl_type());
+    let c_string = c_string!(source.code.as_str());
+    gl::ShaderSource(shader, 1, &c_string.as_ptr(), ptr::null());
+    gl::CompileShader(shader);
+
+    let mut status = gl::FALSE as GLint;
+    gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+    if status != (gl::TRUE as GLint) {
+        let log = shader_info_log(shader);
+        gl::DeleteShader(shader);
+        let path = source.files.first().cloned().unwrap_or_default();
+        return Err(ShaderError::Compile {
+            stage,
+            entries: parse_log(&log, Some(source), &path),
+            log: source.map_log(&log),
+            path,
+        });
+    }
+    Ok(shader)
+}
+
+// Compiles each stage and links them, cleaning up every GL object on failure.
+// retrievable asks the driver to keep the binary around for glGetProgramBinary.
+unsafe fn link_program(sources: &[(ShaderStage, PreprocessedSource)], retrievable: bool) -> Result<GLuint, ShaderError> {
+    let mut shaders: Vec<GLuint> = vec![];
+
+    for (stage, source) in sources {
+        match compile_stage(*stage, source) {
+            Ok(shader) => shaders.push(shader),
+            Err(error) => {
+                shaders.iter().for_each(|shader| gl::DeleteShader(*shader));
+                return Err(error);
+            }
+        }
+    }
+
+    let program = gl::CreateProgram();
+    shaders.iter().for_each(|shader| gl::AttachShader(program, *shader));
+    if retrievable {
+        gl::ProgramParameteri(program, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as GLint);
+    }
+    gl::LinkProgram(program);
+
+    // delete the shaders as they're linked into our program now and no longer necessary
+    shaders.iter().for_each(|shader| gl::DeleteShader(*shader));
+
+    let mut status = gl::FALSE as GLint;
+    gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
+    if status != (gl::TRUE as GLint) {
+        let log = program_info_log(program);
+        gl::DeleteProgram(program);
+        return Err(ShaderError::Link {
+            paths: sources.iter().filter_map(|(_, source)| source.files.first().cloned()).collect(),
+            entries: parse_log(&log, None, "<program>"),
+            log,
+        });
+    }
+    Ok(program)
+}
+
+// Reads a shader source through the asset locator, so paths are relative to the asset root rather than the working directory.
+pub(crate) fn read_file(filename: &str) -> Result<String, Error> {
+    read_asset_to_string(filename)
+}
+
+unsafe fn shader_info_log(shader: GLuint) -> String {
+    let mut len: GLint = 0;
+    gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
+    if len <= 0 {
+        return String::new();
+    }
+    let mut infoLog = vec![0u8; len as usize];
+    let mut written: GLsizei = 0;
+    gl::GetShaderInfoLog(shader, len, &mut written, infoLog.as_mut_ptr() as *mut GLchar);
+    String::from_utf8_lossy(&infoLog[..written as usize]).to_string()
+}
+
+unsafe fn program_info_log(program: GLuint) -> String {
+    let mut len: GLint = 0;
+    gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
+    if len <= 0 {
+        return String::new();
+    }
+    let mut infoLog = vec![0u8; len as usize];
+    let mut written: GLsizei = 0;
+    gl::GetProgramInfoLog(program, len, &mut written, infoLog.as_mut_ptr() as *mut GLchar);
+    String::from_utf8_lossy(&infoLog[..written as usize]).to_string()
+}
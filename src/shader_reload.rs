@@ -0,0 +1,180 @@
+#![allow(dead_code)]
+
+use glad_gl::gl;
+
+use std::fs;
+use std::ops::Deref;
+use std::time::SystemTime;
+
+use crate::assets::asset_path;
+use crate::shader::Shader;
+use crate::shader_error::ShaderError;
+use crate::shader_program::ShaderProgram;
+
+// A Shader that rebuilds itself when any of its source files, including #included ones, change on disk.
+//
+// Call reload_if_changed() once per frame, before the shader is used. When the new sources compile and
+// link, the program is swapped in place and the old one deleted. When they don't, the last good program
+// stays in use and the error is printed and kept in last_error() until the next successful build.
+//
+// Uniform values live in the program object, so anything set only once at startup has to be set again
+// after a reload.
+//
+// A successful reload also replaces the program object, so shader.id changes and the old id is deleted.
+// Don't copy the id, or a Shader holding it, into anything that outlives a frame; go through the
+// ReloadableShader (it derefs to the current Shader) each time it is used.
+//
+// example:
+//
+//    let mut shader = ReloadableShader::new(ShaderProgram::new().vertex_file("shader.vert").fragment_file("shader.frag")).unwrap();
+//
+//    while !window.should_close() {
+//        shader.reload_if_changed();
+//        shader.use_shader();
+//        ...
+//    }
+//
+pub struct ReloadableShader {
+    program: ShaderProgram,
+    shader: Shader,
+    watched: Vec<WatchedFile>,
+    last_error: Option<ShaderError>,
+}
+
+struct WatchedFile {
+    path: String,
+    modified: Option<SystemTime>,
+}
+
+impl WatchedFile {
+    fn new(path: &str) -> WatchedFile {
+        WatchedFile {
+            path: path.to_string(),
+            modified: modified_time(path),
+        }
+    }
+
+    fn has_changed(&self) -> bool {
+        modified_time(&self.path) != self.modified
+    }
+}
+
+impl ReloadableShader {
+    pub fn new(program: ShaderProgram) -> Result<ReloadableShader, ShaderError> {
+        let sources = program.preprocess()?;
+        let shader = ShaderProgram::link(&sources)?;
+        let files: Vec<String> = sources.iter().flat_map(|(_, source)| source.files.clone()).collect();
+
+        Ok(ReloadableShader {
+            watched: watch(&files),
+            program,
+            shader,
+            last_error: None,
+        })
+    }
+
+    pub fn shader(&self) -> &Shader {
+        &self.shader
+    }
+
+    // The error from the most recent failed reload, cleared by a successful one.
+    pub fn last_error(&self) -> Option<&ShaderError> {
+        self.last_error.as_ref()
+    }
+
+    pub fn watched_files(&self) -> Vec<&str> {
+        self.watched.iter().map(|file| file.path.as_str()).collect()
+    }
+
+    // Returns true when a new program was swapped in.
+    pub fn reload_if_changed(&mut self) -> bool {
+        if !self.watched.iter().any(|file| file.has_changed()) {
+            return false;
+        }
+        self.reload()
+    }
+
+    // Rebuilds from the current sources regardless of modification times. Returns true when a new program was swapped in.
+    pub fn reload(&mut self) -> bool {
+        let sources = match self.program.preprocess() {
+            Ok(sources) => sources,
+            Err(error) => {
+                // keep watching what we had, plus the stage files themselves, so fixing the problem triggers another try
+                let mut files: Vec<String> = self.watched.iter().map(|file| file.path.clone()).collect();
+                files.extend(self.program.stage_files());
+                files.sort();
+                files.dedup();
+                self.watched = watch(&files);
+                self.fail(error);
+                return false;
+            }
+        };
+
+        let files: Vec<String> = sources.iter().flat_map(|(_, source)| source.files.clone()).collect();
+        self.watched = watch(&files);
+
+        match ShaderProgram::link(&sources) {
+            Ok(shader) => {
+                let old = std::mem::replace(&mut self.shader, shader);
+                unsafe {
+                    gl::DeleteProgram(old.id);
+                }
+                self.last_error = None;
+                true
+            }
+            Err(error) => {
+                self.fail(error);
+                false
+            }
+        }
+    }
+
+    fn fail(&mut self, error: ShaderError) {
+        eprintln!("Shader reload failed, keeping previous program:\n{}", error);
+        self.last_error = Some(error);
+    }
+}
+
+impl Deref for ReloadableShader {
+    type Target = Shader;
+
+    fn deref(&self) -> &Shader {
+        &self.shader
+    }
+}
+
+// Skips the pseudo files of inline code and injected defines, which have nothing on disk to stat.
+fn watch(files: &[String]) -> Vec<WatchedFile> {
+    files
+        .iter()
+        .filter(|path| !is_pseudo_file(path))
+        .map(|path| WatchedFile::new(path))
+        .collect()
+}
+
+fn is_pseudo_file(path: &str) -> bool {
+    path.starts_with('<') && path.ends_with('>')
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(asset_path(path)).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pseudo_files_are_not_watched() {
+        let files = ["<string>", "<defines>", "shaders/lighting.frag", "shaders/common/light.glsl"].map(String::from);
+        let watched: Vec<String> = watch(&files).into_iter().map(|file| file.path).collect();
+        assert_eq!(watched, ["shaders/lighting.frag", "shaders/common/light.glsl"]);
+    }
+
+    #[test]
+    fn missing_files_stay_unchanged_until_they_appear() {
+        let file = WatchedFile::new("shaders/does_not_exist.frag");
+        assert_eq!(file.modified, None);
+        assert!(!file.has_changed());
+    }
+}
@@ -4,76 +4,27 @@
 #![allow(unused_assignments)]
 
 use glad_gl::gl;
-use glad_gl::gl::{GLchar, GLint};
 use glam::*;
 
-use std::fs::File;
-use std::io::prelude::*;
-use std::io::Error;
-use std::path::Path;
-use std::ptr;
-
+use crate::shader_error::ShaderError;
+use crate::shader_program::ShaderProgram;
+use crate::uniforms::*;
 use crate::*;
 
+// The setters look names up in the uniforms reflected when the program is linked, like those of Shader,
+// and warn once about a name that isn't an active uniform of the program or is of another type.
 pub struct Shader_S {
     pub id: ShaderId,
+    uniforms: UniformCache,
 }
 
 impl Shader_S {
-    pub fn new(vertexPath: &str, fragmentPath: &str) -> Result<Self, String> {
-        let mut shader = Shader_S { id: 0 };
-        let mut vertexCode: String = Default::default();
-        let mut fragmentCode: String = Default::default();
-
-        match read_file(vertexPath) {
-            Ok(content) => vertexCode = content,
-            Err(error) => return Err(error.to_string()),
-        }
-
-        match read_file(fragmentPath) {
-            Ok(content) => fragmentCode = content,
-            Err(error) => return Err(error.to_string()),
-        }
-
-        unsafe {
-            // vertex shader
-            let vertexShader = gl::CreateShader(gl::VERTEX_SHADER);
-            let fragmentShader = gl::CreateShader(gl::FRAGMENT_SHADER);
-            shader.id = gl::CreateProgram();
-
-            let c_string = c_string!(vertexCode);
-            gl::ShaderSource(vertexShader, 1, &c_string.as_ptr(), ptr::null());
-            gl::CompileShader(vertexShader);
-            match checkCompileErrors(vertexShader, "VERTEX") {
-                Ok(_) => {}
-                Err(error) => return Err(error),
-            }
-
-            let c_string = c_string!(fragmentCode);
-            gl::ShaderSource(fragmentShader, 1, &c_string.as_ptr(), ptr::null());
-            gl::CompileShader(fragmentShader);
-
-            match checkCompileErrors(fragmentShader, "FRAGMENT") {
-                Ok(_) => {}
-                Err(error) => return Err(error),
-            }
-
-            // link the first program object
-            gl::AttachShader(shader.id, vertexShader);
-            gl::AttachShader(shader.id, fragmentShader);
-            gl::LinkProgram(shader.id);
-
-            match checkCompileErrors(shader.id, "PROGRAM") {
-                Ok(_) => {}
-                Err(error) => return Err(error),
-            }
-
-            // Now that the shader programs have been built we can free up memory by deleting the shaders.
-            gl::DeleteShader(vertexShader);
-            gl::DeleteShader(fragmentShader);
-        }
-
-        Ok(shader)
+    pub fn new(vertexPath: &str, fragmentPath: &str) -> Result<Self, ShaderError> {
+        let shader = ShaderProgram::new().vertex_file(vertexPath).fragment_file(fragmentPath).build()?;
+        Ok(Shader_S {
+            id: shader.id,
+            uniforms: UniformCache::reflect(shader.id),
+        })
     }
 
     pub fn use_shader(&self) {
@@ -82,66 +33,33 @@ impl Shader_S {
         }
     }
 
+    fn location(&self, name: &str, setter: &str, accepts: fn(gl::GLenum) -> bool) -> gl::GLint {
+        self.uniforms.location(self.id, name, setter, accepts)
+    }
+
     // utility uniform functions
     // ------------------------------------------------------------------------
     pub fn setBool(&self, name: &str, value: bool) {
         unsafe {
             let v = if value { 1 } else { 0 };
-            let c_string = c_string!(name);
-            gl::Uniform1i(gl::GetUniformLocation(self.id, c_string.as_ptr()), v);
+            let location = self.location(name, "setBool", accepts_bool);
+            gl::Uniform1i(location, v);
         }
     }
 
     // ------------------------------------------------------------------------
     pub fn setInt(&self, name: &str, value: i32) {
         unsafe {
-            let c_string = c_string!(name);
-            gl::Uniform1i(gl::GetUniformLocation(self.id, c_string.as_ptr()), value);
+            let location = self.location(name, "setInt", accepts_int);
+            gl::Uniform1i(location, value);
         }
     }
 
     // ------------------------------------------------------------------------
     pub fn setFloat(&self, name: &str, value: f32) {
         unsafe {
-            let c_string = c_string!(name);
-            gl::Uniform1f(gl::GetUniformLocation(self.id, c_string.as_ptr()), value);
-        }
-    }
-}
-
-fn read_file(filename: &str) -> Result<String, Error> {
-    let mut content: String = Default::default();
-    let mut file = File::open(Path::new(filename))?;
-    file.read_to_string(&mut content)?;
-    Ok(content)
-}
-
-fn checkCompileErrors(shaderId: u32, checkType: &str) -> Result<(), String> {
-    unsafe {
-        let mut status = gl::FALSE as GLint;
-
-        if checkType != "PROGRAM" {
-            gl::GetShaderiv(shaderId, gl::COMPILE_STATUS, &mut status);
-            if status != (gl::TRUE as GLint) {
-                let mut len = 0;
-                gl::GetShaderiv(shaderId, gl::INFO_LOG_LENGTH, &mut len);
-                // Subtract 1 to skip the trailing null character.
-                let mut infoLog = vec![0; len as usize - 1];
-                gl::GetProgramInfoLog(shaderId, 1024, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                return Err(String::from_utf8_lossy(&infoLog).to_string());
-            }
-        } else {
-            gl::GetProgramiv(shaderId, gl::LINK_STATUS, &mut status);
-            if status != (gl::TRUE as GLint) {
-                let mut len = 0;
-                gl::GetProgramiv(shaderId, gl::INFO_LOG_LENGTH, &mut len);
-                // Subtract 1 to skip the trailing null character.
-                let mut infoLog = vec![0; len as usize - 1];
-                gl::GetProgramInfoLog(shaderId, 1024, ptr::null_mut(), infoLog.as_mut_ptr() as *mut GLchar);
-                let error_msg = String::from_utf8_lossy(&infoLog).to_string();
-                return Err(error_msg);
-            }
+            let location = self.location(name, "setFloat", accepts_float);
+            gl::Uniform1f(location, value);
         }
     }
-    Ok(())
 }
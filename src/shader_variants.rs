@@ -0,0 +1,80 @@
+#![allow(dead_code)]
+
+use glad_gl::gl;
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::shader::Shader;
+use crate::shader_error::ShaderError;
+use crate::shader_program::ShaderProgram;
+
+// The permutations of one shader, compiled on first use and kept for the life of the ShaderVariants.
+//
+// Each variant is the base ShaderProgram plus a set of defines, so a toggle like gamma correction or PCF
+// becomes an #ifdef in the glsl and a different program at runtime instead of a branch on a uniform.
+// Defines given to the base program apply to every variant.
+//
+// example:
+//
+//    let mut variants = ShaderVariants::new(ShaderProgram::new().vertex_file("gamma.vert").fragment_file("gamma.frag"));
+//
+//    let shader = if gammaEnabled { variants.get(&["GAMMA"])? } else { variants.get(&[])? };
+//    shader.use_shader();
+//
+pub struct ShaderVariants {
+    program: ShaderProgram,
+    variants: HashMap<BTreeMap<String, String>, Shader>,
+}
+
+impl ShaderVariants {
+    pub fn new(program: ShaderProgram) -> ShaderVariants {
+        ShaderVariants {
+            program,
+            variants: HashMap::new(),
+        }
+    }
+
+    // The variant with each flag defined as 1.
+    pub fn get(&mut self, flags: &[&str]) -> Result<&Shader, ShaderError> {
+        let defines: Vec<(&str, &str)> = flags.iter().map(|flag| (*flag, "1")).collect();
+        self.get_with_values(&defines)
+    }
+
+    // The variant with the given name / value defines. The order of defines does not matter.
+    pub fn get_with_values(&mut self, defines: &[(&str, &str)]) -> Result<&Shader, ShaderError> {
+        let key: BTreeMap<String, String> = defines.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+
+        if !self.variants.contains_key(&key) {
+            let mut program = self.program.clone();
+            for (name, value) in &key {
+                program = program.define(name, value);
+            }
+            let shader = program.build()?;
+            self.variants.insert(key.clone(), shader);
+        }
+        Ok(&self.variants[&key])
+    }
+
+    // Compiles a variant ahead of time so the first frame that needs it doesn't stall.
+    pub fn prepare(&mut self, flags: &[&str]) -> Result<(), ShaderError> {
+        self.get(flags).map(|_| ())
+    }
+
+    pub fn len(&self) -> usize {
+        self.variants.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variants.is_empty()
+    }
+
+    // Deletes every compiled variant. They are rebuilt on next use.
+    pub fn clear(&mut self) {
+        for shader in self.variants.values() {
+            unsafe {
+                gl::DeleteProgram(shader.id);
+            }
+        }
+        self.variants.clear();
+    }
+}
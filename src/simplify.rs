@@ -0,0 +1,561 @@
+#![allow(dead_code)]
+
+use glam::*;
+use ordered_float::OrderedFloat;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::ops::AddAssign;
+
+use crate::mesh::{Mesh, PrimitiveMode};
+use crate::mesh_optimizer::{optimize_vertex_cache, optimize_vertex_fetch};
+use crate::vertex_attributes::VertexLayout;
+
+// Mesh simplification by edge collapse with quadric error metrics (Garland and Heckbert), for building
+// levels of detail. Works on triangle lists in any VertexLayout, using only the vertex positions.
+//
+// Each collapse moves every vertex at one position onto a neighbouring position, so vertices are never
+// changed or added and the simplified indices use the original vertices. Where a position has several
+// vertices, like along a texture seam, each moves to the vertex at the other end of its own edge, and
+// collapses along borders and seams are kept to the border or seam, so their outline stays in place.
+//
+//    let (indices, error) = simplify(&mesh.vertices, &mesh.indices, 0.25, f32::MAX);
+
+// how strongly borders and seams hold their shape, against the area weighted planes of the faces
+const BORDER_WEIGHT: f64 = 10.0;
+
+// Simplifies a triangle list down to about target_ratio of its triangles, stopping early where the
+// next collapse would move the surface further than max_error. Returns the indices of the remaining
+// triangles and the error reached, an estimate of the largest distance from the original surface,
+// both in the units of the positions.
+pub fn simplify<V: VertexLayout>(vertices: &[V], indices: &[u32], target_ratio: f32, max_error: f32) -> (Vec<u32>, f32) {
+    let mut simplifier = Simplifier::new(vertices, indices);
+    let target = (simplifier.triangles.len() as f32 * target_ratio.clamp(0.0, 1.0)).ceil() as usize;
+    let error = simplifier.run(target, max_error);
+    (simplifier.indices(), error)
+}
+
+// A simplified copy of a mesh with the unused vertices dropped, sharing its textures, and the error
+// of the simplification. Meshes that aren't triangle lists are copied as they are.
+pub fn simplify_mesh<V: VertexLayout>(mesh: &Mesh<V>, target_ratio: f32, max_error: f32) -> (Mesh<V>, f32) {
+    if mesh.mode != PrimitiveMode::Triangles {
+        let copy = Mesh::with_usage(mesh.vertices.clone(), mesh.indices.clone(), mesh.textures.clone(), mesh.usage);
+        return (copy.with_mode(mesh.mode), 0.0);
+    }
+    let indices: Vec<u32> = if mesh.is_indexed() {
+        mesh.indices.clone()
+    } else {
+        (0..mesh.vertices.len() as u32).collect()
+    };
+    let (indices, error) = simplify(&mesh.vertices, &indices, target_ratio, max_error);
+    let mut indices = optimize_vertex_cache(&indices, mesh.vertices.len());
+    let vertices = optimize_vertex_fetch(&mesh.vertices, &mut indices);
+    (Mesh::with_usage(vertices, indices, mesh.textures.clone(), mesh.usage), error)
+}
+
+// The squared distance to a set of planes, as the symmetric 4x4 matrix sum of p * p^T for each plane p,
+// with the total weight of the planes to turn the sum into an average.
+#[derive(Debug, Copy, Clone, Default)]
+struct Quadric {
+    // upper triangle of the matrix, row by row
+    m: [f64; 10],
+    weight: f64,
+}
+
+impl Quadric {
+    fn from_plane(normal: DVec3, point: DVec3, weight: f64) -> Quadric {
+        let (a, b, c) = (normal.x, normal.y, normal.z);
+        let d = -normal.dot(point);
+        let m = [a * a, a * b, a * c, a * d, b * b, b * c, b * d, c * c, c * d, d * d].map(|value| value * weight);
+        Quadric { m, weight }
+    }
+
+    // the weighted average squared distance of point to the planes
+    fn error(&self, point: DVec3) -> f64 {
+        if self.weight <= 0.0 {
+            return 0.0;
+        }
+        let m = &self.m;
+        let (x, y, z) = (point.x, point.y, point.z);
+        let error = m[0] * x * x
+            + 2.0 * m[1] * x * y
+            + 2.0 * m[2] * x * z
+            + 2.0 * m[3] * x
+            + m[4] * y * y
+            + 2.0 * m[5] * y * z
+            + 2.0 * m[6] * y
+            + m[7] * z * z
+            + 2.0 * m[8] * z
+            + m[9];
+        error.max(0.0) / self.weight
+    }
+}
+
+impl AddAssign for Quadric {
+    fn add_assign(&mut self, other: Quadric) {
+        for (value, other) in self.m.iter_mut().zip(other.m) {
+            *value += other;
+        }
+        self.weight += other.weight;
+    }
+}
+
+// Cost, the area the two positions stand for, from position, to position, and the versions of the two
+// positions the cost was worked out for. Among collapses that cost the same, as on a flat surface where
+// every collapse is free, the smaller area goes first so the mesh doesn't all collapse into a few positions.
+type QueuedCollapse = (OrderedFloat<f64>, OrderedFloat<f64>, usize, usize, u32, u32);
+
+struct Simplifier {
+    triangles: Vec<[u32; 3]>,
+    alive: Vec<bool>,
+    live_triangles: usize,
+    // the position each vertex is at, positions being vertex positions with the duplicates merged
+    position_of: Vec<usize>,
+    positions: Vec<DVec3>,
+    // the triangles around each position, including ones since collapsed
+    triangles_at: Vec<Vec<usize>>,
+    quadrics: Vec<Quadric>,
+    // the positions joined to each by a border or seam edge
+    border_neighbours: Vec<Vec<usize>>,
+    // changed whenever a position takes part in a collapse
+    versions: Vec<u32>,
+    // positions with a collapse turned down for flipping a triangle
+    rejected: Vec<bool>,
+    // costs below this are rounding error, taken as 0 so the areas decide between them
+    noise: f64,
+}
+
+impl Simplifier {
+    fn new<V: VertexLayout>(vertices: &[V], indices: &[u32]) -> Simplifier {
+        let mut position_ids: HashMap<[u32; 3], usize> = HashMap::new();
+        let mut positions: Vec<DVec3> = vec![];
+        let mut position_of: Vec<usize> = Vec::with_capacity(vertices.len());
+        for vertex in vertices {
+            let position = vertex.position();
+            // +0.0 and -0.0 are the same position
+            let key = (position + Vec3::ZERO).to_array().map(f32::to_bits);
+            let id = *position_ids.entry(key).or_insert_with(|| {
+                positions.push(position.as_dvec3());
+                positions.len() - 1
+            });
+            position_of.push(id);
+        }
+
+        let triangles: Vec<[u32; 3]> = indices.chunks_exact(3).map(|triangle| [triangle[0], triangle[1], triangle[2]]).collect();
+        let mut simplifier = Simplifier {
+            alive: vec![true; triangles.len()],
+            live_triangles: triangles.len(),
+            triangles_at: vec![vec![]; positions.len()],
+            quadrics: vec![Quadric::default(); positions.len()],
+            border_neighbours: vec![vec![]; positions.len()],
+            versions: vec![0; positions.len()],
+            rejected: vec![false; positions.len()],
+            noise: 0.0,
+            triangles,
+            position_of,
+            positions,
+        };
+
+        for (triangle, corners) in simplifier.triangles.iter().enumerate() {
+            let p = corners.map(|index| simplifier.position_of[index as usize]);
+            if p[0] == p[1] || p[1] == p[2] || p[2] == p[0] {
+                simplifier.alive[triangle] = false;
+                simplifier.live_triangles -= 1;
+                continue;
+            }
+            for position in p {
+                simplifier.triangles_at[position].push(triangle);
+            }
+            let points = p.map(|position| simplifier.positions[position]);
+            let normal = (points[1] - points[0]).cross(points[2] - points[0]);
+            let area = normal.length() * 0.5;
+            if area > 0.0 {
+                let quadric = Quadric::from_plane(normal / (area * 2.0), points[0], area);
+                for position in p {
+                    simplifier.quadrics[position] += quadric;
+                }
+            }
+        }
+
+        // planes through the border and seam edges, at right angles to their triangle
+        for (edge, triangle) in simplifier.border_edges() {
+            let corners = simplifier.triangles[triangle].map(|index| simplifier.positions[simplifier.position_of[index as usize]]);
+            let face_normal = (corners[1] - corners[0]).cross(corners[2] - corners[0]);
+            let (a, b) = (simplifier.positions[edge.0], simplifier.positions[edge.1]);
+            let Some(normal) = (b - a).cross(face_normal).try_normalize() else {
+                continue;
+            };
+            let quadric = Quadric::from_plane(normal, a, a.distance_squared(b) * BORDER_WEIGHT);
+            simplifier.quadrics[edge.0] += quadric;
+            simplifier.quadrics[edge.1] += quadric;
+        }
+        let (min, max) = simplifier
+            .positions
+            .iter()
+            .fold((DVec3::splat(f64::MAX), DVec3::splat(f64::MIN)), |(min, max), &position| {
+                (min.min(position), max.max(position))
+            });
+        if min.x <= max.x {
+            simplifier.noise = min.distance_squared(max) * 1e-12;
+        }
+
+        for ((a, b), _) in simplifier.border_edges() {
+            if !simplifier.border_neighbours[a].contains(&b) {
+                simplifier.border_neighbours[a].push(b);
+                simplifier.border_neighbours[b].push(a);
+            }
+        }
+        simplifier
+    }
+
+    // The live edges used by only one triangle between their two vertices, by position, with that triangle.
+    // These are the borders of the mesh and the seams where vertices are split.
+    fn border_edges(&self) -> Vec<((usize, usize), usize)> {
+        let mut edge_triangles: HashMap<(u32, u32), (usize, usize)> = HashMap::new();
+        for (triangle, corners) in self.triangles.iter().enumerate().filter(|(triangle, _)| self.alive[*triangle]) {
+            for i in 0..3 {
+                let (a, b) = (corners[i], corners[(i + 1) % 3]);
+                edge_triangles.entry((a.min(b), a.max(b))).or_insert((0, triangle)).0 += 1;
+            }
+        }
+        edge_triangles
+            .into_iter()
+            .filter(|(_, (count, _))| *count == 1)
+            .map(|((a, b), (_, triangle))| ((self.position_of[a as usize], self.position_of[b as usize]), triangle))
+            .collect()
+    }
+
+    // Collapses the cheapest edges first until target triangles are left or the next collapse would
+    // cost more than max_error. Queued collapses carry the versions of their two positions when they
+    // were costed, a collapse changes the version of the positions it merges, so outdated ones are skipped.
+    fn run(&mut self, target: usize, max_error: f32) -> f32 {
+        let max_error = max_error as f64 * max_error as f64;
+        let mut error: f64 = 0.0;
+
+        let mut queue: BinaryHeap<Reverse<QueuedCollapse>> = BinaryHeap::new();
+        for position in 0..self.positions.len() {
+            for neighbour in self.neighbours(position) {
+                if neighbour > position {
+                    self.queue_edge(position, neighbour, &mut queue);
+                }
+            }
+        }
+
+        while self.live_triangles > target {
+            let Some(Reverse((OrderedFloat(cost), _, from, to, from_version, to_version))) = queue.pop() else {
+                break;
+            };
+            if self.versions[from] != from_version || self.versions[to] != to_version {
+                continue;
+            }
+            if cost > max_error {
+                break;
+            }
+            let Some(remap) = self.collapse_remap(from, to) else {
+                // tried again once a collapse changes the triangles around from
+                self.rejected[from] = true;
+                continue;
+            };
+            self.collapse(from, to, &remap);
+            error = error.max(cost);
+
+            let neighbours = self.neighbours(to);
+            for &neighbour in &neighbours {
+                self.queue_edge(to, neighbour, &mut queue);
+            }
+            for neighbour in neighbours {
+                if self.rejected[neighbour] {
+                    self.rejected[neighbour] = false;
+                    for other in self.neighbours(neighbour) {
+                        if other != to {
+                            self.queue_edge(neighbour, other, &mut queue);
+                        }
+                    }
+                }
+            }
+        }
+        error.sqrt() as f32
+    }
+
+    // the positions sharing a live triangle with position
+    fn neighbours(&self, position: usize) -> Vec<usize> {
+        let mut neighbours: Vec<usize> = vec![];
+        for &triangle in self.triangles_at[position].iter().filter(|&&triangle| self.alive[triangle]) {
+            for index in self.triangles[triangle] {
+                let other = self.position_of[index as usize];
+                if other != position && !neighbours.contains(&other) {
+                    neighbours.push(other);
+                }
+            }
+        }
+        neighbours
+    }
+
+    // Queues the cheaper direction to collapse the edge between positions a and b in. A position on a
+    // border or seam only moves along it, so the outline keeps its shape.
+    fn queue_edge(&self, a: usize, b: usize, queue: &mut BinaryHeap<Reverse<QueuedCollapse>>) {
+        let is_border = self.border_neighbours[a].contains(&b);
+        let mut quadric = self.quadrics[a];
+        quadric += self.quadrics[b];
+        let cheapest = [(a, b), (b, a)]
+            .into_iter()
+            .filter(|&(from, _)| is_border || self.border_neighbours[from].is_empty())
+            .map(|(from, to)| {
+                let cost = quadric.error(self.positions[to]);
+                (if cost <= self.noise { 0.0 } else { cost }, from, to)
+            })
+            .min_by(|x, y| x.0.total_cmp(&y.0));
+        if let Some((cost, from, to)) = cheapest {
+            queue.push(Reverse((
+                OrderedFloat(cost),
+                OrderedFloat(quadric.weight),
+                from,
+                to,
+                self.versions[from],
+                self.versions[to],
+            )));
+        }
+    }
+
+    // The vertex at to that each vertex at from moves to, or None when the collapse is not allowed:
+    // when a vertex at from has no edge to a vertex at to, or a triangle around from would flip over.
+    fn collapse_remap(&self, from: usize, to: usize) -> Option<Vec<(u32, u32)>> {
+        let mut remap: Vec<(u32, u32)> = vec![];
+        let live = || self.triangles_at[from].iter().copied().filter(|&triangle| self.alive[triangle]);
+
+        for triangle in live() {
+            let corners = self.triangles[triangle];
+            let p = corners.map(|index| self.position_of[index as usize]);
+            if let Some(to_corner) = p.iter().position(|&position| position == to) {
+                let from_corner = p.iter().position(|&position| position == from).unwrap();
+                let (vertex, target) = (corners[from_corner], corners[to_corner]);
+                match remap.iter().find(|(other, _)| *other == vertex) {
+                    // the vertex would have to move to two different vertices
+                    Some(&(_, other_target)) if other_target != target => return None,
+                    Some(_) => {}
+                    None => remap.push((vertex, target)),
+                }
+                continue;
+            }
+
+            let points = p.map(|position| self.positions[position]);
+            let moved = p.map(|position| {
+                if position == from {
+                    self.positions[to]
+                } else {
+                    self.positions[position]
+                }
+            });
+            let before = (points[1] - points[0]).cross(points[2] - points[0]);
+            let after = (moved[1] - moved[0]).cross(moved[2] - moved[0]);
+            // turned by more than about 75 degrees, or collapsed to a sliver
+            if before.dot(after) <= 0.25 * before.length() * after.length() || after.length_squared() <= before.length_squared() * 1e-6 {
+                return None;
+            }
+        }
+
+        // every vertex still in use at from needs a vertex to move to
+        for triangle in live() {
+            for index in self.triangles[triangle] {
+                if self.position_of[index as usize] == from && !remap.iter().any(|(vertex, _)| *vertex == index) {
+                    return None;
+                }
+            }
+        }
+        Some(remap)
+    }
+
+    fn collapse(&mut self, from: usize, to: usize, remap: &[(u32, u32)]) {
+        let around: Vec<usize> = self.triangles_at[from].iter().copied().filter(|&triangle| self.alive[triangle]).collect();
+        for triangle in around {
+            let corners = &mut self.triangles[triangle];
+            let mut has_to = false;
+            for corner in corners.iter_mut() {
+                if let Some(&(_, target)) = remap.iter().find(|(vertex, _)| vertex == corner) {
+                    *corner = target;
+                } else if self.position_of[*corner as usize] == to {
+                    has_to = true;
+                }
+            }
+            if has_to {
+                // the triangles along the collapsed edge
+                self.alive[triangle] = false;
+                self.live_triangles -= 1;
+            } else {
+                self.triangles_at[to].push(triangle);
+            }
+        }
+        self.triangles_at[from].clear();
+        let alive = &self.alive;
+        self.triangles_at[to].retain(|&triangle| alive[triangle]);
+        let quadric = self.quadrics[from];
+        self.quadrics[to] += quadric;
+
+        // the border edges of from now end at to, the one between them is gone
+        for other in std::mem::take(&mut self.border_neighbours[from]) {
+            self.border_neighbours[other].retain(|&position| position != from);
+            if other != to && !self.border_neighbours[to].contains(&other) {
+                self.border_neighbours[to].push(other);
+                self.border_neighbours[other].push(to);
+            }
+        }
+        self.rejected[from] = false;
+        self.versions[from] += 1;
+        self.versions[to] += 1;
+    }
+
+    fn indices(&self) -> Vec<u32> {
+        self.triangles
+            .iter()
+            .zip(&self.alive)
+            .filter(|(_, alive)| **alive)
+            .flat_map(|(corners, _)| corners.iter().copied())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::Vertex;
+    use crate::primitives::vertex;
+
+    const SIZE: u32 = 32;
+
+    // A gently curved size x size grid over x and z from -1 to 1, with a texture seam down x = 0:
+    // the vertices there are doubled, one for the triangles on each side.
+    fn seamed_grid() -> (Vec<Vertex>, Vec<u32>) {
+        let mut vertices: Vec<Vertex> = vec![];
+        let mut ids: HashMap<(u32, u32, bool), u32> = HashMap::new();
+        let mut id = |vertices: &mut Vec<Vertex>, x: u32, z: u32, right: bool| -> u32 {
+            let right = right && x == SIZE / 2;
+            *ids.entry((x, z, right)).or_insert_with(|| {
+                let (s, t) = (x as f32 / SIZE as f32, z as f32 / SIZE as f32);
+                let position = vec3(s * 2.0 - 1.0, 0.1 * (s * 3.0).sin() * (t * 2.0).cos(), t * 2.0 - 1.0);
+                let u = if right { s + 1.0 } else { s };
+                vertices.push(vertex(position, Vec3::Y, vec2(u, t), Vec3::X));
+                vertices.len() as u32 - 1
+            })
+        };
+        let mut indices = vec![];
+        for z in 0..SIZE {
+            for x in 0..SIZE {
+                let right = x >= SIZE / 2;
+                let corners = [(x, z), (x + 1, z), (x + 1, z + 1), (x, z + 1)].map(|(x, z)| id(&mut vertices, x, z, right));
+                // counter clockwise seen from above
+                indices.extend_from_slice(&[corners[0], corners[2], corners[1], corners[0], corners[3], corners[2]]);
+            }
+        }
+        (vertices, indices)
+    }
+
+    // the edges used by only one triangle, by vertex index
+    fn border_edges(indices: &[u32]) -> Vec<(u32, u32)> {
+        let mut counts: HashMap<(u32, u32), usize> = HashMap::new();
+        for triangle in indices.chunks_exact(3) {
+            for i in 0..3 {
+                let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+                *counts.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+        counts.into_iter().filter(|(_, count)| *count == 1).map(|(edge, _)| edge).collect()
+    }
+
+    fn on_outline(vertex: &Vertex) -> bool {
+        let position = vertex.Position;
+        position.x.abs() == 1.0 || position.z.abs() == 1.0 || position.x == 0.0
+    }
+
+    #[test]
+    fn reaches_target_ratio() {
+        let (vertices, indices) = seamed_grid();
+        let triangles = indices.len() / 3;
+        for ratio in [0.5, 0.25, 0.1] {
+            let (simplified, _) = simplify(&vertices, &indices, ratio, f32::MAX);
+            let target = (triangles as f32 * ratio).ceil() as usize;
+            // a collapse takes out one or two triangles
+            let count = simplified.len() / 3;
+            assert!(count <= target && count + 2 >= target, "{} triangles for a target of {}", count, target);
+        }
+        let (simplified, error) = simplify(&vertices, &indices, 1.0, f32::MAX);
+        assert_eq!(simplified, indices);
+        assert_eq!(error, 0.0);
+    }
+
+    #[test]
+    fn stops_at_max_error() {
+        let (vertices, indices) = seamed_grid();
+        let (_, unlimited) = simplify(&vertices, &indices, 0.05, f32::MAX);
+        let (simplified, error) = simplify(&vertices, &indices, 0.05, unlimited / 4.0);
+        assert!(error <= unlimited / 4.0);
+        assert!(simplified.len() / 3 > (indices.len() / 3) / 20);
+    }
+
+    #[test]
+    fn no_triangles_flip() {
+        let (vertices, indices) = seamed_grid();
+        for ratio in [0.5, 0.25, 0.1, 0.05] {
+            let (simplified, _) = simplify(&vertices, &indices, ratio, f32::MAX);
+            for triangle in simplified.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|corner| vertices[triangle[corner] as usize].Position);
+                // every triangle of the grid faces up
+                assert!((b - a).cross(c - a).y > 0.0, "flipped at ratio {}", ratio);
+            }
+        }
+    }
+
+    #[test]
+    fn borders_and_seams_stay_put() {
+        let (vertices, indices) = seamed_grid();
+        let (simplified, _) = simplify(&vertices, &indices, 0.1, f32::MAX);
+
+        // the outline and the seam only lose vertices along themselves
+        for (a, b) in border_edges(&simplified) {
+            let (a, b) = (&vertices[a as usize], &vertices[b as usize]);
+            assert!(on_outline(a) && on_outline(b));
+            let (a, b) = (a.Position, b.Position);
+            let along = (a.x == b.x && (a.x.abs() == 1.0 || a.x == 0.0)) || (a.z == b.z && a.z.abs() == 1.0);
+            assert!(along, "border edge {:?} to {:?} cuts a corner", a, b);
+        }
+
+        // the corners of the grid and the ends of the seam are still there
+        let used: Vec<Vec3> = simplified.iter().map(|&index| vertices[index as usize].Position).collect();
+        for x in [-1.0, 0.0, 1.0] {
+            for z in [-1.0, 1.0] {
+                assert!(
+                    used.iter().any(|position| position.x == x && position.z == z),
+                    "lost the corner at {}, {}",
+                    x,
+                    z
+                );
+            }
+        }
+
+        // triangles keep to their side of the seam, with texture coordinates from that side
+        for triangle in simplified.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| vertices[triangle[corner] as usize]);
+            let sides = [a, b, c].map(|vertex| {
+                let (position, tex_coords) = (vertex.Position, vertex.TexCoords);
+                let seam = position.x == 0.0;
+                (
+                    position.x > 0.0 || seam && tex_coords.x >= 1.0,
+                    position.x < 0.0 || seam && tex_coords.x < 1.0,
+                )
+            });
+            let right = sides.iter().all(|side| side.0);
+            let left = sides.iter().all(|side| side.1);
+            assert!(right || left, "a triangle crosses the seam");
+        }
+    }
+
+    #[test]
+    fn error_grows_with_simplification() {
+        let (vertices, indices) = seamed_grid();
+        let errors: Vec<f32> = [1.0, 0.5, 0.25, 0.1, 0.05]
+            .iter()
+            .map(|&ratio| simplify(&vertices, &indices, ratio, f32::MAX).1)
+            .collect();
+        // LodSelector::select relies on coarser levels never having a smaller error
+        assert!(errors.windows(2).all(|pair| pair[0] <= pair[1]), "{:?}", errors);
+        assert!(errors[4] > 0.0);
+    }
+}
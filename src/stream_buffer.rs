@@ -0,0 +1,179 @@
+#![allow(dead_code)]
+
+use glad_gl::gl;
+use glad_gl::gl::{GLenum, GLsizeiptr, GLsync, GLuint};
+
+use std::mem;
+use std::ptr;
+
+use crate::vertex_attributes::VertexLayout;
+
+// How often a buffer's contents change, the usage hint given to glBufferData.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum BufferUsage {
+    // uploaded once, like a loaded model
+    Static,
+    // changed now and then and drawn many times, like a CPU deformed mesh
+    Dynamic,
+    // rewritten about every frame, like particles or debug lines
+    Stream,
+}
+
+impl BufferUsage {
+    pub fn gl_usage(&self) -> GLenum {
+        match self {
+            BufferUsage::Static => gl::STATIC_DRAW,
+            BufferUsage::Dynamic => gl::DYNAMIC_DRAW,
+            BufferUsage::Stream => gl::STREAM_DRAW,
+        }
+    }
+}
+
+// A persistently mapped ring buffer for data written by the CPU every frame.
+//
+// The buffer is split into one region per frame in flight. Each frame writes into its own region
+// while the GPU may still be reading the regions of the frames before it, and end_frame waits on a
+// fence before a region is handed out again, so there is no orphaning or implicit synchronization.
+//
+// example, streaming debug lines:
+//
+//    let mut lines: StreamBuffer<LineVertex> = StreamBuffer::new(gl::ARRAY_BUFFER, 10_000, 3);
+//    gl::BindVertexArray(linesVAO);
+//    lines.bind_attributes();
+//    ...
+//    // each frame
+//    let first = lines.write(&lineVertices);
+//    gl::BindVertexArray(linesVAO);
+//    gl::DrawArrays(gl::LINES, first as GLint, lineVertices.len() as GLsizei);
+//    lines.end_frame();
+//
+pub struct StreamBuffer<T: Copy> {
+    pub id: GLuint,
+    pub target: GLenum,
+    // elements per region
+    capacity: usize,
+    region: usize,
+    // elements written to the current region
+    cursor: usize,
+    mapped: *mut T,
+    fences: Vec<GLsync>,
+}
+
+impl<T: Copy> StreamBuffer<T> {
+    // A buffer for target, like gl::ARRAY_BUFFER, with room for capacity elements in each of
+    // frames regions. Three frames is enough to never wait on a driver that queues two frames.
+    // glBufferStorage fails with GL_INVALID_VALUE for a size of 0, so a capacity of 0 still gets room for one element.
+    pub fn new(target: GLenum, capacity: usize, frames: usize) -> StreamBuffer<T> {
+        assert!(mem::size_of::<T>() > 0, "StreamBuffer elements can't be zero sized");
+        let capacity = capacity.max(1);
+        let frames = frames.max(1);
+        let size = (capacity * frames * mem::size_of::<T>()) as GLsizeiptr;
+        let flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+
+        let mut id: GLuint = 0;
+        let mapped = unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(target, id);
+            gl::BufferStorage(target, size, ptr::null(), flags);
+            let mapped = gl::MapBufferRange(target, 0, size, flags) as *mut T;
+            gl::BindBuffer(target, 0);
+            mapped
+        };
+        assert!(!mapped.is_null(), "StreamBuffer could not map buffer {}", id);
+
+        StreamBuffer {
+            id,
+            target,
+            capacity,
+            region: 0,
+            cursor: 0,
+            mapped,
+            fences: vec![ptr::null(); frames],
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // room left in this frame's region
+    pub fn remaining(&self) -> usize {
+        self.capacity - self.cursor
+    }
+
+    // Copies data into this frame's region and returns the index of its first element in the whole
+    // buffer, the first vertex for glDrawArrays or base vertex for glDrawElementsBaseVertex.
+    // Panics if the region has no room left for data.
+    pub fn write(&mut self, data: &[T]) -> usize {
+        assert!(
+            data.len() <= self.remaining(),
+            "StreamBuffer write of {} elements with {} left this frame",
+            data.len(),
+            self.remaining()
+        );
+        let first = self.region * self.capacity + self.cursor;
+        unsafe {
+            ptr::copy_nonoverlapping(data.as_ptr(), self.mapped.add(first), data.len());
+        }
+        self.cursor += data.len();
+        first
+    }
+
+    // The byte offset of an element returned by write, for glVertexAttribPointer or glBindBufferRange.
+    pub fn byte_offset(&self, first: usize) -> usize {
+        first * mem::size_of::<T>()
+    }
+
+    // Fences the draws that read this frame's region and moves on to the next region, waiting
+    // for the GPU to finish with it if it has not yet. Call after the last draw of the frame.
+    pub fn end_frame(&mut self) {
+        unsafe {
+            self.fences[self.region] = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+            self.region = (self.region + 1) % self.fences.len();
+            self.cursor = 0;
+
+            let fence = mem::replace(&mut self.fences[self.region], ptr::null());
+            if !fence.is_null() {
+                // flush on the first wait so the fence is sure to be signaled eventually
+                let mut flags = gl::SYNC_FLUSH_COMMANDS_BIT;
+                loop {
+                    let result = gl::ClientWaitSync(fence, flags, 1_000_000);
+                    if result != gl::TIMEOUT_EXPIRED {
+                        break;
+                    }
+                    flags = 0;
+                }
+                gl::DeleteSync(fence);
+            }
+        }
+    }
+}
+
+impl<T: VertexLayout> StreamBuffer<T> {
+    // Points the attributes of T at this buffer, on the vertex array that is bound. Draws use the
+    // index returned by write as their first vertex.
+    pub fn bind_attributes(&self) {
+        unsafe {
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.id);
+        }
+        for attribute in T::attributes() {
+            attribute.enable(T::stride());
+        }
+    }
+}
+
+impl<T: Copy> Drop for StreamBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            for fence in &self.fences {
+                if !fence.is_null() {
+                    gl::DeleteSync(*fence);
+                }
+            }
+            gl::BindBuffer(self.target, self.id);
+            gl::UnmapBuffer(self.target);
+            gl::BindBuffer(self.target, 0);
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
@@ -0,0 +1,284 @@
+#![allow(dead_code)]
+
+use glam::*;
+use std::collections::HashMap;
+
+use crate::mesh::Vertex;
+
+// Normals and tangents computed on the CPU, for meshes that don't come through assimp's
+// GenerateSmoothNormals and CalculateTangentSpace, like procedural or hand built ones.
+//
+// Both work on triangle lists and may add vertices: a vertex shared by faces that end up with
+// different normals or tangents is split, with the indices of the faces updated to match.
+//
+//    let mut geometry = primitives::plane_geometry(10.0, 32);
+//    displace(&mut geometry.vertices);
+//    compute_normals(&mut geometry.vertices, &mut geometry.indices, 60.0);
+//    compute_tangents(&mut geometry.vertices, &mut geometry.indices);
+//    let terrain = geometry.mesh();
+
+// Sets the normal of every vertex from the faces around it.
+//
+// Faces meeting at a vertex position at an angle of less than smoothing_angle degrees are smoothed
+// together, sharper edges stay hard. 0 gives flat shading, 180 smooths everything. Each face counts
+// in proportion to its angle at the vertex, so a vertex normal does not depend on how the faces
+// around it happen to be split into triangles.
+pub fn compute_normals(vertices: &mut Vec<Vertex>, indices: &mut [u32], smoothing_angle: f32) {
+    let triangles = indices.len() / 3;
+    let mut face_normals: Vec<Vec3> = Vec::with_capacity(triangles);
+    // the angle of each corner, in the order of indices
+    let mut corner_angles: Vec<f32> = Vec::with_capacity(triangles * 3);
+
+    for triangle in indices.chunks_exact(3) {
+        let p = [0, 1, 2].map(|i| vertices[triangle[i] as usize].Position);
+        face_normals.push((p[1] - p[0]).cross(p[2] - p[0]).normalize_or_zero());
+        for i in 0..3 {
+            let a = p[(i + 1) % 3] - p[i];
+            let b = p[(i + 2) % 3] - p[i];
+            corner_angles.push(a.angle_between(b).max(0.0));
+        }
+    }
+
+    // the corners at each position, so faces are smoothed across vertices split for texture seams
+    let mut corners_at: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for (corner, &index) in indices.iter().enumerate().take(triangles * 3) {
+        corners_at.entry(position_key(&vertices[index as usize])).or_default().push(corner);
+    }
+
+    let min_cos = smoothing_angle.clamp(0.0, 180.0).to_radians().cos() - 1e-5;
+    let mut normals: Vec<Vec3> = Vec::with_capacity(triangles * 3);
+    for corner in 0..triangles * 3 {
+        let face_normal = face_normals[corner / 3];
+        let key = position_key(&vertices[indices[corner] as usize]);
+        let mut normal = Vec3::ZERO;
+        for &other in &corners_at[&key] {
+            let other_normal = face_normals[other / 3];
+            if other == corner || face_normal.dot(other_normal) >= min_cos {
+                normal += other_normal * corner_angles[other];
+            }
+        }
+        normals.push(normal.try_normalize().unwrap_or(face_normal));
+    }
+
+    set_per_corner(vertices, indices, &normals, |vertex, normal| vertex.Normal = normal);
+}
+
+// Shorthand for compute_normals with flat shading, every face gets its own normal.
+pub fn compute_flat_normals(vertices: &mut Vec<Vertex>, indices: &mut [u32]) {
+    compute_normals(vertices, indices, 0.0);
+}
+
+// Sets tangents and bitangents with the MikkTSpace algorithm, the tangent space Blender, Substance and
+// most other tools bake normal maps in, from the positions, normals and texture coordinates.
+// The bitangent is cross(Normal, Tangent), negated where the texture is mirrored.
+// Returns false, leaving the vertices unchanged, when there are no triangles to work with.
+pub fn compute_tangents(vertices: &mut Vec<Vertex>, indices: &mut [u32]) -> bool {
+    let mut faces = MikkFaces {
+        vertices: vertices.as_slice(),
+        indices: &*indices,
+        tangents: vec![Vec4::ZERO; indices.len() / 3 * 3],
+    };
+    if faces.tangents.is_empty() || !bevy_mikktspace::generate_tangents(&mut faces) {
+        return false;
+    }
+    let tangents = faces.tangents;
+
+    set_per_corner(vertices, indices, &tangents, |vertex, tangent| {
+        let normal = vertex.Normal;
+        vertex.Tangent = tangent.truncate();
+        vertex.Bitangent = normal.cross(tangent.truncate()) * tangent.w;
+    });
+    true
+}
+
+struct MikkFaces<'a> {
+    vertices: &'a [Vertex],
+    indices: &'a [u32],
+    tangents: Vec<Vec4>,
+}
+
+impl MikkFaces<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> &Vertex {
+        &self.vertices[self.indices[face * 3 + vert] as usize]
+    }
+}
+
+impl bevy_mikktspace::Geometry for MikkFaces<'_> {
+    fn num_faces(&self) -> usize {
+        self.tangents.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        let position = self.vertex(face, vert).Position;
+        position.to_array()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        let normal = self.vertex(face, vert).Normal;
+        normal.to_array()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        let tex_coords = self.vertex(face, vert).TexCoords;
+        tex_coords.to_array()
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = Vec4::from_array(tangent);
+    }
+}
+
+fn position_key(vertex: &Vertex) -> [u32; 3] {
+    let position = vertex.Position;
+    // +0.0 and -0.0 are the same position
+    (position + Vec3::ZERO).to_array().map(f32::to_bits)
+}
+
+// Stores a value computed for each corner of each triangle in the vertex the corner uses. The first
+// corner to reach a vertex sets it, later corners with a different value get a copy of the vertex.
+fn set_per_corner<T: Copy + PartialEq>(vertices: &mut Vec<Vertex>, indices: &mut [u32], values: &[T], set: impl Fn(&mut Vertex, T)) {
+    let mut assigned: Vec<Option<T>> = vec![None; vertices.len()];
+    // copies already made of a vertex, to reuse for corners with the same value
+    let mut copies: HashMap<u32, Vec<u32>> = HashMap::new();
+
+    for (corner, &value) in values.iter().enumerate() {
+        let index = indices[corner];
+        match assigned[index as usize] {
+            None => {
+                assigned[index as usize] = Some(value);
+                set(&mut vertices[index as usize], value);
+                continue;
+            }
+            Some(existing) if existing == value => continue,
+            Some(_) => {}
+        }
+
+        let copy = copies
+            .get(&index)
+            .and_then(|copies| copies.iter().copied().find(|&copy| assigned[copy as usize] == Some(value)));
+        indices[corner] = match copy {
+            Some(copy) => copy,
+            None => {
+                let mut vertex = vertices[index as usize];
+                set(&mut vertex, value);
+                vertices.push(vertex);
+                assigned.push(Some(value));
+                let copy = vertices.len() as u32 - 1;
+                copies.entry(index).or_default().push(copy);
+                copy
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::{cube_geometry, vertex};
+
+    // a unit quad in the xy plane facing +z, with no normals or tangents yet
+    fn quad(tex_coords: [Vec2; 4]) -> (Vec<Vertex>, Vec<u32>) {
+        let positions = [vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(1.0, 1.0, 0.0), vec3(0.0, 1.0, 0.0)];
+        let vertices = positions
+            .iter()
+            .zip(tex_coords)
+            .map(|(&position, tex_coords)| vertex(position, Vec3::ZERO, tex_coords, Vec3::ZERO))
+            .collect();
+        (vertices, vec![0, 1, 2, 0, 2, 3])
+    }
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn flat_quad() {
+        let (mut vertices, mut indices) = quad([vec2(0.0, 0.0), vec2(1.0, 0.0), vec2(1.0, 1.0), vec2(0.0, 1.0)]);
+        compute_flat_normals(&mut vertices, &mut indices);
+        assert!(compute_tangents(&mut vertices, &mut indices));
+        // both triangles agree everywhere, so nothing is split
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3]);
+        for vertex in &vertices {
+            assert_near(vertex.Normal, Vec3::Z);
+            // u runs along +x and v along +y
+            assert_near(vertex.Tangent, Vec3::X);
+            assert_near(vertex.Bitangent, Vec3::Y);
+        }
+    }
+
+    #[test]
+    fn mirrored_tex_coords_flip_the_bitangent() {
+        // u runs along -x, v still along +y
+        let (mut vertices, mut indices) = quad([vec2(1.0, 0.0), vec2(0.0, 0.0), vec2(0.0, 1.0), vec2(1.0, 1.0)]);
+        compute_flat_normals(&mut vertices, &mut indices);
+        assert!(compute_tangents(&mut vertices, &mut indices));
+        for vertex in &vertices {
+            let (normal, tangent, bitangent) = (vertex.Normal, vertex.Tangent, vertex.Bitangent);
+            assert_near(tangent, Vec3::NEG_X);
+            assert_near(bitangent, Vec3::Y);
+            // the bitangent is no longer cross(normal, tangent), w is -1
+            assert!(normal.cross(tangent).dot(bitangent) < 0.0);
+        }
+    }
+
+    #[test]
+    fn no_triangles_leave_tangents_unchanged() {
+        let (mut vertices, _) = quad([Vec2::ZERO; 4]);
+        assert!(!compute_tangents(&mut vertices, &mut []));
+        assert!(vertices.iter().all(|vertex| { vertex.Tangent } == Vec3::ZERO));
+    }
+
+    #[test]
+    fn cube_edges_stay_hard() {
+        let cube = cube_geometry();
+
+        // the cube with a single vertex at each corner, shared by the three faces meeting there
+        let mut corners: Vec<Vertex> = vec![];
+        let indices: Vec<u32> = cube
+            .indices
+            .iter()
+            .map(|&index| {
+                let position = cube.vertices[index as usize].Position;
+                let found = corners.iter().position(|corner| {
+                    let corner = corner.Position;
+                    corner == position
+                });
+                found.unwrap_or_else(|| {
+                    corners.push(vertex(position, Vec3::ZERO, Vec2::ZERO, Vec3::ZERO));
+                    corners.len() - 1
+                }) as u32
+            })
+            .collect();
+        assert_eq!(corners.len(), 8);
+
+        // the faces meet at 90 degrees, more than the smoothing angle, so each corner is split in three
+        let (mut vertices, mut indices) = (corners, indices);
+        compute_normals(&mut vertices, &mut indices, 60.0);
+        assert_eq!(vertices.len(), 24);
+        for (corner, &index) in indices.iter().enumerate() {
+            let expected = cube.vertices[cube.indices[corner] as usize].Normal;
+            assert_near(vertices[index as usize].Normal, expected);
+        }
+
+        // smoothing everything averages the three faces at each corner
+        let mut smooth = cube.clone();
+        compute_normals(&mut smooth.vertices, &mut smooth.indices, 180.0);
+        assert_eq!(smooth.vertices.len(), 24);
+        for vertex in &smooth.vertices {
+            assert_near(vertex.Normal, vertex.Position.normalize());
+        }
+
+        // the cube's own vertices per face come out with the face normals they had
+        let mut hard = cube.clone();
+        compute_normals(&mut hard.vertices, &mut hard.indices, 89.0);
+        assert_eq!(hard.vertices.len(), 24);
+        for (vertex, original) in hard.vertices.iter().zip(&cube.vertices) {
+            assert_near(vertex.Normal, original.Normal);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+#![allow(dead_code)]
+
+use glad_gl::gl;
+use glad_gl::gl::{GLenum, GLint, GLuint};
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::mesh::Texture;
+
+// Anything that names a GL texture object, so set_texture takes a raw id or a loaded Texture alike.
+pub trait TextureHandle {
+    fn texture_id(&self) -> GLuint;
+}
+
+impl TextureHandle for GLuint {
+    fn texture_id(&self) -> GLuint {
+        *self
+    }
+}
+
+impl TextureHandle for Texture {
+    fn texture_id(&self) -> GLuint {
+        self.id
+    }
+}
+
+impl<T: TextureHandle + ?Sized> TextureHandle for Rc<T> {
+    fn texture_id(&self) -> GLuint {
+        (**self).texture_id()
+    }
+}
+
+static FRAME: AtomicU64 = AtomicU64::new(0);
+
+// Starts a new frame. Every shader hands out its texture units from 0 again the next time
+// set_texture is called on it. Call once at the top of the render loop.
+pub fn begin_frame() {
+    FRAME.fetch_add(1, Ordering::Relaxed);
+}
+
+// The texture target a sampler type reads from, so a texture is bound to the right target for its uniform.
+pub fn sampler_target(gl_type: GLenum) -> Option<GLenum> {
+    let target = match gl_type {
+        gl::SAMPLER_1D | gl::SAMPLER_1D_SHADOW | gl::INT_SAMPLER_1D | gl::UNSIGNED_INT_SAMPLER_1D => gl::TEXTURE_1D,
+        gl::SAMPLER_2D | gl::SAMPLER_2D_SHADOW | gl::INT_SAMPLER_2D | gl::UNSIGNED_INT_SAMPLER_2D => gl::TEXTURE_2D,
+        gl::SAMPLER_3D | gl::INT_SAMPLER_3D | gl::UNSIGNED_INT_SAMPLER_3D => gl::TEXTURE_3D,
+        gl::SAMPLER_CUBE | gl::SAMPLER_CUBE_SHADOW | gl::INT_SAMPLER_CUBE | gl::UNSIGNED_INT_SAMPLER_CUBE => gl::TEXTURE_CUBE_MAP,
+        gl::SAMPLER_1D_ARRAY | gl::SAMPLER_1D_ARRAY_SHADOW | gl::INT_SAMPLER_1D_ARRAY | gl::UNSIGNED_INT_SAMPLER_1D_ARRAY => {
+            gl::TEXTURE_1D_ARRAY
+        }
+        gl::SAMPLER_2D_ARRAY | gl::SAMPLER_2D_ARRAY_SHADOW | gl::INT_SAMPLER_2D_ARRAY | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY => {
+            gl::TEXTURE_2D_ARRAY
+        }
+        gl::SAMPLER_CUBE_MAP_ARRAY | gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW => gl::TEXTURE_CUBE_MAP_ARRAY,
+        gl::SAMPLER_2D_MULTISAMPLE | gl::INT_SAMPLER_2D_MULTISAMPLE | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE => {
+            gl::TEXTURE_2D_MULTISAMPLE
+        }
+        gl::SAMPLER_2D_MULTISAMPLE_ARRAY | gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY => {
+            gl::TEXTURE_2D_MULTISAMPLE_ARRAY
+        }
+        gl::SAMPLER_2D_RECT | gl::SAMPLER_2D_RECT_SHADOW => gl::TEXTURE_RECTANGLE,
+        gl::SAMPLER_BUFFER | gl::INT_SAMPLER_BUFFER | gl::UNSIGNED_INT_SAMPLER_BUFFER => gl::TEXTURE_BUFFER,
+        _ => return None,
+    };
+    Some(target)
+}
+
+// Hands out texture units to the sampler uniforms of one program.
+//
+// A sampler keeps its unit for the rest of the frame, so setting the same name again, like
+// texture_diffuse1 for each mesh of a model, rebinds the same unit, while a different sampler such
+// as a shadow map always lands on a unit of its own.
+#[derive(Debug)]
+pub struct TextureUnits {
+    max_units: GLuint,
+    frame: Cell<u64>,
+    assigned: RefCell<HashMap<String, GLuint>>,
+}
+
+impl TextureUnits {
+    pub fn new() -> TextureUnits {
+        let mut max_units: GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS, &mut max_units);
+        }
+        TextureUnits {
+            // the spec guarantees at least 80 in GL 4.x
+            max_units: if max_units > 0 { max_units as GLuint } else { 80 },
+            frame: Cell::new(FRAME.load(Ordering::Relaxed)),
+            assigned: RefCell::new(HashMap::new()),
+        }
+    }
+
+    // The unit for a sampler, allocating the next free one on first use this frame.
+    // None when the program has run out of units.
+    pub fn unit_for(&self, name: &str) -> Option<GLuint> {
+        let frame = FRAME.load(Ordering::Relaxed);
+        if self.frame.get() != frame {
+            self.frame.set(frame);
+            self.assigned.borrow_mut().clear();
+        }
+
+        let mut assigned = self.assigned.borrow_mut();
+        if let Some(unit) = assigned.get(name) {
+            return Some(*unit);
+        }
+        let unit = assigned.len() as GLuint;
+        if unit >= self.max_units {
+            return None;
+        }
+        assigned.insert(name.to_string(), unit);
+        Some(unit)
+    }
+
+    pub fn get(&self, name: &str) -> Option<GLuint> {
+        if self.frame.get() != FRAME.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.assigned.borrow().get(name).copied()
+    }
+
+    pub fn reset(&self) {
+        self.assigned.borrow_mut().clear();
+    }
+}
+
+impl Default for TextureUnits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,378 @@
+#![allow(dead_code)]
+
+use glad_gl::gl;
+use glad_gl::gl::{GLsizeiptr, GLuint, GLvoid};
+use glam::*;
+
+use std::marker::PhantomData;
+use std::ptr;
+
+// A type with a std140 layout, the layout glsl uses for uniform blocks by default.
+//
+// The rules that differ from Rust's own layout: vec3 aligns like vec4, every array element and every
+// matrix column starts on a 16 byte boundary, and structs are aligned and padded to 16 bytes.
+// Structs get an implementation from the std140_struct! macro.
+pub trait Std140 {
+    const ALIGN: usize;
+    const SIZE: usize;
+
+    // Writes SIZE bytes at the writer's current offset, which the caller has aligned to ALIGN.
+    fn write_std140(&self, writer: &mut Std140Writer);
+
+    fn to_std140_bytes(&self) -> Vec<u8> {
+        let mut writer = Std140Writer::default();
+        writer.write(self);
+        writer.bytes
+    }
+}
+
+pub const fn round_up(value: usize, alignment: usize) -> usize {
+    value.div_ceil(alignment) * alignment
+}
+
+#[derive(Debug, Default)]
+pub struct Std140Writer {
+    pub bytes: Vec<u8>,
+}
+
+impl Std140Writer {
+    pub fn pad_to(&mut self, offset: usize) {
+        if self.bytes.len() < offset {
+            self.bytes.resize(offset, 0);
+        }
+    }
+
+    pub fn align(&mut self, alignment: usize) {
+        self.pad_to(round_up(self.bytes.len(), alignment));
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    // Aligns then writes a value, padding it out to its full std140 size.
+    pub fn write<T: Std140 + ?Sized>(&mut self, value: &T) {
+        self.align(T::ALIGN);
+        let start = self.bytes.len();
+        value.write_std140(self);
+        self.pad_to(start + T::SIZE);
+    }
+
+    fn write_floats(&mut self, values: &[f32]) {
+        for value in values {
+            self.write_bytes(&value.to_le_bytes());
+        }
+    }
+}
+
+impl Std140 for f32 {
+    const ALIGN: usize = 4;
+    const SIZE: usize = 4;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write_bytes(&self.to_le_bytes());
+    }
+}
+
+impl Std140 for i32 {
+    const ALIGN: usize = 4;
+    const SIZE: usize = 4;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write_bytes(&self.to_le_bytes());
+    }
+}
+
+impl Std140 for u32 {
+    const ALIGN: usize = 4;
+    const SIZE: usize = 4;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write_bytes(&self.to_le_bytes());
+    }
+}
+
+// glsl bools are 4 bytes
+impl Std140 for bool {
+    const ALIGN: usize = 4;
+    const SIZE: usize = 4;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write_bytes(&(*self as u32).to_le_bytes());
+    }
+}
+
+impl Std140 for Vec2 {
+    const ALIGN: usize = 8;
+    const SIZE: usize = 8;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write_floats(&self.to_array());
+    }
+}
+
+// a vec3 takes 12 bytes but aligns to 16, so a following float can fill the gap
+impl Std140 for Vec3 {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 12;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write_floats(&self.to_array());
+    }
+}
+
+impl Std140 for Vec4 {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 16;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write_floats(&self.to_array());
+    }
+}
+
+impl Std140 for IVec4 {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 16;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        for value in self.to_array() {
+            writer.write_bytes(&value.to_le_bytes());
+        }
+    }
+}
+
+// matrices are laid out as arrays of column vectors, each column padded to 16 bytes
+impl Std140 for Mat2 {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 32;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write(&[self.x_axis, self.y_axis]);
+    }
+}
+
+impl Std140 for Mat3 {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 48;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write(&[self.x_axis, self.y_axis, self.z_axis]);
+    }
+}
+
+impl Std140 for Mat4 {
+    const ALIGN: usize = 16;
+    const SIZE: usize = 64;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.write_floats(&self.to_cols_array());
+    }
+}
+
+impl<T: Std140, const N: usize> Std140 for [T; N] {
+    const ALIGN: usize = round_up(T::ALIGN, 16);
+    const SIZE: usize = round_up(T::SIZE, 16) * N;
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        let stride = round_up(T::SIZE, 16);
+        for element in self {
+            let start = writer.bytes.len();
+            writer.write(element);
+            writer.pad_to(start + stride);
+        }
+    }
+}
+
+// Declares a struct and implements Std140 for it, computing each field's offset with the std140 rules.
+//
+// example, matching the Matrices block of 8-advanced_glsl.vert:
+//
+//    std140_struct! {
+//        pub struct Matrices {
+//            pub projection: Mat4,
+//            pub view: Mat4,
+//        }
+//    }
+//
+#[macro_export]
+macro_rules! std140_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident : $field_type:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($field_vis $field: $field_type),*
+        }
+
+        impl $crate::uniform_buffer::Std140 for $name {
+            const ALIGN: usize = {
+                let mut align = 16;
+                $(
+                    if <$field_type as $crate::uniform_buffer::Std140>::ALIGN > align {
+                        align = <$field_type as $crate::uniform_buffer::Std140>::ALIGN;
+                    }
+                )*
+                align
+            };
+            const SIZE: usize = {
+                let mut offset = 0;
+                $(
+                    offset = $crate::uniform_buffer::round_up(offset, <$field_type as $crate::uniform_buffer::Std140>::ALIGN)
+                        + <$field_type as $crate::uniform_buffer::Std140>::SIZE;
+                )*
+                $crate::uniform_buffer::round_up(offset, <Self as $crate::uniform_buffer::Std140>::ALIGN)
+            };
+
+            fn write_std140(&self, writer: &mut $crate::uniform_buffer::Std140Writer) {
+                $( writer.write(&self.$field); )*
+            }
+        }
+    };
+}
+
+// A uniform buffer object holding one T in std140 layout.
+//
+// example:
+//
+//    let matrices: UniformBuffer<Matrices> = UniformBuffer::new();
+//    matrices.bind(0);
+//    shader.bind_uniform_block::<Matrices>("Matrices", 0).unwrap();
+//    ...
+//    matrices.upload(&Matrices { projection, view });
+//
+pub struct UniformBuffer<T: Std140> {
+    pub id: GLuint,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Std140> UniformBuffer<T> {
+    pub fn new() -> UniformBuffer<T> {
+        let mut id: GLuint = 0;
+        unsafe {
+            gl::GenBuffers(1, &mut id);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, id);
+            gl::BufferData(gl::UNIFORM_BUFFER, T::SIZE as GLsizeiptr, ptr::null(), gl::DYNAMIC_DRAW);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+        UniformBuffer { id, _marker: PhantomData }
+    }
+
+    pub fn size(&self) -> usize {
+        T::SIZE
+    }
+
+    pub fn upload(&self, value: &T) {
+        let bytes = value.to_std140_bytes();
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+            gl::BufferSubData(gl::UNIFORM_BUFFER, 0, bytes.len() as GLsizeiptr, bytes.as_ptr() as *const GLvoid);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+    }
+
+    // Attaches the whole buffer to a uniform buffer binding point.
+    pub fn bind(&self, binding: GLuint) {
+        unsafe {
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, self.id);
+        }
+    }
+}
+
+impl<T: Std140> Default for UniformBuffer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Std140> Drop for UniformBuffer<T> {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the Matrices block of 8-advanced_glsl_ubo
+    std140_struct! {
+        struct Matrices {
+            projection: Mat4,
+            view: Mat4,
+        }
+    }
+
+    std140_struct! {
+        struct Light {
+            position: Vec3,
+            intensity: f32,
+            color: Vec3,
+            radius: f32,
+            direction: Vec3,
+        }
+    }
+
+    std140_struct! {
+        struct Spot {
+            cutoff: f32,
+            direction: Vec3,
+            lights: [Light; 2],
+        }
+    }
+
+    fn floats(bytes: &[u8]) -> Vec<f32> {
+        bytes.chunks_exact(4).map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap())).collect()
+    }
+
+    // a light whose fields are numbered from first, so where each lands can be read back
+    fn light(first: f32) -> Light {
+        Light {
+            position: Vec3::splat(first),
+            intensity: first + 1.0,
+            color: Vec3::splat(first + 2.0),
+            radius: first + 3.0,
+            direction: Vec3::splat(first + 4.0),
+        }
+    }
+
+    #[test]
+    fn matrices_block() {
+        assert_eq!(Matrices::SIZE, 128);
+        assert_eq!(Matrices::ALIGN, 16);
+        let projection = Mat4::from_cols_array(&std::array::from_fn(|i| i as f32));
+        let view = Mat4::from_cols_array(&std::array::from_fn(|i| (i + 16) as f32));
+        let bytes = Matrices { projection, view }.to_std140_bytes();
+        assert_eq!(bytes.len(), 128);
+        // projection at 0 and view at 64, each column major
+        assert_eq!(floats(&bytes), (0..32).map(|i| i as f32).collect::<Vec<f32>>());
+    }
+
+    #[test]
+    fn floats_fill_the_gap_after_vec3() {
+        // position 0, intensity 12, color 16, radius 28, direction 32, padded to 48
+        assert_eq!(Light::SIZE, 48);
+        let bytes = light(1.0).to_std140_bytes();
+        assert_eq!(bytes.len(), 48);
+        assert_eq!(floats(&bytes), vec![1.0, 1.0, 1.0, 2.0, 3.0, 3.0, 3.0, 4.0, 5.0, 5.0, 5.0, 0.0]);
+    }
+
+    #[test]
+    fn vec3_after_float_and_arrays_of_structs() {
+        // cutoff 0, direction 16, lights 32 and 80
+        assert_eq!(Spot::SIZE, 128);
+        let spot = Spot {
+            cutoff: 0.5,
+            direction: Vec3::splat(0.25),
+            lights: [light(1.0), light(10.0)],
+        };
+        let values = floats(&spot.to_std140_bytes());
+        assert_eq!(values.len(), 32);
+        assert_eq!(values[..8], [0.5, 0.0, 0.0, 0.0, 0.25, 0.25, 0.25, 0.0]);
+        assert_eq!(values[8..20], floats(&light(1.0).to_std140_bytes())[..]);
+        assert_eq!(values[20..], floats(&light(10.0).to_std140_bytes())[..]);
+    }
+
+    #[test]
+    fn array_elements_start_on_16_bytes() {
+        assert_eq!(<[f32; 3]>::SIZE, 48);
+        assert_eq!(<[Vec3; 2]>::SIZE, 32);
+        let values = floats(&[1.0f32, 2.0, 3.0].to_std140_bytes());
+        assert_eq!(values, vec![1.0, 0.0, 0.0, 0.0, 2.0, 0.0, 0.0, 0.0, 3.0, 0.0, 0.0, 0.0]);
+        assert_eq!(Mat3::SIZE, 48);
+        let values = floats(&Mat3::IDENTITY.to_std140_bytes());
+        assert_eq!(values, vec![1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+    }
+}
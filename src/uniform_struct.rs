@@ -0,0 +1,124 @@
+#![allow(dead_code)]
+
+use glam::*;
+
+use crate::shader::Shader;
+
+// A value that can be set on a single named uniform. Used for the fields of uniform_struct! structs.
+pub trait UniformValue {
+    fn set_uniform(&self, shader: &Shader, name: &str);
+}
+
+impl UniformValue for bool {
+    fn set_uniform(&self, shader: &Shader, name: &str) {
+        shader.set_bool(name, *self);
+    }
+}
+
+impl UniformValue for i32 {
+    fn set_uniform(&self, shader: &Shader, name: &str) {
+        shader.set_int(name, *self);
+    }
+}
+
+impl UniformValue for f32 {
+    fn set_uniform(&self, shader: &Shader, name: &str) {
+        shader.set_float(name, *self);
+    }
+}
+
+impl UniformValue for Vec2 {
+    fn set_uniform(&self, shader: &Shader, name: &str) {
+        shader.set_vec2(name, self);
+    }
+}
+
+impl UniformValue for Vec3 {
+    fn set_uniform(&self, shader: &Shader, name: &str) {
+        shader.set_vec3(name, self);
+    }
+}
+
+impl UniformValue for Vec4 {
+    fn set_uniform(&self, shader: &Shader, name: &str) {
+        shader.set_vec4(name, self);
+    }
+}
+
+impl UniformValue for Mat3 {
+    fn set_uniform(&self, shader: &Shader, name: &str) {
+        shader.set_mat3(name, self);
+    }
+}
+
+impl UniformValue for Mat4 {
+    fn set_uniform(&self, shader: &Shader, name: &str) {
+        shader.set_mat4(name, self);
+    }
+}
+
+// A Rust value that maps onto a glsl struct uniform field by field, e.g. "dirLight.direction".
+// Structs get an implementation from the uniform_struct! macro, slices set "name[i].field" for each element.
+pub trait UniformStruct {
+    fn set_uniforms(&self, shader: &Shader, name: &str);
+}
+
+impl<T: UniformStruct> UniformStruct for [T] {
+    fn set_uniforms(&self, shader: &Shader, name: &str) {
+        // "pointLights" and "pointLights[0]" both name the array
+        let name = name.strip_suffix("[0]").unwrap_or(name);
+        for (i, element) in self.iter().enumerate() {
+            element.set_uniforms(shader, &format!("{}[{}]", name, i));
+        }
+    }
+}
+
+impl<T: UniformStruct, const N: usize> UniformStruct for [T; N] {
+    fn set_uniforms(&self, shader: &Shader, name: &str) {
+        self.as_slice().set_uniforms(shader, name);
+    }
+}
+
+// Declares a struct and implements UniformStruct for it. Field names are the glsl member names,
+// so they keep the glsl casing.
+//
+// example, matching the DirLight struct of 6-multiple_lights.frag:
+//
+//    uniform_struct! {
+//        pub struct DirLight {
+//            pub direction: Vec3,
+//            pub ambient: Vec3,
+//            pub diffuse: Vec3,
+//            pub specular: Vec3,
+//        }
+//    }
+//
+//    shader.set_struct("dirLight", &dirLight);
+//
+#[macro_export]
+macro_rules! uniform_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident : $field_type:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[allow(non_snake_case)]
+        $vis struct $name {
+            $($field_vis $field: $field_type),*
+        }
+
+        impl $crate::uniform_struct::UniformStruct for $name {
+            fn set_uniforms(&self, shader: &$crate::shader::Shader, name: &str) {
+                $(
+                    $crate::uniform_struct::UniformValue::set_uniform(
+                        &self.$field,
+                        shader,
+                        &format!("{}.{}", name, stringify!($field)),
+                    );
+                )*
+            }
+        }
+    };
+}
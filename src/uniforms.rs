@@ -0,0 +1,350 @@
+#![allow(dead_code)]
+#![allow(non_snake_case)]
+
+use glad_gl::gl;
+use glad_gl::gl::{GLchar, GLenum, GLint, GLsizei, GLuint};
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use crate::*;
+
+// An active uniform as reported by glGetActiveUniform after linking.
+// Arrays are reported once with the name of their first element, e.g. "offsets[0]", and size > 1.
+#[derive(Debug, Clone)]
+pub struct UniformInfo {
+    pub name: String,
+    pub location: GLint,
+    pub gl_type: GLenum,
+    pub size: GLint,
+}
+
+impl UniformInfo {
+    pub fn type_name(&self) -> &'static str {
+        gl_type_name(self.gl_type)
+    }
+}
+
+// An active uniform block, with the size in bytes the linker laid it out with.
+#[derive(Debug, Clone)]
+pub struct UniformBlockInfo {
+    pub name: String,
+    pub index: GLuint,
+    pub size: usize,
+}
+
+// Name -> uniform lookup for a linked program. Replaces calling glGetUniformLocation on every set_* call.
+#[derive(Debug, Default)]
+pub struct UniformCache {
+    uniforms: HashMap<String, UniformInfo>,
+    blocks: HashMap<String, UniformBlockInfo>,
+    // array elements other than [0] are looked up on first use
+    elements: RefCell<HashMap<String, UniformInfo>>,
+    // names we have already complained about, so a bad name in the render loop only warns once
+    warned: RefCell<HashSet<String>>,
+}
+
+impl UniformCache {
+    pub fn reflect(program: GLuint) -> UniformCache {
+        let mut uniforms: HashMap<String, UniformInfo> = HashMap::new();
+
+        unsafe {
+            let mut count: GLint = 0;
+            gl::GetProgramiv(program, gl::ACTIVE_UNIFORMS, &mut count);
+            let mut max_length: GLint = 0;
+            gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_MAX_LENGTH, &mut max_length);
+
+            for index in 0..count {
+                let mut name_buffer = vec![0u8; max_length.max(1) as usize];
+                let mut length: GLsizei = 0;
+                let mut size: GLint = 0;
+                let mut gl_type: GLenum = 0;
+                gl::GetActiveUniform(
+                    program,
+                    index as GLuint,
+                    max_length,
+                    &mut length,
+                    &mut size,
+                    &mut gl_type,
+                    name_buffer.as_mut_ptr() as *mut GLchar,
+                );
+                let name = String::from_utf8_lossy(&name_buffer[..length as usize]).to_string();
+
+                let location = gl_get_uniform_location!(program, name.as_str());
+                // members of uniform blocks have no location
+                if location < 0 {
+                    continue;
+                }
+
+                let info = UniformInfo {
+                    name: name.clone(),
+                    location,
+                    gl_type,
+                    size,
+                };
+
+                // glsl lets "offsets" stand for "offsets[0]"
+                if let Some(base) = name.strip_suffix("[0]") {
+                    uniforms.insert(base.to_string(), info.clone());
+                }
+                uniforms.insert(name, info);
+            }
+        }
+
+        UniformCache {
+            uniforms,
+            blocks: reflect_blocks(program),
+            elements: RefCell::new(HashMap::new()),
+            warned: RefCell::new(HashSet::new()),
+        }
+    }
+
+    // All active uniforms sorted by location.
+    pub fn list(&self) -> Vec<UniformInfo> {
+        let mut list: Vec<UniformInfo> = self.uniforms.iter().filter(|(name, info)| **name == info.name).map(|(_, info)| info.clone()).collect();
+        list.sort_by_key(|info| info.location);
+        list
+    }
+
+    // All active uniform blocks sorted by index.
+    pub fn blocks(&self) -> Vec<UniformBlockInfo> {
+        let mut list: Vec<UniformBlockInfo> = self.blocks.values().cloned().collect();
+        list.sort_by_key(|block| block.index);
+        list
+    }
+
+    pub fn block(&self, name: &str) -> Option<UniformBlockInfo> {
+        self.blocks.get(name).cloned()
+    }
+
+    pub fn get(&self, program: GLuint, name: &str) -> Option<UniformInfo> {
+        if let Some(info) = self.uniforms.get(name) {
+            return Some(info.clone());
+        }
+        if let Some(info) = self.elements.borrow().get(name) {
+            return Some(info.clone());
+        }
+        self.lookup_array_element(program, name)
+    }
+
+    // Returns the location for name, or -1 with a one time warning if the program has no such uniform
+    // or the uniform's type does not match what the setter uploads. Setting location -1 is a no-op in GL.
+    pub fn location(&self, program: GLuint, name: &str, setter: &str, accepts: fn(GLenum) -> bool) -> GLint {
+        match self.get(program, name) {
+            Some(info) => {
+                if accepts(info.gl_type) {
+                    info.location
+                } else {
+                    self.warn(name, &format!("{} used on uniform '{}' of type {}", setter, name, info.type_name()));
+                    -1
+                }
+            }
+            None => {
+                self.warn(name, &format!("{} used on unknown uniform '{}'", setter, name));
+                -1
+            }
+        }
+    }
+
+    pub fn warn(&self, name: &str, message: &str) {
+        if self.warned.borrow_mut().insert(name.to_string()) {
+            eprintln!("Shader warning: {}", message);
+        }
+    }
+
+    // Resolves names like "offsets[3]" against the reflected "offsets[0]" entry.
+    fn lookup_array_element(&self, program: GLuint, name: &str) -> Option<UniformInfo> {
+        let open = name.rfind('[')?;
+        let index: GLint = name.strip_suffix(']')?[open + 1..].parse().ok()?;
+        let first = self.uniforms.get(&format!("{}[0]", &name[..open]))?;
+        if index >= first.size {
+            return None;
+        }
+
+        let location = unsafe { gl_get_uniform_location!(program, name) };
+        if location < 0 {
+            return None;
+        }
+        let info = UniformInfo {
+            name: name.to_string(),
+            location,
+            gl_type: first.gl_type,
+            size: first.size - index,
+        };
+        self.elements.borrow_mut().insert(name.to_string(), info.clone());
+        Some(info)
+    }
+}
+
+fn reflect_blocks(program: GLuint) -> HashMap<String, UniformBlockInfo> {
+    let mut blocks: HashMap<String, UniformBlockInfo> = HashMap::new();
+
+    unsafe {
+        let mut count: GLint = 0;
+        gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_BLOCKS, &mut count);
+        let mut max_length: GLint = 0;
+        gl::GetProgramiv(program, gl::ACTIVE_UNIFORM_BLOCK_MAX_NAME_LENGTH, &mut max_length);
+
+        for index in 0..count as GLuint {
+            let mut name_buffer = vec![0u8; max_length.max(1) as usize];
+            let mut length: GLsizei = 0;
+            gl::GetActiveUniformBlockName(program, index, max_length, &mut length, name_buffer.as_mut_ptr() as *mut GLchar);
+            let name = String::from_utf8_lossy(&name_buffer[..length as usize]).to_string();
+
+            let mut size: GLint = 0;
+            gl::GetActiveUniformBlockiv(program, index, gl::UNIFORM_BLOCK_DATA_SIZE, &mut size);
+
+            blocks.insert(
+                name.clone(),
+                UniformBlockInfo {
+                    name,
+                    index,
+                    size: size as usize,
+                },
+            );
+        }
+    }
+    blocks
+}
+
+pub fn is_sampler(gl_type: GLenum) -> bool {
+    matches!(
+        gl_type,
+        gl::SAMPLER_1D
+            | gl::SAMPLER_2D
+            | gl::SAMPLER_3D
+            | gl::SAMPLER_CUBE
+            | gl::SAMPLER_1D_SHADOW
+            | gl::SAMPLER_2D_SHADOW
+            | gl::SAMPLER_1D_ARRAY
+            | gl::SAMPLER_2D_ARRAY
+            | gl::SAMPLER_1D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_ARRAY_SHADOW
+            | gl::SAMPLER_2D_MULTISAMPLE
+            | gl::SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::SAMPLER_CUBE_SHADOW
+            | gl::SAMPLER_CUBE_MAP_ARRAY
+            | gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW
+            | gl::SAMPLER_BUFFER
+            | gl::SAMPLER_2D_RECT
+            | gl::SAMPLER_2D_RECT_SHADOW
+            | gl::INT_SAMPLER_1D
+            | gl::INT_SAMPLER_2D
+            | gl::INT_SAMPLER_3D
+            | gl::INT_SAMPLER_CUBE
+            | gl::INT_SAMPLER_1D_ARRAY
+            | gl::INT_SAMPLER_2D_ARRAY
+            | gl::INT_SAMPLER_2D_MULTISAMPLE
+            | gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::INT_SAMPLER_BUFFER
+            | gl::UNSIGNED_INT_SAMPLER_1D
+            | gl::UNSIGNED_INT_SAMPLER_2D
+            | gl::UNSIGNED_INT_SAMPLER_3D
+            | gl::UNSIGNED_INT_SAMPLER_CUBE
+            | gl::UNSIGNED_INT_SAMPLER_1D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE
+            | gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY
+            | gl::UNSIGNED_INT_SAMPLER_BUFFER
+    )
+}
+
+pub fn is_image(gl_type: GLenum) -> bool {
+    matches!(
+        gl_type,
+        gl::IMAGE_1D
+            | gl::IMAGE_2D
+            | gl::IMAGE_3D
+            | gl::IMAGE_CUBE
+            | gl::IMAGE_1D_ARRAY
+            | gl::IMAGE_2D_ARRAY
+            | gl::IMAGE_CUBE_MAP_ARRAY
+            | gl::IMAGE_BUFFER
+            | gl::INT_IMAGE_2D
+            | gl::INT_IMAGE_3D
+            | gl::UNSIGNED_INT_IMAGE_2D
+            | gl::UNSIGNED_INT_IMAGE_3D
+    )
+}
+
+// bool uniforms may be set with glUniform1i, samplers and images take their unit as an int
+pub fn accepts_int(gl_type: GLenum) -> bool {
+    matches!(gl_type, gl::INT | gl::BOOL) || is_sampler(gl_type) || is_image(gl_type)
+}
+
+pub fn accepts_bool(gl_type: GLenum) -> bool {
+    matches!(gl_type, gl::BOOL | gl::INT)
+}
+
+pub fn accepts_float(gl_type: GLenum) -> bool {
+    gl_type == gl::FLOAT
+}
+
+pub fn accepts_vec2(gl_type: GLenum) -> bool {
+    gl_type == gl::FLOAT_VEC2
+}
+
+pub fn accepts_vec3(gl_type: GLenum) -> bool {
+    gl_type == gl::FLOAT_VEC3
+}
+
+pub fn accepts_vec4(gl_type: GLenum) -> bool {
+    gl_type == gl::FLOAT_VEC4
+}
+
+pub fn accepts_mat2(gl_type: GLenum) -> bool {
+    gl_type == gl::FLOAT_MAT2
+}
+
+pub fn accepts_mat3(gl_type: GLenum) -> bool {
+    gl_type == gl::FLOAT_MAT3
+}
+
+pub fn accepts_mat4(gl_type: GLenum) -> bool {
+    gl_type == gl::FLOAT_MAT4
+}
+
+pub fn gl_type_name(gl_type: GLenum) -> &'static str {
+    match gl_type {
+        gl::FLOAT => "float",
+        gl::FLOAT_VEC2 => "vec2",
+        gl::FLOAT_VEC3 => "vec3",
+        gl::FLOAT_VEC4 => "vec4",
+        gl::INT => "int",
+        gl::INT_VEC2 => "ivec2",
+        gl::INT_VEC3 => "ivec3",
+        gl::INT_VEC4 => "ivec4",
+        gl::UNSIGNED_INT => "uint",
+        gl::UNSIGNED_INT_VEC2 => "uvec2",
+        gl::UNSIGNED_INT_VEC3 => "uvec3",
+        gl::UNSIGNED_INT_VEC4 => "uvec4",
+        gl::BOOL => "bool",
+        gl::BOOL_VEC2 => "bvec2",
+        gl::BOOL_VEC3 => "bvec3",
+        gl::BOOL_VEC4 => "bvec4",
+        gl::FLOAT_MAT2 => "mat2",
+        gl::FLOAT_MAT3 => "mat3",
+        gl::FLOAT_MAT4 => "mat4",
+        gl::FLOAT_MAT2x3 => "mat2x3",
+        gl::FLOAT_MAT2x4 => "mat2x4",
+        gl::FLOAT_MAT3x2 => "mat3x2",
+        gl::FLOAT_MAT3x4 => "mat3x4",
+        gl::FLOAT_MAT4x2 => "mat4x2",
+        gl::FLOAT_MAT4x3 => "mat4x3",
+        gl::SAMPLER_1D => "sampler1D",
+        gl::SAMPLER_2D => "sampler2D",
+        gl::SAMPLER_3D => "sampler3D",
+        gl::SAMPLER_CUBE => "samplerCube",
+        gl::SAMPLER_2D_SHADOW => "sampler2DShadow",
+        gl::SAMPLER_2D_ARRAY => "sampler2DArray",
+        gl::SAMPLER_2D_ARRAY_SHADOW => "sampler2DArrayShadow",
+        gl::SAMPLER_2D_MULTISAMPLE => "sampler2DMS",
+        gl::SAMPLER_CUBE_SHADOW => "samplerCubeShadow",
+        gl::SAMPLER_CUBE_MAP_ARRAY => "samplerCubeArray",
+        gl::IMAGE_2D => "image2D",
+        gl::IMAGE_3D => "image3D",
+        _ if is_sampler(gl_type) => "sampler",
+        _ if is_image(gl_type) => "image",
+        _ => "unknown",
+    }
+}
@@ -0,0 +1,587 @@
+#![allow(dead_code)]
+
+use glad_gl::gl;
+use glad_gl::gl::{GLchar, GLenum, GLint, GLsizei, GLuint};
+
+use glam::*;
+use std::ffi::CString;
+
+use crate::uniforms::gl_type_name;
+
+// An active vertex input as reported by glGetActiveAttrib after linking.
+// Built-ins like gl_VertexID are reported too, with location -1.
+#[derive(Debug, Clone)]
+pub struct AttributeInfo {
+    pub name: String,
+    pub location: GLint,
+    pub gl_type: GLenum,
+    pub size: GLint,
+}
+
+impl AttributeInfo {
+    pub fn type_name(&self) -> &'static str {
+        gl_type_name(self.gl_type)
+    }
+}
+
+// One attribute of a vertex buffer layout, as handed to glVertexAttribPointer or glVertexAttribIPointer.
+// integer attributes are read by the shader as int/uint, the others are converted to float.
+// Matrices take one location per column, starting at location, with components per column.
+#[derive(Debug, Copy, Clone)]
+pub struct VertexAttribute {
+    pub name: &'static str,
+    pub location: GLuint,
+    pub components: GLint,
+    pub columns: GLint,
+    pub data_type: GLenum,
+    pub integer: bool,
+    pub offset: usize,
+}
+
+impl VertexAttribute {
+    // Enables the attribute and points it at the bound array buffer. Call with the VAO bound.
+    pub fn enable(&self, stride: usize) {
+        // every data type used in layouts is 4 bytes
+        let column_size = self.components as usize * 4;
+        for column in 0..self.columns.max(1) as usize {
+            let location = self.location + column as GLuint;
+            let offset = (self.offset + column * column_size) as *const _;
+            unsafe {
+                gl::EnableVertexAttribArray(location);
+                if self.integer {
+                    gl::VertexAttribIPointer(location, self.components, self.data_type, stride as GLsizei, offset);
+                } else {
+                    gl::VertexAttribPointer(location, self.components, self.data_type, gl::FALSE, stride as GLsizei, offset);
+                }
+            }
+        }
+    }
+
+    // Sets how many instances share each value, 0 to advance per vertex. Call with the VAO bound.
+    pub fn set_divisor(&self, divisor: GLuint) {
+        for column in 0..self.columns.max(1) as GLuint {
+            unsafe {
+                gl::VertexAttribDivisor(self.location + column, divisor);
+            }
+        }
+    }
+
+    pub fn covers(&self, location: GLuint) -> bool {
+        location >= self.location && location < self.location + self.columns.max(1) as GLuint
+    }
+
+    // whether the two attributes share a location
+    pub fn overlaps(&self, other: &VertexAttribute) -> bool {
+        (0..self.columns.max(1) as GLuint).any(|column| other.covers(self.location + column))
+    }
+}
+
+// A vertex format for Mesh<V>: the attributes its fields are uploaded as, in location order.
+// Usually implemented with the vertex_layout! macro. Vertices are plain data copied to the GPU as is.
+pub trait VertexLayout: Copy {
+    fn attributes() -> &'static [VertexAttribute];
+
+    fn stride() -> usize {
+        std::mem::size_of::<Self>()
+    }
+
+    // The position of the vertex, for bounding volumes. By default the float attribute named Position,
+    // or else the one at location 0, the aPos of the shaders.
+    fn position(&self) -> Vec3 {
+        let floats = || {
+            Self::attributes()
+                .iter()
+                .filter(|attribute| attribute.data_type == gl::FLOAT && !attribute.integer)
+        };
+        let Some(attribute) = floats()
+            .find(|attribute| attribute.name.eq_ignore_ascii_case("position"))
+            .or_else(|| floats().find(|attribute| attribute.location == 0))
+        else {
+            return Vec3::ZERO;
+        };
+        let mut position = [0.0f32; 3];
+        let components = (attribute.components as usize).min(3);
+        unsafe {
+            let start = (self as *const Self as *const u8).add(attribute.offset) as *const f32;
+            for (i, component) in position.iter_mut().enumerate().take(components) {
+                *component = start.add(i).read_unaligned();
+            }
+        }
+        Vec3::from_array(position)
+    }
+}
+
+// How a field type is handed to the vertex attribute pointer functions.
+pub trait AttributeType {
+    const COMPONENTS: GLint;
+    // locations taken, more than one for matrices
+    const COLUMNS: GLint = 1;
+    const DATA_TYPE: GLenum;
+    // read by the shader as int/uint through glVertexAttribIPointer
+    const INTEGER: bool;
+}
+
+macro_rules! attribute_type {
+    ($($rust_type:ty => $components:expr, $data_type:expr, $integer:expr;)*) => {
+        $(
+            impl AttributeType for $rust_type {
+                const COMPONENTS: GLint = $components;
+                const DATA_TYPE: GLenum = $data_type;
+                const INTEGER: bool = $integer;
+            }
+        )*
+    };
+}
+
+attribute_type! {
+    f32 => 1, gl::FLOAT, false;
+    Vec2 => 2, gl::FLOAT, false;
+    Vec3 => 3, gl::FLOAT, false;
+    Vec4 => 4, gl::FLOAT, false;
+    [f32; 2] => 2, gl::FLOAT, false;
+    [f32; 3] => 3, gl::FLOAT, false;
+    [f32; 4] => 4, gl::FLOAT, false;
+    i32 => 1, gl::INT, true;
+    IVec2 => 2, gl::INT, true;
+    IVec3 => 3, gl::INT, true;
+    IVec4 => 4, gl::INT, true;
+    [i32; 2] => 2, gl::INT, true;
+    [i32; 3] => 3, gl::INT, true;
+    [i32; 4] => 4, gl::INT, true;
+    u32 => 1, gl::UNSIGNED_INT, true;
+    UVec2 => 2, gl::UNSIGNED_INT, true;
+    UVec3 => 3, gl::UNSIGNED_INT, true;
+    UVec4 => 4, gl::UNSIGNED_INT, true;
+    [u32; 2] => 2, gl::UNSIGNED_INT, true;
+    [u32; 3] => 3, gl::UNSIGNED_INT, true;
+    [u32; 4] => 4, gl::UNSIGNED_INT, true;
+}
+
+impl AttributeType for Mat2 {
+    const COMPONENTS: GLint = 2;
+    const COLUMNS: GLint = 2;
+    const DATA_TYPE: GLenum = gl::FLOAT;
+    const INTEGER: bool = false;
+}
+
+impl AttributeType for Mat3 {
+    const COMPONENTS: GLint = 3;
+    const COLUMNS: GLint = 3;
+    const DATA_TYPE: GLenum = gl::FLOAT;
+    const INTEGER: bool = false;
+}
+
+impl AttributeType for Mat4 {
+    const COMPONENTS: GLint = 4;
+    const COLUMNS: GLint = 4;
+    const DATA_TYPE: GLenum = gl::FLOAT;
+    const INTEGER: bool = false;
+}
+
+// The attribute for a field of type T, used by vertex_layout!.
+pub const fn attribute<T: AttributeType>(name: &'static str, location: GLuint, offset: usize) -> VertexAttribute {
+    VertexAttribute {
+        name,
+        location,
+        components: T::COMPONENTS,
+        columns: T::COLUMNS,
+        data_type: T::DATA_TYPE,
+        integer: T::INTEGER,
+        offset,
+    }
+}
+
+// Gives the attributes of a vertex_layout! struct locations 0, 1, 2, ... in field order,
+// skipping the extra locations taken by matrices.
+pub const fn in_field_order<const N: usize>(mut attributes: [VertexAttribute; N]) -> [VertexAttribute; N] {
+    let mut i = 0;
+    let mut location = 0;
+    while i < N {
+        attributes[i].location = location;
+        location += attributes[i].columns as GLuint;
+        i += 1;
+    }
+    attributes
+}
+
+// Declares a #[repr(C)] vertex struct and implements VertexLayout for it, with the fields at
+// locations 0, 1, 2, ... in the order they are declared, with a location per column for matrix
+// fields. Needs #![feature(offset_of)].
+//
+// example, for a shader with layout (location = 0) in vec3 aPos and layout (location = 1) in vec2 aTexCoords:
+//
+//    vertex_layout! {
+//        #[derive(Debug, Copy, Clone, Default)]
+//        pub struct QuadVertex {
+//            pub Position: Vec3,
+//            pub TexCoords: Vec2,
+//        }
+//    }
+//
+//    let quad: Mesh<QuadVertex> = Mesh::new(vertices, indices, vec![]);
+//
+#[macro_export]
+macro_rules! vertex_layout {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident : $field_type:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[allow(non_snake_case)]
+        $vis struct $name {
+            $($field_vis $field: $field_type),*
+        }
+
+        impl $crate::vertex_attributes::VertexLayout for $name {
+            fn attributes() -> &'static [$crate::vertex_attributes::VertexAttribute] {
+                const COUNT: usize = [$(stringify!($field)),*].len();
+                static ATTRIBUTES: [$crate::vertex_attributes::VertexAttribute; COUNT] = $crate::vertex_attributes::in_field_order([
+                    $(
+                        $crate::vertex_attributes::attribute::<$field_type>(
+                            stringify!($field),
+                            0,
+                            std::mem::offset_of!($name, $field),
+                        )
+                    ),*
+                ]);
+                &ATTRIBUTES
+            }
+        }
+    };
+}
+
+pub fn reflect_attributes(program: GLuint) -> Vec<AttributeInfo> {
+    let mut attributes: Vec<AttributeInfo> = vec![];
+
+    unsafe {
+        let mut count: GLint = 0;
+        gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTES, &mut count);
+        let mut max_length: GLint = 0;
+        gl::GetProgramiv(program, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, &mut max_length);
+
+        for index in 0..count {
+            let mut name_buffer = vec![0u8; max_length.max(1) as usize];
+            let mut length: GLsizei = 0;
+            let mut size: GLint = 0;
+            let mut gl_type: GLenum = 0;
+            gl::GetActiveAttrib(
+                program,
+                index as GLuint,
+                max_length,
+                &mut length,
+                &mut size,
+                &mut gl_type,
+                name_buffer.as_mut_ptr() as *mut GLchar,
+            );
+            let name = String::from_utf8_lossy(&name_buffer[..length as usize]).to_string();
+
+            let c_name = CString::new(name.as_str()).unwrap();
+            let location = gl::GetAttribLocation(program, c_name.as_ptr());
+
+            attributes.push(AttributeInfo {
+                name,
+                location,
+                gl_type,
+                size,
+            });
+        }
+    }
+
+    attributes.sort_by_key(|attribute| attribute.location);
+    attributes
+}
+
+// The components per location, how many locations it takes and whether the shader reads it as an integer.
+// Matrices take one location per column, doubles are not handled.
+pub fn attribute_shape(gl_type: GLenum) -> Option<(GLint, GLint, bool)> {
+    let shape = match gl_type {
+        gl::FLOAT => (1, 1, false),
+        gl::FLOAT_VEC2 => (2, 1, false),
+        gl::FLOAT_VEC3 => (3, 1, false),
+        gl::FLOAT_VEC4 => (4, 1, false),
+        gl::INT | gl::UNSIGNED_INT => (1, 1, true),
+        gl::INT_VEC2 | gl::UNSIGNED_INT_VEC2 => (2, 1, true),
+        gl::INT_VEC3 | gl::UNSIGNED_INT_VEC3 => (3, 1, true),
+        gl::INT_VEC4 | gl::UNSIGNED_INT_VEC4 => (4, 1, true),
+        gl::FLOAT_MAT2 => (2, 2, false),
+        gl::FLOAT_MAT3 => (3, 3, false),
+        gl::FLOAT_MAT4 => (4, 4, false),
+        gl::FLOAT_MAT2x3 => (3, 2, false),
+        gl::FLOAT_MAT2x4 => (4, 2, false),
+        gl::FLOAT_MAT3x2 => (2, 3, false),
+        gl::FLOAT_MAT3x4 => (4, 3, false),
+        gl::FLOAT_MAT4x2 => (2, 4, false),
+        gl::FLOAT_MAT4x3 => (3, 4, false),
+        _ => return None,
+    };
+    Some(shape)
+}
+
+// Compares the vertex inputs of a program against a vertex layout and describes every mismatch:
+//   - an input at a location the layout does not provide, which reads a constant (0, 0, 0, 1)
+//   - an int/uint input fed from float data or a float input fed from integer data, which reads garbage
+//   - an input with more components than the layout provides, which are filled in with 0 or 1
+// Providing more components than the shader reads is fine and not reported.
+pub fn check_attributes(attributes: &[AttributeInfo], layout: &[VertexAttribute]) -> Vec<String> {
+    let mut mismatches: Vec<String> = vec![];
+
+    for attribute in attributes {
+        // built-ins like gl_VertexID and gl_InstanceID
+        if attribute.location < 0 {
+            continue;
+        }
+        let Some((components, columns, integer)) = attribute_shape(attribute.gl_type) else {
+            mismatches.push(format!(
+                "attribute '{}' has type {} which vertex layouts can not provide",
+                attribute.name,
+                attribute.type_name()
+            ));
+            continue;
+        };
+
+        let locations = columns * attribute.size.max(1);
+        for offset in 0..locations {
+            let location = (attribute.location + offset) as GLuint;
+            let input = if locations > 1 {
+                format!("'{}' (location {})", attribute.name, location)
+            } else {
+                format!("'{}'", attribute.name)
+            };
+
+            let Some(provided) = layout.iter().find(|provided| provided.covers(location)) else {
+                mismatches.push(format!(
+                    "attribute '{}' at location {} is not provided by the vertex layout",
+                    attribute.name, location
+                ));
+                continue;
+            };
+
+            if integer != provided.integer {
+                mismatches.push(format!(
+                    "attribute {} is {} but the vertex layout provides {} as {}",
+                    input,
+                    attribute.type_name(),
+                    provided.name,
+                    if provided.integer { "integers" } else { "floats" }
+                ));
+            } else if components > provided.components {
+                mismatches.push(format!(
+                    "attribute {} is {} but the vertex layout provides {} with {} component{}",
+                    input,
+                    attribute.type_name(),
+                    provided.name,
+                    provided.components,
+                    if provided.components == 1 { "" } else { "s" }
+                ));
+            }
+        }
+    }
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // an active vertex input as the driver would report it
+    fn input(name: &str, location: GLint, gl_type: GLenum) -> AttributeInfo {
+        AttributeInfo {
+            name: name.to_string(),
+            location,
+            gl_type,
+            size: 1,
+        }
+    }
+
+    #[test]
+    fn bone_ids_from_float_data() {
+        let layout = [attribute::<Vec3>("Position", 0, 0), attribute::<Vec4>("BoneIDs", 5, 12)];
+        let mismatches = check_attributes(&[input("boneIds", 5, gl::INT_VEC4)], &layout);
+        assert_eq!(
+            mismatches,
+            vec!["attribute 'boneIds' is ivec4 but the vertex layout provides BoneIDs as floats"]
+        );
+
+        // and the other way around, weights read from integer data
+        let layout = [attribute::<IVec4>("Weights", 6, 0)];
+        let mismatches = check_attributes(&[input("weights", 6, gl::FLOAT_VEC4)], &layout);
+        assert_eq!(
+            mismatches,
+            vec!["attribute 'weights' is vec4 but the vertex layout provides Weights as integers"]
+        );
+
+        let layout = [attribute::<IVec4>("BoneIDs", 5, 0)];
+        assert!(check_attributes(&[input("boneIds", 5, gl::INT_VEC4)], &layout).is_empty());
+    }
+
+    #[test]
+    fn missing_locations() {
+        let layout = [attribute::<Vec3>("Position", 0, 0), attribute::<Vec3>("Normal", 1, 12)];
+        let inputs = [
+            input("aPos", 0, gl::FLOAT_VEC3),
+            input("aTexCoords", 2, gl::FLOAT_VEC2),
+            input("gl_VertexID", -1, gl::INT),
+        ];
+        // the built-in has no location and is left alone
+        assert_eq!(
+            check_attributes(&inputs, &layout),
+            vec!["attribute 'aTexCoords' at location 2 is not provided by the vertex layout"]
+        );
+    }
+
+    #[test]
+    fn too_few_components() {
+        let layout = [attribute::<Vec3>("Position", 0, 0), attribute::<Vec2>("TexCoords", 2, 12)];
+        let inputs = [input("aPos", 0, gl::FLOAT_VEC4), input("aTexCoords", 2, gl::FLOAT)];
+        // a vec4 position is filled out with w = 1 but still reported, reading fewer components is fine
+        assert_eq!(
+            check_attributes(&inputs, &layout),
+            vec!["attribute 'aPos' is vec4 but the vertex layout provides Position with 3 components"]
+        );
+
+        let layout = [attribute::<f32>("Weight", 0, 0)];
+        assert_eq!(
+            check_attributes(&[input("aWeight", 0, gl::FLOAT_VEC2)], &layout),
+            vec!["attribute 'aWeight' is vec2 but the vertex layout provides Weight with 1 component"]
+        );
+    }
+
+    #[test]
+    fn mat4_takes_four_locations() {
+        let inputs = [input("aInstanceMatrix", 3, gl::FLOAT_MAT4)];
+        assert!(check_attributes(&inputs, &[attribute::<Mat4>("Model", 3, 0)]).is_empty());
+
+        // two columns given as vec4s, the last two missing
+        let layout = [attribute::<Vec4>("Column0", 3, 0), attribute::<Vec4>("Column1", 4, 16)];
+        assert_eq!(
+            check_attributes(&inputs, &layout),
+            vec![
+                "attribute 'aInstanceMatrix' at location 5 is not provided by the vertex layout",
+                "attribute 'aInstanceMatrix' at location 6 is not provided by the vertex layout",
+            ]
+        );
+
+        // a mat3 given where the shader reads a mat4 is short a location and a component per column
+        let mismatches = check_attributes(&inputs, &[attribute::<Mat3>("Model", 3, 0)]);
+        assert_eq!(mismatches.len(), 4);
+        assert_eq!(
+            mismatches[0],
+            "attribute 'aInstanceMatrix' (location 3) is mat4 but the vertex layout provides Model with 3 components"
+        );
+        assert_eq!(
+            mismatches[3],
+            "attribute 'aInstanceMatrix' at location 6 is not provided by the vertex layout"
+        );
+    }
+
+    crate::vertex_layout! {
+        #[derive(Copy, Clone)]
+        struct SkinnedInstance {
+            Model: Mat4,
+            Position: Vec3,
+            TexCoords: Vec2,
+            BoneIDs: IVec4,
+            Weight: f32,
+        }
+    }
+
+    #[test]
+    fn vertex_layout_offsets_and_locations() {
+        let attributes = SkinnedInstance::attributes();
+        let shape: Vec<(&str, GLuint, usize, GLint, GLint)> = attributes
+            .iter()
+            .map(|attribute| {
+                (
+                    attribute.name,
+                    attribute.location,
+                    attribute.offset,
+                    attribute.components,
+                    attribute.columns,
+                )
+            })
+            .collect();
+        // the matrix columns take locations 0 to 3, the fields after it follow from 4
+        assert_eq!(
+            shape,
+            vec![
+                ("Model", 0, 0, 4, 4),
+                ("Position", 4, 64, 3, 1),
+                ("TexCoords", 5, 76, 2, 1),
+                ("BoneIDs", 6, 84, 4, 1),
+                ("Weight", 7, 100, 1, 1),
+            ]
+        );
+        assert_eq!(SkinnedInstance::stride(), std::mem::size_of::<SkinnedInstance>());
+        assert!(SkinnedInstance::stride() >= 104);
+        let integers: Vec<&str> = attributes
+            .iter()
+            .filter(|attribute| attribute.integer)
+            .map(|attribute| attribute.name)
+            .collect();
+        assert_eq!(integers, vec!["BoneIDs"]);
+        assert_eq!(attributes[3].data_type, gl::INT);
+        assert!(attributes[0].covers(3) && !attributes[0].covers(4));
+        assert!(attributes[0].overlaps(&attribute::<Vec4>("Column3", 3, 0)));
+
+        let vertex = SkinnedInstance {
+            Model: Mat4::IDENTITY,
+            Position: vec3(1.0, 2.0, 3.0),
+            TexCoords: Vec2::ZERO,
+            BoneIDs: IVec4::ZERO,
+            Weight: 0.0,
+        };
+        // found by name, although it isn't at location 0
+        assert_eq!(vertex.position(), vec3(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn in_field_order_skips_matrix_columns() {
+        let attributes = in_field_order([
+            attribute::<Mat3>("Normal", 9, 0),
+            attribute::<Mat2>("Uv", 9, 0),
+            attribute::<u32>("Id", 9, 0),
+        ]);
+        let locations: Vec<GLuint> = attributes.iter().map(|attribute| attribute.location).collect();
+        assert_eq!(locations, vec![0, 3, 5]);
+    }
+
+    #[test]
+    fn attribute_shapes() {
+        assert_eq!(attribute_shape(gl::FLOAT_VEC2), Some((2, 1, false)));
+        assert_eq!(attribute_shape(gl::INT_VEC4), Some((4, 1, true)));
+        assert_eq!(attribute_shape(gl::UNSIGNED_INT), Some((1, 1, true)));
+        assert_eq!(attribute_shape(gl::FLOAT_MAT4), Some((4, 4, false)));
+        // a mat2x3 has 2 columns of 3 components
+        assert_eq!(attribute_shape(gl::FLOAT_MAT2x3), Some((3, 2, false)));
+        assert_eq!(attribute_shape(gl::DOUBLE), None);
+    }
+
+    #[test]
+    fn model_vertex_attributes() {
+        use crate::mesh::{Vertex, VERTEX_ATTRIBUTES};
+
+        let find = |name: &str| VERTEX_ATTRIBUTES.iter().find(|attribute| attribute.name == name).unwrap();
+        assert_eq!(find("TexCoords").components, 2);
+        assert!(find("m_BoneIDs").integer);
+        assert_eq!(find("m_BoneIDs").data_type, gl::INT);
+        assert!(!find("m_Weights").integer);
+
+        // Vertex is packed, so the offsets follow each other without padding
+        let offsets: Vec<usize> = VERTEX_ATTRIBUTES.iter().map(|attribute| attribute.offset).collect();
+        assert_eq!(offsets, vec![0, 12, 24, 32, 44, 56, 72]);
+        assert_eq!(Vertex::stride(), 88);
+
+        // the inputs of the skinning shaders are fed without a mismatch
+        let inputs = [
+            input("aPos", 0, gl::FLOAT_VEC3),
+            input("aNormal", 1, gl::FLOAT_VEC3),
+            input("aTexCoords", 2, gl::FLOAT_VEC2),
+            input("boneIds", 5, gl::INT_VEC4),
+            input("weights", 6, gl::FLOAT_VEC4),
+        ];
+        assert!(check_attributes(&inputs, &VERTEX_ATTRIBUTES).is_empty());
+    }
+}